@@ -0,0 +1,117 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+// Measures fanning a request out to several repositories, with every repository running
+// in-process. Besides the timings, prints the allocations made per request, which is the
+// figure to watch when changing how upstream requests are built
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use hyper::{Body, Client, Request, Response, Server, StatusCode, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use rust_maven_proxy::{Application, Repository};
+use tokio::runtime::Runtime;
+
+const POM: &str = "/org/example/example/1.0/example-1.0.pom";
+const MEASURED_REQUESTS: u64 = 1000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Only the last repository has the artifact, so every repository is asked for it
+async fn repository(has_artifact: bool) -> Repository {
+    let service_function = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |_: Request<Body>| async move {
+            let response = if has_artifact {
+                Response::new(Body::from("<project/>"))
+            } else {
+                Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap()
+            };
+            Ok::<_, Infallible>(response)
+        }))
+    });
+    let server = Server::bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).serve(service_function);
+    let address = server.local_addr();
+    tokio::spawn(server);
+    Uri::from_str(&format!("http://{}/maven2", address)).unwrap().into()
+}
+
+async fn application(repositories: usize) -> Application<hyper::client::HttpConnector> {
+    let mut upstreams = Vec::with_capacity(repositories);
+    for index in 0..repositories {
+        upstreams.push(repository(index == repositories - 1).await);
+    }
+    Application::new(Client::new(), upstreams, Duration::from_secs(5))
+}
+
+async fn fetch(application: &Application<hyper::client::HttpConnector>) {
+    let request = Request::get(POM)
+        .header("user-agent", "Apache-Maven/3.8.4")
+        .header("accept-encoding", "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = application.respond(request).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    hyper::body::to_bytes(response.into_body()).await.unwrap();
+}
+
+fn fanout(criterion: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = criterion.benchmark_group("fanout");
+    for repositories in [1, 4, 16] {
+        let application = runtime.block_on(application(repositories));
+        runtime.block_on(async {
+            // Connections are established before counting
+            fetch(&application).await;
+            let before = ALLOCATIONS.load(Ordering::Relaxed);
+            for _ in 0..MEASURED_REQUESTS {
+                fetch(&application).await;
+            }
+            let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+            println!("fanout/{}: {} allocations per request", repositories, allocations / MEASURED_REQUESTS);
+        });
+        group.bench_with_input(BenchmarkId::from_parameter(repositories), &application, |bencher, application| {
+            bencher.to_async(&runtime).iter(|| fetch(application));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fanout);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Method, StatusCode};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use crate::repository::ServedBy;
+
+// A single access log line, formatted as space-separated key=value pairs
+pub struct AccessLogEntry<'r> {
+    method: &'r Method,
+    path: &'r str,
+    status: StatusCode,
+    served_by: Option<&'r ServedBy>,
+    duration: Duration
+}
+
+impl<'r> AccessLogEntry<'r> {
+    pub fn new(method: &'r Method,
+               path: &'r str,
+               status: StatusCode,
+               served_by: Option<&'r ServedBy>,
+               duration: Duration) -> Self {
+        Self {
+            method,
+            path,
+            status,
+            served_by,
+            duration
+        }
+    }
+
+    pub fn log(&self) {
+        log::info!(target: "access", "{}", self);
+    }
+}
+
+impl Display for AccessLogEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "method={} path={} status={} ", self.method, self.path, self.status.as_u16())?;
+        match self.served_by {
+            Some(served_by) => write!(
+                f, "repository={} upstream={} ", served_by.index(), served_by.uri())?,
+            None => write!(f, "repository=none upstream=none ")?
+        }
+        write!(f, "duration_ms={}", self.duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Uri;
+
+    #[test]
+    fn format_served_entry() {
+        let served_by = ServedBy::new(1, Uri::from_static("https://repo1.maven.org/maven2"));
+        let entry = AccessLogEntry::new(
+            &Method::GET, "/org/example/example.jar", StatusCode::OK,
+            Some(&served_by), Duration::from_millis(42));
+        assert_eq!(
+            "method=GET path=/org/example/example.jar status=200 \
+            repository=1 upstream=https://repo1.maven.org/maven2 duration_ms=42",
+            entry.to_string());
+    }
+
+    #[test]
+    fn format_not_found_entry() {
+        let entry = AccessLogEntry::new(
+            &Method::HEAD, "/org/example/missing.pom", StatusCode::NOT_FOUND,
+            None, Duration::from_millis(7));
+        assert_eq!(
+            "method=HEAD path=/org/example/missing.pom status=404 \
+            repository=none upstream=none duration_ms=7",
+            entry.to_string());
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::path::PathBuf;
+use hyper::HeaderMap;
+use hyper::header::AUTHORIZATION;
+use eyre::Result;
+use crate::app::Upstreams;
+use crate::config::Config;
+
+pub const RELOAD_PATH: &str = "/admin/reload";
+pub const LOG_LEVEL_PATH: &str = "/admin/log-level";
+pub const CACHE_PATH: &str = "/admin/cache";
+pub const PATHS: &[&str] = &[RELOAD_PATH, LOG_LEVEL_PATH, CACHE_PATH];
+
+// Re-reads the configuration file on behalf of POST /admin/reload, and holds the
+// secret which all admin endpoints require
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    config_path: PathBuf,
+    secret: String,
+    port: u16
+}
+
+// The result of a successful reload, with notes on settings which were not applied
+#[derive(Debug)]
+pub struct Reloaded {
+    pub upstreams: Upstreams,
+    pub notes: Vec<String>
+}
+
+impl ConfigReload {
+    // The port is the one the server is bound to
+    pub fn new(config_path: PathBuf, secret: String, port: u16) -> Self {
+        Self {
+            config_path,
+            secret,
+            port
+        }
+    }
+
+    // Expects Authorization: Bearer <secret>
+    pub fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        headers.get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.secret.as_bytes()))
+    }
+
+    pub fn load(&self) -> Result<Reloaded> {
+        // Loading would otherwise write a default config in place of a deleted one
+        if !self.config_path.is_file() {
+            return Err(eyre::eyre!("Config {} does not exist", self.config_path.display()));
+        }
+        let config = Config::load_from(&self.config_path)?;
+        config.validate()?;
+        let upstreams = Upstreams::new(config.repositories()?, config.proxy_timeout())
+            .with_fallback_repositories(config.fallback_repositories()?)
+            .with_groups(config.groups()?)
+            .with_repository_rules(config.repository_rules())
+            .with_local_repositories(config.local_repositories());
+        let mut notes = Vec::new();
+        if config.port() != self.port {
+            notes.push(format!("The port change from {} to {} requires a restart", self.port, config.port()));
+        }
+        Ok(Reloaded {
+            upstreams,
+            notes
+        })
+    }
+}
+
+// What DELETE /admin/cache removes, from either ?path=<path> or ?confirm=true for everything
+#[derive(Debug, PartialEq, Eq)]
+pub enum CachePurge {
+    Path(String),
+    All
+}
+
+impl CachePurge {
+    pub fn from_query(query: Option<&str>) -> Option<Self> {
+        let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query?.as_bytes())
+            .into_owned()
+            .collect();
+        if let Some((_, path)) = pairs.iter().find(|(key, _)| key == "path") {
+            // Cache keys are absolute paths
+            let path = if path.starts_with('/') {
+                path.clone()
+            } else {
+                format!("/{}", path)
+            };
+            return Some(Self::Path(path));
+        }
+        pairs.iter()
+            .any(|(key, value)| key == "confirm" && value == "true")
+            .then_some(Self::All)
+    }
+}
+
+// Compares without returning early, so the time taken does not reveal the secret
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len() && left.iter()
+        .zip(right)
+        .fold(0, |difference, (left, right)| difference | (left ^ right)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+    use std::io::Write;
+
+    fn reload_for(config: &str) -> Result<(tempfile::NamedTempFile, ConfigReload)> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(config.as_bytes())?;
+        let reload = ConfigReload::new(file.path().to_owned(), "secret".to_owned(), 8080);
+        Ok((file, reload))
+    }
+
+    #[test]
+    fn authorization() -> Result<()> {
+        let (_file, reload) = reload_for("()")?;
+        let mut headers = HeaderMap::new();
+        assert!(!reload.is_authorized(&headers));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secrets"));
+        assert!(!reload.is_authorized(&headers));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic secret"));
+        assert!(!reload.is_authorized(&headers));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        assert!(reload.is_authorized(&headers));
+        Ok(())
+    }
+
+    #[test]
+    fn port_change_requires_restart() -> Result<()> {
+        let (_file, reload) = reload_for(r#"(port: 9090, repositories: ["https://repo.example.com/releases"])"#)?;
+        let reloaded = reload.load()?;
+        assert_eq!(1, reloaded.upstreams.repositories().len());
+        assert_eq!(vec!["The port change from 8080 to 9090 requires a restart".to_owned()], reloaded.notes);
+        Ok(())
+    }
+
+    #[test]
+    fn cache_purge_query() {
+        let path = "/org/example/1.0/example-1.0.jar";
+        assert_eq!(Some(CachePurge::Path(path.to_owned())), CachePurge::from_query(Some(&format!("path={}", path))));
+        assert_eq!(Some(CachePurge::Path(path.to_owned())), CachePurge::from_query(Some("path=org%2Fexample%2F1.0%2Fexample-1.0.jar")));
+        assert_eq!(Some(CachePurge::All), CachePurge::from_query(Some("confirm=true")));
+        assert_eq!(None, CachePurge::from_query(Some("confirm=yes")));
+        assert_eq!(None, CachePurge::from_query(None));
+    }
+
+    #[test]
+    fn invalid_config() -> Result<()> {
+        let (_file, reload) = reload_for("(repositories: [])")?;
+        reload.load().expect_err("No repositories");
+        let missing = ConfigReload::new(PathBuf::from("/nonexistent/config.ron"), "secret".to_owned(), 8080);
+        missing.load().expect_err("Missing config");
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::SystemTime;
+use hyper::{Body, Response};
+use hyper::body::Bytes;
+use crate::body_observer::{self, BodyObserver};
+use crate::logging;
+use crate::repository::ServedBy;
+
+// Appends a line for every artifact fully sent to a client. Lines are written and flushed by
+// a dedicated thread, so request handling never waits on the file. The file is only ever
+// appended to, so it may be rotated by copying and truncating it
+#[derive(Debug)]
+pub struct AuditLog {
+    sender: Mutex<Sender<String>>
+}
+
+// A single audit line, formatted as space-separated key=value pairs
+pub struct AuditEntry<'e> {
+    time: SystemTime,
+    client: Option<IpAddr>,
+    path: &'e str,
+    upstream: Option<&'e str>,
+    bytes: u64
+}
+
+impl<'e> AuditEntry<'e> {
+    pub fn new(time: SystemTime,
+               client: Option<IpAddr>,
+               path: &'e str,
+               upstream: Option<&'e str>,
+               bytes: u64) -> Self {
+        Self {
+            time,
+            client,
+            path,
+            upstream,
+            bytes
+        }
+    }
+}
+
+impl Display for AuditEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timestamp={} ", logging::format_timestamp(self.time))?;
+        match self.client {
+            Some(client) => write!(f, "client={} ", client)?,
+            None => write!(f, "client=unknown ")?
+        }
+        write!(f, "path={} upstream={} bytes={}", self.path, self.upstream.unwrap_or("none"), self.bytes)
+    }
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("audit-log".to_owned())
+            .spawn(move || write_lines(file, receiver))?;
+        Ok(Self {
+            sender: Mutex::new(sender)
+        })
+    }
+
+    // Records the response once its whole body has been sent. Bodies which fail or which
+    // the client abandons are not recorded
+    pub fn watch(&self, client: Option<IpAddr>, path: &str, response: Response<Body>) -> Response<Body> {
+        let upstream = response.extensions().get::<ServedBy>().map(ServedBy::redacted_url);
+        let pending = PendingEntry {
+            sender: self.sender.lock().unwrap().clone(),
+            client,
+            path: path.to_owned(),
+            upstream
+        };
+        body_observer::observe_body(response, pending)
+    }
+}
+
+struct PendingEntry {
+    sender: Sender<String>,
+    client: Option<IpAddr>,
+    path: String,
+    upstream: Option<String>
+}
+
+impl BodyObserver for PendingEntry {
+    fn data(&mut self, _data: &Bytes) {}
+
+    fn complete(&mut self, sent: u64) {
+        let entry = AuditEntry::new(SystemTime::now(), self.client, &self.path, self.upstream.as_deref(), sent);
+        // The writer only stops if the file can no longer be written, which it reports itself
+        let _ = self.sender.send(entry.to_string());
+    }
+}
+
+// Lines which arrive together are written together, then flushed
+fn write_lines(file: File, receiver: Receiver<String>) {
+    let mut writer = BufWriter::new(file);
+    while let Ok(line) = receiver.recv() {
+        let result = std::iter::once(line)
+            .chain(receiver.try_iter())
+            .try_for_each(|line| writeln!(writer, "{}", line))
+            .and_then(|()| writer.flush());
+        if let Err(error) = result {
+            log::warn!("Unable to write to the audit log: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn format_entry() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_445_412_480_000);
+        let entry = AuditEntry::new(
+            time, Some(IpAddr::from([10, 0, 0, 7])), "/org/example/1.0/example-1.0.jar",
+            Some("https://repo1.maven.org/maven2"), 42);
+        assert_eq!(
+            "timestamp=2015-10-21T07:28:00.000Z client=10.0.0.7 path=/org/example/1.0/example-1.0.jar \
+            upstream=https://repo1.maven.org/maven2 bytes=42",
+            entry.to_string());
+        let entry = AuditEntry::new(time, None, "/org/example/1.0/example-1.0.pom", None, 7);
+        assert_eq!(
+            "timestamp=2015-10-21T07:28:00.000Z client=unknown path=/org/example/1.0/example-1.0.pom \
+            upstream=none bytes=7",
+            entry.to_string());
+    }
+}
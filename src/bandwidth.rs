@@ -0,0 +1,170 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use hyper::Body;
+use hyper::body::Bytes;
+use futures_util::StreamExt;
+
+// A token bucket of bytes, shared by all responses, allowing bursts of a tenth of a second's worth
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_second: u64,
+    state: Mutex<BucketState>
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self::new_at(bytes_per_second, Instant::now())
+    }
+
+    fn new_at(bytes_per_second: u64, now: Instant) -> Self {
+        Self {
+            bytes_per_second,
+            state: Mutex::new(BucketState {
+                tokens: burst(bytes_per_second) as f64,
+                last_refill: now
+            })
+        }
+    }
+
+    fn burst(&self) -> usize {
+        burst(self.bytes_per_second)
+    }
+
+    // Takes tokens for the bytes, returning how long to wait before sending them.
+    // The bucket may go into debt, so concurrent responses queue behind each other
+    fn reserve(&self, bytes: usize) -> Duration {
+        self.reserve_at(bytes, Instant::now())
+    }
+
+    fn reserve_at(&self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_second as f64;
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(self.burst() as f64);
+        state.last_refill = now;
+        state.tokens -= bytes as f64;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / rate)
+        }
+    }
+}
+
+// Reports the size of each chunk as it is sent, and delays chunks to stay within the limit
+pub fn meter_body<F>(body: Body, limiter: Option<Arc<BandwidthLimiter>>, mut on_chunk: F) -> Body
+    where F: FnMut(usize) + Send + 'static {
+
+    // Large chunks are split so that the delay between pieces stays short
+    let piece_size = limiter.as_ref().map_or(usize::MAX, |limiter| limiter.burst());
+    let pieces = body.flat_map(move |chunk| futures_util::stream::iter(match chunk {
+        Ok(chunk) => split(chunk, piece_size).into_iter().map(Ok).collect(),
+        Err(error) => vec![Err(error)]
+    }));
+    Body::wrap_stream(pieces.then(move |piece| {
+        let delay = match &piece {
+            Ok(piece) => {
+                on_chunk(piece.len());
+                limiter.as_ref().map_or(Duration::ZERO, |limiter| limiter.reserve(piece.len()))
+            },
+            Err(_) => Duration::ZERO
+        };
+        async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            piece
+        }
+    }))
+}
+
+fn burst(bytes_per_second: u64) -> usize {
+    (bytes_per_second / 10).max(1) as usize
+}
+
+fn split(mut chunk: Bytes, piece_size: usize) -> Vec<Bytes> {
+    let mut pieces = Vec::new();
+    while chunk.len() > piece_size {
+        pieces.push(chunk.split_to(piece_size));
+    }
+    pieces.push(chunk);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use eyre::Result;
+
+    #[test]
+    fn reserve_within_burst() {
+        let now = Instant::now();
+        let limiter = BandwidthLimiter::new_at(1000, now);
+        assert_eq!(Duration::ZERO, limiter.reserve_at(100, now));
+        assert_eq!(Duration::from_millis(500), limiter.reserve_at(500, now));
+        // Later reservations wait behind the debt
+        assert_eq!(Duration::from_millis(600), limiter.reserve_at(100, now));
+        assert_eq!(Duration::from_millis(100), limiter.reserve_at(100, now + Duration::from_millis(600)));
+    }
+
+    #[test]
+    fn split_chunks() {
+        let pieces = split(Bytes::from_static(b"0123456789"), 4);
+        assert_eq!(vec![&b"0123"[..], &b"4567"[..], &b"89"[..]], pieces.iter().map(|piece| &piece[..]).collect::<Vec<_>>());
+        assert_eq!(1, split(Bytes::from_static(b"0123"), 4).len());
+    }
+
+    #[tokio::test]
+    async fn throttled_throughput() -> Result<()> {
+        let rate = 256 * 1024;
+        let size = 128 * 1024;
+        let counted = Arc::new(AtomicUsize::new(0));
+        let counter = counted.clone();
+        let limiter = Arc::new(BandwidthLimiter::new(rate));
+        let body = meter_body(Body::from(vec![7u8; size]), Some(limiter), move |bytes| {
+            counter.fetch_add(bytes, Ordering::Relaxed);
+        });
+        let started = Instant::now();
+        assert_eq!(size, hyper::body::to_bytes(body).await?.len());
+        let elapsed = started.elapsed().as_secs_f64();
+        assert_eq!(size, counted.load(Ordering::Relaxed));
+        // Only the initial burst may exceed the rate
+        let throughput = (size as f64 - (rate / 10) as f64) / elapsed;
+        assert!(throughput <= rate as f64, "{} bytes per second exceeds {}", throughput, rate);
+        assert!(elapsed < 2.0, "Took {}s", elapsed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unlimited() -> Result<()> {
+        let body = meter_body(Body::from("artifact"), None, |_| {});
+        assert_eq!("artifact", hyper::body::to_bytes(body).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Body, HeaderMap};
+use hyper::header::CONTENT_LENGTH;
+use futures_util::StreamExt;
+use crate::error::ProxyError;
+
+// Whether the declared Content-Length already exceeds the limit
+pub fn exceeds_content_length(headers: &HeaderMap, limit: u64) -> bool {
+    headers.get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|length| length > limit)
+}
+
+// Fails the body stream once more than the limit has been transferred,
+// for responses which do not declare their length up front
+pub fn limit_body(body: Body, limit: u64) -> Body {
+    let mut transferred: u64 = 0;
+    Body::wrap_stream(body.map(move |chunk| {
+        let chunk = chunk.map_err(ProxyError::from)?;
+        transferred = transferred.saturating_add(chunk.len() as u64);
+        if transferred > limit {
+            log::warn!("Aborting response exceeding the maximum artifact size of {} bytes", limit);
+            return Err(ProxyError::ArtifactTooLarge { limit });
+        }
+        Ok(chunk)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+    use eyre::Result;
+
+    fn chunked_body(chunks: &'static [&'static str]) -> Body {
+        Body::wrap_stream(futures_util::stream::iter(
+            chunks.iter().map(|chunk| Ok::<_, std::io::Error>(*chunk))))
+    }
+
+    #[test]
+    fn content_length() {
+        let mut headers = HeaderMap::new();
+        assert!(!exceeds_content_length(&headers, 10));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("10"));
+        assert!(!exceeds_content_length(&headers, 10));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("11"));
+        assert!(exceeds_content_length(&headers, 10));
+    }
+
+    #[tokio::test]
+    async fn within_limit() -> Result<()> {
+        let body = limit_body(chunked_body(&["12345", "67890"]), 10);
+        assert_eq!("1234567890", hyper::body::to_bytes(body).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streamed_overflow() {
+        let body = limit_body(chunked_body(&["12345", "67890", "X"]), 10);
+        hyper::body::to_bytes(body).await.expect_err("Body exceeds the limit");
+    }
+}
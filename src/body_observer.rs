@@ -0,0 +1,169 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Body, Response};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::CONTENT_LENGTH;
+use futures_util::StreamExt;
+
+// Told about a response body as it streams to the client
+pub trait BodyObserver: Send + 'static {
+    fn data(&mut self, data: &Bytes);
+
+    // Called once the whole body has been sent, with its length
+    fn complete(&mut self, sent: u64);
+
+    // Called if the body fails after some of it has been sent
+    fn failed(&mut self, _sent: u64) {}
+
+    // Called if the body is dropped before it ends, which happens when the client disconnects
+    fn abandoned(&mut self, _sent: u64) {}
+}
+
+// Hands each chunk of the body to the observer, then tells it once whether the body completed,
+// failed, or was abandoned by the client
+pub fn observe_body(response: Response<Body>, observer: impl BodyObserver) -> Response<Body> {
+    // The server stops reading once the announced length has been sent, so the end of the
+    // body is only seen when its length is unknown
+    let expected = response.body().size_hint().exact().or_else(|| {
+        response.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+    });
+    let mut observed = Observed {
+        observer,
+        expected,
+        sent: 0,
+        ended: false
+    };
+    response.map(|body| {
+        let ended = futures_util::stream::once(async { None });
+        Body::wrap_stream(body.map(Some).chain(ended).filter_map(move |chunk| {
+            let item = match chunk {
+                Some(Ok(data)) => {
+                    observed.data(&data);
+                    Some(Ok(data))
+                },
+                Some(Err(error)) => {
+                    observed.failed();
+                    Some(Err(error))
+                },
+                None => {
+                    observed.complete();
+                    None
+                }
+            };
+            async move { item }
+        }))
+    })
+}
+
+struct Observed<O: BodyObserver> {
+    observer: O,
+    expected: Option<u64>,
+    sent: u64,
+    // Whether the observer has been told the body completed or failed
+    ended: bool
+}
+
+impl<O: BodyObserver> Observed<O> {
+    fn data(&mut self, data: &Bytes) {
+        if self.ended {
+            return;
+        }
+        self.observer.data(data);
+        self.sent += data.len() as u64;
+        if self.expected.is_some_and(|expected| self.sent >= expected) {
+            self.complete();
+        }
+    }
+
+    fn complete(&mut self) {
+        if !std::mem::replace(&mut self.ended, true) {
+            self.observer.complete(self.sent);
+        }
+    }
+
+    fn failed(&mut self) {
+        if !std::mem::replace(&mut self.ended, true) {
+            self.observer.failed(self.sent);
+        }
+    }
+}
+
+impl<O: BodyObserver> Drop for Observed<O> {
+    fn drop(&mut self) {
+        if !self.ended {
+            self.observer.abandoned(self.sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use eyre::Result;
+
+    #[derive(Clone, Default)]
+    struct Recording {
+        events: Arc<Mutex<Vec<String>>>
+    }
+
+    impl BodyObserver for Recording {
+        fn data(&mut self, data: &Bytes) {
+            self.events.lock().unwrap().push(format!("data {}", data.len()));
+        }
+
+        fn complete(&mut self, sent: u64) {
+            self.events.lock().unwrap().push(format!("complete {}", sent));
+        }
+
+        fn failed(&mut self, sent: u64) {
+            self.events.lock().unwrap().push(format!("failed {}", sent));
+        }
+
+        fn abandoned(&mut self, sent: u64) {
+            self.events.lock().unwrap().push(format!("abandoned {}", sent));
+        }
+    }
+
+    async fn observe(body: Body) -> Vec<String> {
+        let recording = Recording::default();
+        let response = observe_body(Response::new(body), recording.clone());
+        let _ = hyper::body::to_bytes(response.into_body()).await;
+        let events = recording.events.lock().unwrap().clone();
+        events
+    }
+
+    #[tokio::test]
+    async fn completed_bodies() -> Result<()> {
+        assert_eq!(vec!["data 5", "complete 5"], observe(Body::from("hello")).await);
+        let chunks = ["he", "llo"].map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk)));
+        let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+        assert_eq!(vec!["data 2", "data 3", "complete 5"], observe(body).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_bodies() -> Result<()> {
+        let chunks = [Ok(Bytes::from("he")), Err(std::io::Error::new(std::io::ErrorKind::Other, "reset"))];
+        let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+        assert_eq!(vec!["data 2", "failed 2"], observe(body).await);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+// The part of a body a client asked for with a Range header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ByteRange {
+    // The header is malformed or asks for several ranges, so it is ignored and the
+    // whole body is sent
+    Whole,
+    // The bytes from first to last, inclusive
+    Partial {
+        first: u64,
+        last: u64
+    },
+    // The range starts beyond the end of the body
+    Unsatisfiable
+}
+
+impl ByteRange {
+    // Parses bytes=first-last, bytes=first- and bytes=-suffix against a body of the given length
+    pub fn parse(header: &str, length: u64) -> Self {
+        let spec = match header.trim().strip_prefix("bytes=") {
+            Some(spec) => spec.trim(),
+            None => return Self::Whole
+        };
+        let (first, last) = match spec.split_once('-') {
+            Some((first, last)) if !spec.contains(',') => (first.trim(), last.trim()),
+            _ => return Self::Whole
+        };
+        if first.is_empty() {
+            return match last.parse::<u64>() {
+                Ok(0) => Self::Unsatisfiable,
+                Ok(_) if length == 0 => Self::Unsatisfiable,
+                Ok(suffix) => Self::Partial {
+                    first: length.saturating_sub(suffix),
+                    last: length - 1
+                },
+                Err(_) => Self::Whole
+            };
+        }
+        let first: u64 = match first.parse() {
+            Ok(first) => first,
+            Err(_) => return Self::Whole
+        };
+        let last = match last {
+            "" => u64::MAX,
+            last => match last.parse() {
+                Ok(last) if last >= first => last,
+                _ => return Self::Whole
+            }
+        };
+        if first >= length {
+            return Self::Unsatisfiable;
+        }
+        Self::Partial {
+            first,
+            last: last.min(length - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(ByteRange::Partial { first: 0, last: 4 }, ByteRange::parse("bytes=0-4", 10));
+        assert_eq!(ByteRange::Partial { first: 6, last: 9 }, ByteRange::parse("bytes=6-", 10));
+        assert_eq!(ByteRange::Partial { first: 7, last: 9 }, ByteRange::parse("bytes=-3", 10));
+        assert_eq!(ByteRange::Partial { first: 0, last: 9 }, ByteRange::parse("bytes=-30", 10));
+        assert_eq!(ByteRange::Partial { first: 5, last: 9 }, ByteRange::parse("bytes=5-100", 10));
+        assert_eq!(ByteRange::Unsatisfiable, ByteRange::parse("bytes=10-", 10));
+        assert_eq!(ByteRange::Unsatisfiable, ByteRange::parse("bytes=-0", 10));
+        assert_eq!(ByteRange::Unsatisfiable, ByteRange::parse("bytes=-5", 0));
+        for ignored in ["bytes=0-1,4-5", "bytes=5-2", "bytes=a-b", "items=0-4", "bytes=-"] {
+            assert_eq!(ByteRange::Whole, ByteRange::parse(ignored, 10), "{}", ignored);
+        }
+    }
+}
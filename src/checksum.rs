@@ -0,0 +1,146 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use sha1::{Sha1, Digest};
+use md5::Md5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1
+}
+
+impl ChecksumAlgorithm {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => ".md5",
+            ChecksumAlgorithm::Sha1 => ".sha1"
+        }
+    }
+
+    // Splits a checksum file path into the path of the file it checksums and the algorithm
+    pub fn split_path(path: &str) -> Option<(&str, Self)> {
+        [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha1]
+            .iter()
+            .find_map(|algorithm| {
+                path.strip_suffix(algorithm.extension())
+                    .map(|checksummed_path| (checksummed_path, *algorithm))
+            })
+    }
+
+    fn hex_length(&self) -> usize {
+        match self {
+            ChecksumAlgorithm::Md5 => 32,
+            ChecksumAlgorithm::Sha1 => 40
+        }
+    }
+
+    // Reduces a checksum file to the bare lowercase hash, dropping surrounding whitespace and any
+    // file name after the hash. None if the file does not start with a hash of this algorithm
+    pub fn normalize(&self, contents: &str) -> Option<String> {
+        let hash = contents.split_whitespace().next()?;
+        let valid = hash.len() == self.hex_length() && hash.chars().all(|character| character.is_ascii_hexdigit());
+        valid.then(|| hash.to_ascii_lowercase())
+    }
+
+    pub fn hex_digest(&self, data: &[u8]) -> String {
+        match self {
+            ChecksumAlgorithm::Md5 => format!("{:x}", Md5::digest(data)),
+            ChecksumAlgorithm::Sha1 => format!("{:x}", Sha1::digest(data))
+        }
+    }
+
+    pub fn hasher(&self) -> ChecksumHasher {
+        match self {
+            ChecksumAlgorithm::Md5 => ChecksumHasher::Md5(Md5::new()),
+            ChecksumAlgorithm::Sha1 => ChecksumHasher::Sha1(Sha1::new())
+        }
+    }
+}
+
+// Hashes a file as it arrives, for files too large to be read whole
+pub enum ChecksumHasher {
+    Md5(Md5),
+    Sha1(Sha1)
+}
+
+impl ChecksumHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            ChecksumHasher::Md5(hasher) => hasher.update(data),
+            ChecksumHasher::Sha1(hasher) => hasher.update(data)
+        }
+    }
+
+    pub fn hex_digest(self) -> String {
+        match self {
+            ChecksumHasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            ChecksumHasher::Sha1(hasher) => format!("{:x}", hasher.finalize())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_checksum_path() {
+        assert_eq!(Some(("/org/example/example.jar", ChecksumAlgorithm::Sha1)),
+                   ChecksumAlgorithm::split_path("/org/example/example.jar.sha1"));
+        assert_eq!(Some(("/org/example/maven-metadata.xml", ChecksumAlgorithm::Md5)),
+                   ChecksumAlgorithm::split_path("/org/example/maven-metadata.xml.md5"));
+        assert_eq!(None, ChecksumAlgorithm::split_path("/org/example/example.jar"));
+    }
+
+    #[test]
+    fn incremental_checksums() {
+        for algorithm in [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha1] {
+            let mut hasher = algorithm.hasher();
+            hasher.update(b"hel");
+            hasher.update(b"lo");
+            assert_eq!(algorithm.hex_digest(b"hello"), hasher.hex_digest());
+        }
+    }
+
+    #[test]
+    fn normalize_checksums() {
+        const SHA1: &str = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+        for contents in [
+            SHA1,
+            "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d\n",
+            "  AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D  ",
+            "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d  example-1.0.jar\n",
+            "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d *example-1.0.jar"
+        ] {
+            assert_eq!(Some(SHA1), ChecksumAlgorithm::Sha1.normalize(contents).as_deref(), "{:?}", contents);
+        }
+        assert_eq!(Some("5d41402abc4b2a76b9719d911017c592"),
+                   ChecksumAlgorithm::Md5.normalize("5D41402ABC4B2A76B9719D911017C592 example-1.0.pom").as_deref());
+        assert_eq!(None, ChecksumAlgorithm::Sha1.normalize("5d41402abc4b2a76b9719d911017c592"));
+        assert_eq!(None, ChecksumAlgorithm::Sha1.normalize("<html>Not found</html>"));
+        assert_eq!(None, ChecksumAlgorithm::Md5.normalize(""));
+    }
+
+    #[test]
+    fn hex_digests() {
+        assert_eq!("5d41402abc4b2a76b9719d911017c592", ChecksumAlgorithm::Md5.hex_digest(b"hello"));
+        assert_eq!("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d", ChecksumAlgorithm::Sha1.hex_digest(b"hello"));
+    }
+}
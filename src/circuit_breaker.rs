@@ -0,0 +1,344 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use eyre::Result;
+
+// Skips repositories which keep failing, until a cooldown has passed
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+    probes: AtomicU64
+}
+
+#[derive(Debug)]
+enum Circuit {
+    // Failures only count as consecutive when each follows the last within the cooldown
+    Closed { failures: u32, last_failure: Instant },
+    Open { until: Instant },
+    // A single request is let through to probe whether the repository has recovered
+    HalfOpen { probe: u64 }
+}
+
+// Whether a request may be sent to a repository
+#[must_use]
+#[derive(Debug)]
+pub enum Admission<'b> {
+    Allowed,
+    // The request probes whether the repository has recovered after its cooldown
+    Probe(Probe<'b>),
+    Skipped
+}
+
+impl Admission<'_> {
+    pub fn is_allowed(&self) -> bool {
+        !matches!(self, Self::Skipped)
+    }
+}
+
+// Held while a probe is in flight. Dropping it before the probe's outcome is recorded counts
+// as a failure, since probes are cancelled when another repository answers first or the
+// client goes away, and the repository would otherwise never be probed again
+#[derive(Debug)]
+pub struct Probe<'b> {
+    breaker: &'b CircuitBreaker,
+    repository: String,
+    id: u64
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        self.breaker.abandon_probe(&self.repository, self.id, Instant::now());
+    }
+}
+
+// The open circuits, saved across restarts. Instants are only meaningful within a process,
+// so the cooldowns are saved as wall clock times
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedState {
+    open_until: BTreeMap<String, SystemTime>
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+            probes: AtomicU64::new(0)
+        }
+    }
+
+    // Whether a request may be sent to the repository. A probe must be held until the
+    // request's outcome is recorded
+    pub fn allows(&self, repository: &str) -> Admission<'_> {
+        self.allows_at(repository, Instant::now())
+    }
+
+    fn allows_at(&self, repository: &str, now: Instant) -> Admission<'_> {
+        let mut circuits = self.circuits.lock().unwrap();
+        match circuits.get_mut(repository) {
+            Some(Circuit::Open { until }) if *until <= now => {
+                log::info!("Probing repository {} after its cooldown", repository);
+                let id = self.probes.fetch_add(1, Ordering::Relaxed);
+                circuits.insert(repository.to_owned(), Circuit::HalfOpen { probe: id });
+                Admission::Probe(Probe {
+                    breaker: self,
+                    repository: repository.to_owned(),
+                    id
+                })
+            },
+            Some(Circuit::Open { .. }) | Some(Circuit::HalfOpen { .. }) => Admission::Skipped,
+            Some(Circuit::Closed { .. }) | None => Admission::Allowed
+        }
+    }
+
+    // Only the probe which is still in flight is failed, not a later probe of the same repository
+    fn abandon_probe(&self, repository: &str, id: u64, now: Instant) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(Circuit::HalfOpen { probe }) = circuits.get(repository) {
+            if *probe == id {
+                log::info!("Skipping repository {} for {:?}, since its probe ended without an answer",
+                           repository, self.cooldown);
+                circuits.insert(repository.to_owned(), Circuit::Open { until: now + self.cooldown });
+            }
+        }
+    }
+
+    // Whether the repository is being skipped, or has yet to prove it has recovered.
+    // Unlike allows, this never starts a probe
+    pub fn is_tripped(&self, repository: &str) -> bool {
+        self.is_tripped_at(repository, Instant::now())
+    }
+
+    fn is_tripped_at(&self, repository: &str, now: Instant) -> bool {
+        match self.circuits.lock().unwrap().get(repository) {
+            Some(Circuit::Open { until }) => *until > now,
+            Some(Circuit::HalfOpen { .. }) => true,
+            Some(Circuit::Closed { .. }) | None => false
+        }
+    }
+
+    pub fn record_success(&self, repository: &str) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(Circuit::HalfOpen { .. }) = circuits.remove(repository) {
+            log::info!("Repository {} has recovered", repository);
+        }
+    }
+
+    pub fn record_failure(&self, repository: &str) {
+        self.record_failure_at(repository, Instant::now())
+    }
+
+    fn record_failure_at(&self, repository: &str, now: Instant) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let failures = match circuits.get(repository) {
+            Some(Circuit::Closed { failures, last_failure }) if now - *last_failure < self.cooldown => failures + 1,
+            Some(Circuit::Open { .. }) => return,
+            Some(Circuit::HalfOpen { .. }) => self.threshold,
+            _ => 1
+        };
+        let circuit = if failures >= self.threshold {
+            log::warn!("Skipping repository {} for {:?} after {} consecutive failures",
+                       repository, self.cooldown, failures);
+            Circuit::Open { until: now + self.cooldown }
+        } else {
+            Circuit::Closed { failures, last_failure: now }
+        };
+        circuits.insert(repository.to_owned(), circuit);
+    }
+
+    // Saves which repositories are being skipped. A repository being probed is saved as due
+    // another probe, since the probe is lost with the process
+    pub fn write_state(&self, path: &Path) -> Result<()> {
+        self.write_state_at(path, Instant::now(), SystemTime::now())
+    }
+
+    fn write_state_at(&self, path: &Path, now: Instant, wall_clock: SystemTime) -> Result<()> {
+        let open_until = self.circuits.lock().unwrap()
+            .iter()
+            .filter_map(|(repository, circuit)| {
+                let remaining = match circuit {
+                    Circuit::Open { until } => until.saturating_duration_since(now),
+                    Circuit::HalfOpen { .. } => Duration::ZERO,
+                    Circuit::Closed { .. } => return None
+                };
+                Some((repository.clone(), wall_clock + remaining))
+            })
+            .collect();
+        let mut partial_path = path.as_os_str().to_owned();
+        partial_path.push(".part");
+        std::fs::write(&partial_path, serde_json::to_vec_pretty(&SavedState { open_until })?)?;
+        std::fs::rename(&partial_path, path)?;
+        Ok(())
+    }
+
+    // Skips the repositories saved by write_state for the rest of their cooldowns. Those whose
+    // cooldowns passed while the proxy was stopped are probed by their next request
+    pub fn restore_state(&self, path: &Path) -> Result<()> {
+        self.restore_state_at(path, Instant::now(), SystemTime::now())
+    }
+
+    fn restore_state_at(&self, path: &Path, now: Instant, wall_clock: SystemTime) -> Result<()> {
+        let state: SavedState = serde_json::from_slice(&std::fs::read(path)?)?;
+        let mut circuits = self.circuits.lock().unwrap();
+        for (repository, until) in state.open_until {
+            let remaining = until.duration_since(wall_clock).unwrap_or(Duration::ZERO);
+            log::info!("Skipping repository {} for {:?}, as before the restart", repository, remaining);
+            circuits.insert(repository, Circuit::Open { until: now + remaining });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPOSITORY: &str = "https://repo.example.com/maven2";
+
+    #[test]
+    fn opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        let now = Instant::now();
+        for _ in 0..2 {
+            breaker.record_failure_at(REPOSITORY, now);
+            assert!(breaker.allows_at(REPOSITORY, now).is_allowed());
+        }
+        breaker.record_failure_at(REPOSITORY, now);
+        assert!(!breaker.allows_at(REPOSITORY, now).is_allowed());
+        assert!(!breaker.allows_at(REPOSITORY, now + Duration::from_secs(29)).is_allowed());
+        assert!(breaker.allows_at("https://other.example.com/maven2", now).is_allowed());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(REPOSITORY, now);
+        breaker.record_success(REPOSITORY);
+        breaker.record_failure_at(REPOSITORY, now);
+        assert!(breaker.allows_at(REPOSITORY, now).is_allowed());
+    }
+
+    #[test]
+    fn failures_outside_window() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(REPOSITORY, now);
+        breaker.record_failure_at(REPOSITORY, now + Duration::from_secs(31));
+        assert!(breaker.allows_at(REPOSITORY, now + Duration::from_secs(31)).is_allowed());
+    }
+
+    #[test]
+    fn half_open_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(REPOSITORY, now);
+        let after_cooldown = now + Duration::from_secs(30);
+        let probe = breaker.allows_at(REPOSITORY, after_cooldown);
+        assert!(matches!(probe, Admission::Probe(_)));
+        // Only one probe is sent at a time
+        assert!(!breaker.allows_at(REPOSITORY, after_cooldown).is_allowed());
+        breaker.record_failure_at(REPOSITORY, after_cooldown);
+        drop(probe);
+        assert!(!breaker.allows_at(REPOSITORY, after_cooldown + Duration::from_secs(29)).is_allowed());
+
+        let probe = breaker.allows_at(REPOSITORY, after_cooldown + Duration::from_secs(30));
+        assert!(matches!(probe, Admission::Probe(_)));
+        breaker.record_success(REPOSITORY);
+        drop(probe);
+        assert!(matches!(breaker.allows_at(REPOSITORY, after_cooldown + Duration::from_secs(30)), Admission::Allowed));
+    }
+
+    #[test]
+    fn abandoned_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(REPOSITORY, now);
+        let probe = breaker.allows_at(REPOSITORY, now + Duration::from_secs(30));
+        assert!(breaker.is_tripped_at(REPOSITORY, now + Duration::from_secs(30)));
+        let abandoned = Instant::now();
+        drop(probe);
+        assert!(!breaker.allows_at(REPOSITORY, abandoned + Duration::from_secs(29)).is_allowed());
+        assert!(!breaker.is_tripped_at(REPOSITORY, abandoned + Duration::from_secs(31)));
+
+        // A late drop does not fail the probe which followed it
+        let stale = breaker.allows_at(REPOSITORY, abandoned + Duration::from_secs(31));
+        breaker.record_success(REPOSITORY);
+        let _probe = match breaker.allows_at(REPOSITORY, abandoned) {
+            Admission::Allowed => {
+                breaker.record_failure_at(REPOSITORY, abandoned);
+                breaker.allows_at(REPOSITORY, abandoned + Duration::from_secs(30))
+            },
+            admission => panic!("Expected a closed circuit: {:?}", admission)
+        };
+        drop(stale);
+        assert!(breaker.is_tripped_at(REPOSITORY, abandoned + Duration::from_secs(30)));
+        assert!(!breaker.allows_at(REPOSITORY, abandoned + Duration::from_secs(30)).is_allowed());
+    }
+
+    #[test]
+    fn restore_saved_state() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("circuits.json");
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        let wall_clock = SystemTime::now();
+        breaker.record_failure_at("https://probed.example.com/maven2", now);
+        let saved = now + Duration::from_secs(30);
+        let _probe = breaker.allows_at("https://probed.example.com/maven2", saved);
+        breaker.record_failure_at(REPOSITORY, saved);
+        breaker.write_state_at(&path, saved, wall_clock)?;
+
+        // Restarted ten seconds later
+        let restarted = CircuitBreaker::new(1, Duration::from_secs(30));
+        let later = Instant::now();
+        restarted.restore_state_at(&path, later, wall_clock + Duration::from_secs(10))?;
+        assert!(!restarted.allows_at(REPOSITORY, later + Duration::from_secs(19)).is_allowed());
+        assert!(restarted.allows_at(REPOSITORY, later + Duration::from_secs(20)).is_allowed());
+        // The interrupted probe is sent again
+        let probe = restarted.allows_at("https://probed.example.com/maven2", later);
+        assert!(matches!(probe, Admission::Probe(_)));
+        assert!(!restarted.allows_at("https://probed.example.com/maven2", later).is_allowed());
+        Ok(())
+    }
+
+    #[test]
+    fn tripped_until_recovered() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(!breaker.is_tripped_at(REPOSITORY, now));
+        breaker.record_failure_at(REPOSITORY, now);
+        assert!(breaker.is_tripped_at(REPOSITORY, now));
+        // Expired, but not yet probed
+        assert!(!breaker.is_tripped_at(REPOSITORY, now + Duration::from_secs(30)));
+        let _probe = breaker.allows_at(REPOSITORY, now + Duration::from_secs(30));
+        assert!(breaker.is_tripped_at(REPOSITORY, now + Duration::from_secs(30)));
+        breaker.record_success(REPOSITORY);
+        assert!(!breaker.is_tripped_at(REPOSITORY, now + Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::path::{Path, PathBuf};
+use eyre::Result;
+
+pub const CONFIG_ENV_VAR: &str = "RUST_MAVEN_PROXY_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "config.ron";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Arguments {
+    config_path: PathBuf,
+    validate_config: bool
+}
+
+impl Arguments {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(std::env::args().skip(1), std::env::var_os(CONFIG_ENV_VAR).map(PathBuf::from))
+    }
+
+    // The config path is taken from the command line, then the environment, then the default
+    fn parse<I>(args: I, env_config_path: Option<PathBuf>) -> Result<Self>
+        where I: IntoIterator<Item=String> {
+
+        let mut config_path = None;
+        let mut validate_config = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "-c" => {
+                    let value = args.next()
+                        .ok_or_else(|| eyre::eyre!("Missing value for argument {}", arg))?;
+                    config_path = Some(PathBuf::from(value));
+                },
+                "--validate-config" => validate_config = true,
+                _ => match arg.strip_prefix("--config=") {
+                    Some(value) => config_path = Some(PathBuf::from(value)),
+                    None => return Err(eyre::eyre!("Unknown argument {}", arg))
+                }
+            }
+        }
+        let config_path = config_path
+            .or(env_config_path)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+        Ok(Self {
+            config_path,
+            validate_config
+        })
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    // Whether to only check the config and exit, without starting the server
+    pub fn validate_config(&self) -> bool {
+        self.validate_config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flag_takes_precedence() -> Result<()> {
+        let env_path = Some(PathBuf::from("/etc/env.ron"));
+        for flag in [&["--config", "flag.ron"][..], &["-c", "flag.ron"], &["--config=flag.ron"]] {
+            let arguments = Arguments::parse(args(flag), env_path.clone())?;
+            assert_eq!(Path::new("flag.ron"), arguments.config_path());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn env_over_default() -> Result<()> {
+        let arguments = Arguments::parse(args(&[]), Some(PathBuf::from("/etc/env.ron")))?;
+        assert_eq!(Path::new("/etc/env.ron"), arguments.config_path());
+        Ok(())
+    }
+
+    #[test]
+    fn default_path() -> Result<()> {
+        let arguments = Arguments::parse(args(&[]), None)?;
+        assert_eq!(Path::new("config.ron"), arguments.config_path());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_config_flag() -> Result<()> {
+        assert!(!Arguments::parse(args(&[]), None)?.validate_config());
+        let arguments = Arguments::parse(args(&["--validate-config", "-c", "flag.ron"]), None)?;
+        assert!(arguments.validate_config());
+        assert_eq!(Path::new("flag.ron"), arguments.config_path());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        Arguments::parse(args(&["--config"]), None).expect_err("Missing value");
+        Arguments::parse(args(&["--unknown"]), None).expect_err("Unknown argument");
+    }
+}
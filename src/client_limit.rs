@@ -0,0 +1,102 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use hyper::{Body, Response};
+use futures_util::StreamExt;
+
+// Caps the requests each client may have in flight at once, so that a single client opening
+// many downloads cannot take over the proxy
+#[derive(Debug)]
+pub struct ClientLimit {
+    max_in_flight: usize,
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>
+}
+
+// Counts a request against its client until dropped
+#[derive(Debug)]
+pub struct ClientPermit {
+    client: IpAddr,
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>
+}
+
+impl ClientLimit {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            in_flight: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    // None if the client already has as many requests in flight as allowed
+    pub fn try_acquire(&self, client: IpAddr) -> Option<ClientPermit> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(client).or_insert(0);
+        if *count >= self.max_in_flight {
+            return None;
+        }
+        *count += 1;
+        Some(ClientPermit {
+            client,
+            in_flight: self.in_flight.clone()
+        })
+    }
+}
+
+impl ClientPermit {
+    // The request stays in flight until its body has been sent or abandoned
+    pub fn hold_until_sent(self, response: Response<Body>) -> Response<Body> {
+        response.map(|body| Body::wrap_stream(body.map(move |chunk| {
+            let _permit = &self;
+            chunk
+        })))
+    }
+}
+
+impl Drop for ClientPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.client) {
+            *count -= 1;
+            // Clients are forgotten once idle, so the map only holds active clients
+            if *count == 0 {
+                in_flight.remove(&self.client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_per_client() {
+        let limit = ClientLimit::new(2);
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let first = limit.try_acquire(client).expect("Within the limit");
+        let _second = limit.try_acquire(client).expect("Within the limit");
+        assert!(limit.try_acquire(client).is_none());
+        assert!(limit.try_acquire(IpAddr::from([10, 0, 0, 2])).is_some());
+        drop(first);
+        assert!(limit.try_acquire(client).is_some());
+    }
+}
@@ -0,0 +1,180 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use hyper::{Body, HeaderMap, Response, StatusCode, http};
+use hyper::body::Bytes;
+use hyper::header::{AUTHORIZATION, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, RANGE};
+use tokio::sync::watch;
+use eyre::Result;
+use crate::checksum::ChecksumAlgorithm;
+use crate::metadata;
+use crate::repository::ServedBy;
+
+// A response read into memory, so that it can be handed to every waiting request
+#[derive(Debug, Clone)]
+pub(crate) struct SharedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    served_by: Option<ServedBy>,
+    body: Bytes
+}
+
+impl SharedResponse {
+    pub(crate) async fn read(response: Response<Body>) -> Result<Self> {
+        let (parts, body) = response.into_parts();
+        Ok(Self {
+            status: parts.status,
+            headers: parts.headers,
+            served_by: parts.extensions.get::<ServedBy>().cloned(),
+            body: hyper::body::to_bytes(body).await?
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.body.len()
+    }
+
+    pub(crate) fn to_response(&self, version: http::version::Version) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.version_mut() = version;
+        *response.headers_mut() = self.headers.clone();
+        if let Some(served_by) = &self.served_by {
+            response.extensions_mut().insert(served_by.clone());
+        }
+        response
+    }
+}
+
+// Lets concurrent requests for the same small file share a single upstream fetch
+#[derive(Debug, Default)]
+pub struct Coalescer {
+    in_flight: Mutex<HashMap<String, watch::Receiver<Option<SharedResponse>>>>
+}
+
+// Metadata and checksums are small enough to hold in memory. Requests whose response
+// depends on their own headers are never shared
+pub fn is_coalescable(path: &str, headers: &HeaderMap) -> bool {
+    let small = metadata::is_metadata_path(path) || ChecksumAlgorithm::split_path(path).is_some();
+    small && !is_personalized(headers)
+}
+
+// Whether the response to a request may differ from the response to an otherwise identical one
+pub fn is_personalized(headers: &HeaderMap) -> bool {
+    headers.contains_key(RANGE) || is_personalized_besides_range(headers)
+}
+
+// As is_personalized, for requests whose range is answered separately, such as from the disk cache
+pub fn is_personalized_besides_range(headers: &HeaderMap) -> bool {
+    [AUTHORIZATION, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE]
+        .iter()
+        .any(|header| headers.contains_key(header))
+}
+
+// Removes the in-flight entry once the leading request finishes or is dropped
+struct InFlightEntry<'a> {
+    coalescer: &'a Coalescer,
+    key: &'a str
+}
+
+impl Drop for InFlightEntry<'_> {
+    fn drop(&mut self) {
+        self.coalescer.in_flight.lock().unwrap().remove(self.key);
+    }
+}
+
+impl Coalescer {
+    // Runs fetch unless an identical request is already in flight, in which case its
+    // response is shared. If that request fails or is abandoned, fetch is run instead
+    pub async fn coalesce<F, Fut>(&self,
+                                  key: &str,
+                                  version: http::version::Version,
+                                  fetch: F) -> Result<Response<Body>>
+        where F: FnOnce() -> Fut, Fut: Future<Output=Result<Response<Body>>> {
+
+        let existing = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(key) {
+                Some(receiver) => Err(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    in_flight.insert(key.to_owned(), receiver);
+                    Ok(sender)
+                }
+            }
+        };
+        let sender = match existing {
+            Ok(sender) => sender,
+            Err(mut receiver) => {
+                while receiver.changed().await.is_ok() {
+                    if let Some(shared) = &*receiver.borrow() {
+                        log::trace!("Sharing the in-flight response for {}", key);
+                        return Ok(shared.to_response(version));
+                    }
+                }
+                log::trace!("In-flight request for {} failed, fetching separately", key);
+                return fetch().await;
+            }
+        };
+        let _entry = InFlightEntry { coalescer: self, key };
+        let shared = SharedResponse::read(fetch().await?).await?;
+        // Waiting requests may all have been abandoned
+        let _ = sender.send(Some(shared.clone()));
+        Ok(shared.to_response(version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn coalescable_paths() {
+        let headers = HeaderMap::new();
+        assert!(is_coalescable("/org/example/example/maven-metadata.xml", &headers));
+        assert!(is_coalescable("/org/example/1.0/example-1.0.jar.sha1", &headers));
+        assert!(!is_coalescable("/org/example/1.0/example-1.0.jar", &headers));
+        let mut conditional = HeaderMap::new();
+        conditional.insert(IF_NONE_MATCH, HeaderValue::from_static("\"etag\""));
+        assert!(!is_coalescable("/org/example/example/maven-metadata.xml", &conditional));
+    }
+
+    #[tokio::test]
+    async fn abandoned_leader() -> Result<()> {
+        let coalescer = Coalescer::default();
+        let version = http::version::Version::HTTP_11;
+        let leader = coalescer.coalesce("/path", version, || async {
+            // Lets the follower join before failing
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            Err(eyre::eyre!("Upstream failed"))
+        });
+        let follower = coalescer.coalesce("/path", version, || async {
+            Ok(Response::new(Body::from("separate")))
+        });
+        let (leader, follower) = tokio::join!(leader, follower);
+        assert!(leader.is_err());
+        assert_eq!("separate", hyper::body::to_bytes(follower?.into_body()).await?);
+        assert!(coalescer.in_flight.lock().unwrap().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use hyper::{Body, HeaderMap, Response, StatusCode, http};
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+                    IF_RANGE, LAST_MODIFIED, RANGE, WARNING};
+use crate::byte_range::ByteRange;
+
+// A copy of an artifact kept by the proxy, along with the headers it was served with
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    headers: HeaderMap,
+    body: Bytes
+}
+
+impl StoredResponse {
+    pub fn new(headers: HeaderMap, body: Bytes) -> Self {
+        Self {
+            headers,
+            body
+        }
+    }
+
+    // Serves the copy without revalidating it
+    pub fn into_response(self, version: http::version::Version) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body));
+        *response.version_mut() = version;
+        *response.headers_mut() = self.headers;
+        response
+    }
+
+    // Serves the copy without revalidating it, marked as possibly out of date
+    pub fn into_stale_response(self, version: http::version::Version) -> Response<Body> {
+        let mut response = self.into_response(version);
+        response.headers_mut().insert(WARNING, HeaderValue::from_static("110 - \"Response is Stale\""));
+        response
+    }
+
+    // Serves the part of the copy asked for by the client's Range header
+    pub fn into_range_response(self,
+                               version: http::version::Version,
+                               range: &HeaderValue,
+                               if_range: Option<&HeaderValue>) -> Response<Body> {
+        let length = self.body.len() as u64;
+        let range = requested_range(&self.headers, length, range, if_range);
+        let body = match range {
+            ByteRange::Whole => self.body,
+            ByteRange::Partial { first, last } => self.body.slice(first as usize..=last as usize),
+            ByteRange::Unsatisfiable => Bytes::new()
+        };
+        StoredRange::new(self.headers, range, Body::from(body), length).into_response(version)
+    }
+}
+
+// The part of a stored copy of the given length asked for by the client's Range header. The range
+// is ignored, and the whole copy sent, when If-Range names a different version than the one stored
+pub fn requested_range(headers: &HeaderMap, length: u64, range: &HeaderValue, if_range: Option<&HeaderValue>) -> ByteRange {
+    let current = if_range.map_or(true, |if_range| {
+        [ETAG, LAST_MODIFIED].iter().any(|validator| headers.get(validator) == Some(if_range))
+    });
+    match range.to_str() {
+        Ok(range) if current => ByteRange::parse(range, length),
+        _ => ByteRange::Whole
+    }
+}
+
+// Part of a stored copy, which may be streamed without reading the rest of its body
+#[derive(Debug)]
+pub struct StoredRange {
+    headers: HeaderMap,
+    range: ByteRange,
+    // Only the bytes within the range, or the whole body if the range is ignored
+    body: Body,
+    // The length of the whole copy
+    length: u64
+}
+
+impl StoredRange {
+    pub fn new(headers: HeaderMap, range: ByteRange, body: Body, length: u64) -> Self {
+        Self {
+            headers,
+            range,
+            body,
+            length
+        }
+    }
+
+    pub fn into_response(self, version: http::version::Version) -> Response<Body> {
+        let length = self.length;
+        let (status, content_range) = match self.range {
+            ByteRange::Whole => (StatusCode::OK, None),
+            ByteRange::Partial { first, last } => (StatusCode::PARTIAL_CONTENT, Some(format!("bytes {}-{}/{}", first, last, length))),
+            ByteRange::Unsatisfiable => (StatusCode::RANGE_NOT_SATISFIABLE, Some(format!("bytes */{}", length)))
+        };
+        let mut response = Response::new(self.body);
+        *response.version_mut() = version;
+        *response.status_mut() = status;
+        *response.headers_mut() = self.headers;
+        if let Some(content_range) = content_range {
+            response.headers_mut().insert(CONTENT_RANGE, HeaderValue::from_str(&content_range).unwrap());
+        }
+        // The stored length describes the whole copy, whereas a streamed range has no length of its own
+        match self.range {
+            ByteRange::Partial { first, last } => {
+                response.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(last - first + 1));
+            },
+            _ => {
+                response.headers_mut().remove(CONTENT_LENGTH);
+            }
+        }
+        response.headers_mut().insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        response
+    }
+}
+
+// Somewhere stored responses are kept, by path
+pub trait ResponseStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<StoredResponse>;
+}
+
+// A simple store held entirely in memory
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    responses: Mutex<HashMap<String, StoredResponse>>
+}
+
+impl MemoryStore {
+    pub fn insert(&self, key: &str, response: StoredResponse) {
+        self.responses.lock().unwrap().insert(key.to_owned(), response);
+    }
+}
+
+impl ResponseStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<StoredResponse> {
+        self.responses.lock().unwrap().get(key).cloned()
+    }
+}
+
+// Revalidates a stored response with an upstream repository, so that the stored
+// copy can be served if the upstream answers 304 Not Modified
+#[derive(Debug)]
+pub struct ConditionalGet {
+    stored: StoredResponse
+}
+
+impl ConditionalGet {
+    // Only responses with a Last-Modified or ETag validator can be revalidated
+    pub fn new(stored: StoredResponse) -> Option<Self> {
+        let has_validator = stored.headers.contains_key(LAST_MODIFIED) || stored.headers.contains_key(ETAG);
+        has_validator.then_some(Self {
+            stored
+        })
+    }
+
+    // Whether the client's own request is conditional or partial, in which case
+    // its headers must be forwarded unchanged rather than replaced
+    pub fn is_client_conditional(request_headers: &HeaderMap) -> bool {
+        [IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE]
+            .iter()
+            .any(|header| request_headers.contains_key(header))
+    }
+
+    // Attaches the stored validators to an outbound request
+    pub fn apply(&self, request_headers: &mut HeaderMap) {
+        if let Some(last_modified) = self.stored.headers.get(LAST_MODIFIED) {
+            request_headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        if let Some(etag) = self.stored.headers.get(ETAG) {
+            request_headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+    }
+
+    // Turns a 304 into the stored response, updated with the headers of the 304.
+    // Any other response is returned as is
+    pub fn resolve(self, response: Response<Body>) -> Response<Body> {
+        if response.status() != StatusCode::NOT_MODIFIED {
+            return response;
+        }
+        let (mut parts, _) = response.into_parts();
+        let mut headers = self.stored.headers;
+        parts.headers.remove(CONTENT_LENGTH);
+        for name in parts.headers.keys() {
+            headers.remove(name);
+        }
+        headers.extend(parts.headers);
+        parts.headers = headers;
+        parts.status = StatusCode::OK;
+        Response::from_parts(parts, Body::from(self.stored.body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use hyper::header::{CONTENT_TYPE, DATE};
+
+    const LAST_MODIFIED_DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+    fn stored() -> StoredResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(LAST_MODIFIED, LAST_MODIFIED_DATE.parse().unwrap());
+        headers.insert(CONTENT_TYPE, "application/java-archive".parse().unwrap());
+        headers.insert(CONTENT_LENGTH, "6".parse().unwrap());
+        headers.insert(DATE, "Wed, 21 Oct 2015 07:30:00 GMT".parse().unwrap());
+        StoredResponse::new(headers, Bytes::from_static(b"stored"))
+    }
+
+    #[test]
+    fn requires_validator() {
+        assert!(ConditionalGet::new(StoredResponse::new(HeaderMap::new(), Bytes::new())).is_none());
+        assert!(ConditionalGet::new(stored()).is_some());
+    }
+
+    #[test]
+    fn apply_validators() {
+        let mut headers = HeaderMap::new();
+        ConditionalGet::new(stored()).unwrap().apply(&mut headers);
+        assert_eq!(LAST_MODIFIED_DATE, headers[IF_MODIFIED_SINCE]);
+        assert!(!headers.contains_key(IF_NONE_MATCH));
+    }
+
+    #[test]
+    fn client_conditional() {
+        let mut headers = HeaderMap::new();
+        assert!(!ConditionalGet::is_client_conditional(&headers));
+        headers.insert(RANGE, "bytes=0-10".parse().unwrap());
+        assert!(ConditionalGet::is_client_conditional(&headers));
+    }
+
+    #[tokio::test]
+    async fn range_of_stored() -> Result<()> {
+        let range = HeaderValue::from_static("bytes=1-3");
+        let response = stored().into_range_response(http::version::Version::HTTP_11, &range, None);
+        assert_eq!(StatusCode::PARTIAL_CONTENT, response.status());
+        assert_eq!("bytes 1-3/6", response.headers()[CONTENT_RANGE]);
+        assert_eq!("tor", hyper::body::to_bytes(response.into_body()).await?);
+
+        // A different version than the one stored is sent whole
+        let if_range = HeaderValue::from_static("Thu, 22 Oct 2015 07:28:00 GMT");
+        let response = stored().into_range_response(http::version::Version::HTTP_11, &range, Some(&if_range));
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("stored", hyper::body::to_bytes(response.into_body()).await?);
+        let if_range = HeaderValue::from_static(LAST_MODIFIED_DATE);
+        let response = stored().into_range_response(http::version::Version::HTTP_11, &range, Some(&if_range));
+        assert_eq!(StatusCode::PARTIAL_CONTENT, response.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_modified_serves_stored() -> Result<()> {
+        let not_modified = Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(DATE, "Thu, 22 Oct 2015 07:28:00 GMT")
+            .body(Body::empty())?;
+        let response = ConditionalGet::new(stored()).unwrap().resolve(not_modified);
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("application/java-archive", response.headers()[CONTENT_TYPE]);
+        assert_eq!("6", response.headers()[CONTENT_LENGTH]);
+        assert_eq!("Thu, 22 Oct 2015 07:28:00 GMT", response.headers()[DATE]);
+        assert_eq!("stored", hyper::body::to_bytes(response.into_body()).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn modified_passes_through() -> Result<()> {
+        let modified = Response::new(Body::from("fresh"));
+        let response = ConditionalGet::new(stored()).unwrap().resolve(modified);
+        assert_eq!("fresh", hyper::body::to_bytes(response.into_body()).await?);
+        Ok(())
+    }
+}
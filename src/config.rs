@@ -17,67 +17,1647 @@
  * and navigate to version 3 of the GNU Affero General Public License.
  */
 
+use std::collections::{BTreeMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::path::Path;
-use ron::de::from_reader;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
-use hyper::Uri;
+use hyper::{StatusCode, Uri};
 use std::str::FromStr;
-use std::io::BufReader;
+use std::io::{BufReader, ErrorKind, Read, Write};
 use ron::ser::to_writer_pretty;
 use url::Url;
+use std::sync::Arc;
 use std::time::Duration;
+use std::net::{IpAddr, Ipv4Addr};
+use serde::Deserializer;
+use crate::repository::{Repository, RepositoryGroup};
+use crate::repository_index::RepositoryIndex;
+use crate::error::ProxyError;
+use crate::logging::LogFormat;
+use crate::rules::{GroupAllowlist, PathPattern, RepositoryRule};
+use crate::headers::{CacheControlPolicy, RequestHeaderRules, ResponseHeaderPolicy};
+use crate::listener::ListenerOptions;
+use crate::local_repository::LocalRepository;
+use crate::disk_cache::EvictionLimits;
+use crate::cors::CorsPolicy;
+use crate::forwarded::{Cidr, TrustedProxies};
+use crate::memory_cache::MemoryCache;
+use crate::pages::{self, StaticResponses};
+use crate::retry::DEFAULT_RETRY_STATUSES;
+use crate::rewrite::PathRewrite;
+use crate::status_policy::StatusPolicy;
+use hyper::header::{HeaderName, HeaderValue};
 
 #[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
     port: u16,
-    repositories: Vec<Url>,
+    bind_address: IpAddr,
+    dual_stack: bool,
+    listen_backlog: u32,
+    reuse_address: bool,
+    tcp_nodelay: bool,
+    http1_keepalive: bool,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    server_idle_timeout: Option<Duration>,
+    multi_thread_runtime: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worker_threads: Option<usize>,
+    #[serde(deserialize_with = "deserialize_repositories")]
+    repositories: Vec<RepositoryConfig>,
+    #[serde(deserialize_with = "deserialize_repositories", skip_serializing_if = "Vec::is_empty")]
+    fallback_repositories: Vec<RepositoryConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<RepositoryGroupConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repository_rules: Vec<RepositoryRuleConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repository_weights: Vec<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    local_repositories: Vec<PathBuf>,
+    allow_file_repositories: bool,
+    // Permits starting without remote repositories, such as when serving only local repositories
+    allow_empty_repositories: bool,
+    reject_duplicate_repositories: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allowed_group_prefixes: Vec<String>,
     log_level: log::Level,
+    log_format: LogFormat,
     #[serde(with = "DurationSerializable")]
-    proxy_timeout: Duration
+    proxy_timeout: Duration,
+    #[serde(with = "DurationSerializable")]
+    connect_timeout: Duration,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    metadata_timeout: Option<Duration>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    artifact_timeout: Option<Duration>,
+    #[serde(with = "DurationSerializable")]
+    timeout_jitter: Duration,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    request_deadline: Option<Duration>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    stream_idle_timeout: Option<Duration>,
+    max_retries: u32,
+    #[serde(with = "DurationSerializable")]
+    retry_backoff: Duration,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    retry_initial_timeout: Option<Duration>,
+    retry_timeout_multiplier: u32,
+    retry_statuses: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_budget_per_second: Option<u32>,
+    success_statuses: Vec<u16>,
+    not_found_statuses: Vec<u16>,
+    max_redirects: u32,
+    follow_cross_host_redirects: bool,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    redirect_cache_ttl: Option<Duration>,
+    prefer_order: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_fanout: Option<usize>,
+    snapshot_freshness: bool,
+    resolve_snapshot_versions: bool,
+    startup_check: bool,
+    startup_check_strict: bool,
+    max_concurrent_upstream: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_connections: Option<usize>,
+    upstream_http2: bool,
+    upstream_http1_fallback: bool,
+    remember_http1_fallback: bool,
+    #[serde(with = "DurationSerializable")]
+    pool_idle_timeout: Duration,
+    pool_max_idle_per_host: usize,
+    #[serde(with = "DurationSerializable")]
+    negative_cache_ttl: Duration,
+    #[serde(with = "DurationSerializable")]
+    shutdown_timeout: Duration,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    warmup_interval: Option<Duration>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    repository_index_reload_interval: Option<Duration>,
+    expose_served_by: bool,
+    reject_query_strings: bool,
+    reject_request_bodies: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cors_allow_origin: Option<String>,
+    allow_directory_listing: bool,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    release_max_age: Option<Duration>,
+    #[serde(with = "DurationSerializable")]
+    snapshot_max_age: Duration,
+    override_cache_control: bool,
+    compress_responses: bool,
+    stats_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_snapshot_path: Option<PathBuf>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    distinct_paths_window: Option<Duration>,
+    top_clients_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant_header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log_path: Option<PathBuf>,
+    log_response_hashes: bool,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    slow_request_threshold: Option<Duration>,
+    tracing_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracing_endpoint: Option<Url>,
+    debug_endpoint_enabled: bool,
+    resolve_endpoint_enabled: bool,
+    allow_repository_pinning: bool,
+    normalize_slashes: bool,
+    validate_coordinates: bool,
+    detect_metadata_divergence: bool,
+    synthesize_checksums: bool,
+    prefetch_checksums: bool,
+    normalize_checksum_responses: bool,
+    serve_stale_on_error: bool,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    recheck_404_after: Option<Duration>,
+    verbose_not_found: bool,
+    offline_mode: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    circuit_breaker_threshold: Option<u32>,
+    #[serde(with = "DurationSerializable")]
+    circuit_breaker_cooldown: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    persist_repository_state_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readiness_min_healthy_repositories: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_cache_capacity: Option<usize>,
+    memory_cache_max_file_size: usize,
+    #[serde(with = "DurationSerializable")]
+    memory_cache_ttl: Duration,
+    #[serde(with = "DurationSerializable")]
+    memory_cache_snapshot_ttl: Duration,
+    head_from_cache: bool,
+    ranges_from_cache: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disk_cache_directory: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_max_bytes: Option<u64>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    cache_max_age: Option<Duration>,
+    #[serde(with = "DurationSerializable")]
+    cache_eviction_interval: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limit_per_second: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_inflight_per_client: Option<usize>,
+    trust_forwarded_headers: bool,
+    // Addresses or networks such as 10.0.0.0/8
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    trusted_proxy_cidrs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_bandwidth_bytes_per_sec: Option<u64>,
+    max_header_count: usize,
+    max_header_bytes: usize,
+    max_path_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_artifact_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    buffer_threshold_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_proxy: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    https_proxy: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ca_bundle: Option<PathBuf>,
+    insecure_skip_tls_verify: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    publish_repository: Option<RepositoryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    strip_request_headers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    set_request_headers: Vec<(String, String)>,
+    response_header_policy: ResponseHeaderPolicyConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    path_rewrites: Vec<(String, String)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_cert_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_key_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    favicon_path: Option<PathBuf>,
+    static_responses: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_page_template: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_secret: Option<String>
+}
+
+impl Config {
+    // Starts from the default configuration
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Self::load_default()
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    // The address to listen on, such as :: for all IPv6 and IPv4 interfaces
+    pub fn bind_address(&self) -> IpAddr {
+        self.bind_address
+    }
+
+    // Whether binding an IPv6 address also accepts IPv4 clients
+    pub fn dual_stack(&self) -> bool {
+        self.dual_stack
+    }
+
+    // The listen backlog, SO_REUSEADDR on Unix, and TCP_NODELAY for accepted connections
+    pub fn listener_options(&self) -> ListenerOptions {
+        ListenerOptions::new(self.listen_backlog, self.reuse_address, self.tcp_nodelay)
+    }
+
+    // Whether client connections are kept open for further requests
+    pub fn http1_keepalive(&self) -> bool {
+        self.http1_keepalive
+    }
+
+    // Client connections without traffic or requests being handled for this long are closed
+    pub fn server_idle_timeout(&self) -> Option<Duration> {
+        self.server_idle_timeout
+    }
+
+    // Whether requests are served by a pool of worker threads rather than a single thread
+    pub fn multi_thread_runtime(&self) -> bool {
+        self.multi_thread_runtime
+    }
+
+    // The number of worker threads of the multi-thread runtime; None uses one per core
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads
+    }
+
+    // Remote repositories only; file:// repositories are served as local repositories instead,
+    // and are not counted by repository rules and weights
+    pub fn repositories(&self) -> Result<Vec<Repository>, ProxyError> {
+        let repositories = self.remote_repositories()
+            .map(RepositoryConfig::to_repository);
+        if self.repository_weights.is_empty() {
+            return repositories.collect();
+        }
+        repositories
+            .zip(&self.repository_weights)
+            .map(|(repository, &weight)| Ok(repository?.with_weight(weight)))
+            .collect()
+    }
+
+    // Repositories listed more than once, including as fallbacks, after normalizing their URLs
+    pub fn duplicate_repositories(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
+        let urls = self.repositories
+            .iter()
+            .chain(&self.fallback_repositories)
+            .filter_map(|repository| repository.parse_url().ok());
+        for url in urls {
+            let url = url.to_string();
+            if !seen.insert(url.clone()) && !duplicates.contains(&url) {
+                duplicates.push(url);
+            }
+        }
+        duplicates
+    }
+
+    // Duplicates would double the load on the repository, so they are either rejected or warned
+    // about, once when the config is validated
+    fn check_duplicate_repositories(&self) -> Result<(), ProxyError> {
+        for duplicate in self.duplicate_repositories() {
+            if self.reject_duplicate_repositories {
+                return Err(ProxyError::InvalidRepositoryUrl {
+                    url: duplicate,
+                    reason: "the repository is listed more than once".to_string()
+                });
+            }
+            log::warn!("Repository {} is listed more than once, so it will be contacted repeatedly", duplicate);
+        }
+        Ok(())
+    }
+
+    // Contacted one at a time, in order, only once all other repositories have answered with a 404
+    pub fn fallback_repositories(&self) -> Result<Vec<Repository>, ProxyError> {
+        self.fallback_repositories
+            .iter()
+            .map(RepositoryConfig::to_repository)
+            .collect()
+    }
+
+    // One weight per repository, in the same order; empty to contact all repositories at once
+    pub fn repository_weights(&self) -> &[u32] {
+        &self.repository_weights
+    }
+
+    pub fn groups(&self) -> Result<Vec<RepositoryGroup>, ProxyError> {
+        self.groups
+            .iter()
+            .map(RepositoryGroupConfig::to_group)
+            .collect()
+    }
+
+    pub fn repository_rules(&self) -> Vec<RepositoryRule> {
+        self.repository_rules
+            .iter()
+            .map(RepositoryRuleConfig::to_rule)
+            .collect()
+    }
+
+    pub fn local_repositories(&self) -> Vec<LocalRepository> {
+        self.local_repositories
+            .iter()
+            .cloned()
+            .chain(self.file_repositories())
+            .map(LocalRepository::new)
+            .collect()
+    }
+
+    // Whether file:// repository URLs are accepted, as an alternative to local_repositories
+    pub fn allow_file_repositories(&self) -> bool {
+        self.allow_file_repositories
+    }
+
+    fn remote_repositories(&self) -> impl Iterator<Item=&RepositoryConfig> {
+        let allow_file_repositories = self.allow_file_repositories;
+        self.repositories
+            .iter()
+            .filter(move |repository| !(allow_file_repositories && repository.is_file()))
+    }
+
+    fn file_repositories(&self) -> Vec<PathBuf> {
+        self.repositories
+            .iter()
+            .filter(|_| self.allow_file_repositories)
+            .filter_map(|repository| repository.parse_url().ok())
+            .filter(|url| url.scheme() == "file")
+            .filter_map(|url| url.to_file_path().ok())
+            .collect()
+    }
+
+    // Only artifacts in groups starting with these prefixes are proxied, unless empty
+    pub fn group_allowlist(&self) -> GroupAllowlist {
+        GroupAllowlist::new(&self.allowed_group_prefixes)
+    }
+
+    pub fn log_level(&self) -> log::Level {
+        self.log_level
+    }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    pub fn proxy_timeout(&self) -> Duration {
+        self.proxy_timeout
+    }
+
+    // Bounds establishing a connection to a repository, so unreachable hosts fail
+    // before the proxy timeout
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    // Used instead of the proxy timeout for metadata, POMs and checksums
+    pub fn metadata_timeout(&self) -> Option<Duration> {
+        self.metadata_timeout
+    }
+
+    // Used instead of the proxy timeout for jars and other archives
+    pub fn artifact_timeout(&self) -> Option<Duration> {
+        self.artifact_timeout
+    }
+
+    // Upstream timeouts are randomly lengthened by up to this much, so that they do not all expire together
+    pub fn timeout_jitter(&self) -> Duration {
+        self.timeout_jitter
+    }
+
+    // The total time for answering a client request, which later upstream attempts are shortened to fit
+    pub fn request_deadline(&self) -> Option<Duration> {
+        self.request_deadline
+    }
+
+    // Upstream bodies are aborted once no data has arrived for this long
+    pub fn stream_idle_timeout(&self) -> Option<Duration> {
+        self.stream_idle_timeout
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    pub fn retry_backoff(&self) -> Duration {
+        self.retry_backoff
+    }
+
+    // When set, the first attempt to each repository is given this timeout, and each retry
+    // the previous timeout times the multiplier, up to the proxy timeout
+    pub fn retry_initial_timeout(&self) -> Option<Duration> {
+        self.retry_initial_timeout
+    }
+
+    pub fn retry_timeout_multiplier(&self) -> u32 {
+        self.retry_timeout_multiplier
+    }
+
+    // Upstream responses with these statuses are retried, honoring Retry-After
+    pub fn retry_statuses(&self) -> Vec<StatusCode> {
+        status_codes(&self.retry_statuses)
+    }
+
+    // At most this many retries are made per second across all requests, so that a struggling
+    // repository is not flooded with retries. None leaves retries unlimited
+    pub fn retry_budget_per_second(&self) -> Option<u32> {
+        self.retry_budget_per_second
+    }
+
+    // Upstream responses with success statuses are passed on, while those with not found
+    // statuses send the request to the other repositories. Any other status is an error
+    pub fn status_policy(&self) -> StatusPolicy {
+        StatusPolicy::new(status_codes(&self.success_statuses), status_codes(&self.not_found_statuses))
+    }
+
+    // Redirects from repositories beyond this many are treated as failures
+    pub fn max_redirects(&self) -> u32 {
+        self.max_redirects
+    }
+
+    // Whether redirects to other hosts, such as CDNs, are followed
+    pub fn follow_cross_host_redirects(&self) -> bool {
+        self.follow_cross_host_redirects
+    }
+
+    // How long the targets of permanent redirects are used directly, without asking the
+    // redirecting repository again. None follows every redirect
+    pub fn redirect_cache_ttl(&self) -> Option<Duration> {
+        self.redirect_cache_ttl
+    }
+
+    pub fn prefer_order(&self) -> bool {
+        self.prefer_order
+    }
+
+    // Repositories are contacted this many at a time, in listed order, until one has the artifact
+    pub fn max_fanout(&self) -> Option<usize> {
+        self.max_fanout
+    }
+
+    // Serves SNAPSHOT artifacts from the repository with the newest build, rather than from
+    // whichever repository answers first
+    pub fn snapshot_freshness(&self) -> bool {
+        self.snapshot_freshness
+    }
+
+    // Requests for example-1.0-SNAPSHOT.jar are forwarded as the newest build's uniquely versioned
+    // file, such as example-1.0-20210101.120000-1.jar, for mirrors which only store those
+    pub fn resolve_snapshot_versions(&self) -> bool {
+        self.resolve_snapshot_versions
+    }
+
+    // Whether to probe each repository when starting
+    pub fn startup_check(&self) -> bool {
+        self.startup_check
+    }
+
+    // Whether to refuse to start if the startup check finds an unreachable repository
+    pub fn startup_check_strict(&self) -> bool {
+        self.startup_check_strict
+    }
+
+    // A limit of 0 disables the limit entirely
+    pub fn max_concurrent_upstream(&self) -> usize {
+        self.max_concurrent_upstream
+    }
+
+    // Client connections open at once; further connections wait until one closes
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    // Whether HTTP/2 is offered to upstream repositories during TLS negotiation
+    pub fn upstream_http2(&self) -> bool {
+        self.upstream_http2
+    }
+
+    // Requests failing at the HTTP/2 protocol level are repeated once over HTTP/1.1, for
+    // repositories which advertise HTTP/2 but mishandle it
+    pub fn upstream_http1_fallback(&self) -> bool {
+        self.upstream_http1_fallback
+    }
+
+    // Repositories which needed the fallback are contacted over HTTP/1.1 until the proxy restarts
+    pub fn remember_http1_fallback(&self) -> bool {
+        self.remember_http1_fallback
+    }
+
+    // How long idle upstream connections are kept open. Defaults to hyper's 90 seconds
+    pub fn pool_idle_timeout(&self) -> Duration {
+        self.pool_idle_timeout
+    }
+
+    // Defaults to no limit, as with hyper
+    pub fn pool_max_idle_per_host(&self) -> usize {
+        self.pool_max_idle_per_host
+    }
+
+    // SNAPSHOT paths are never cached, regardless of this setting
+    pub fn negative_cache_ttl(&self) -> Duration {
+        self.negative_cache_ttl
+    }
+
+    // How long to wait for requests in flight to finish when shutting down
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.shutdown_timeout
+    }
+
+    // How often connections to repositories are refreshed; None disables warmup
+    pub fn warmup_interval(&self) -> Option<Duration> {
+        self.warmup_interval
+    }
+
+    // How often repository indexes are read again, for mirrors which republish them; None
+    // reads them only at startup and on configuration reloads
+    pub fn repository_index_reload_interval(&self) -> Option<Duration> {
+        self.repository_index_reload_interval
+    }
+
+    pub fn expose_served_by(&self) -> bool {
+        self.expose_served_by
+    }
+
+    // Query strings on artifact requests are removed unless this is set
+    pub fn reject_query_strings(&self) -> bool {
+        self.reject_query_strings
+    }
+
+    // Bodies sent with requests other than PUT are ignored unless this is set
+    pub fn reject_request_bodies(&self) -> bool {
+        self.reject_request_bodies
+    }
+
+    // The origin, or *, whose browser-based tools may read responses; None disables CORS
+    pub fn cors(&self) -> Option<CorsPolicy> {
+        let allow_origin = HeaderValue::from_str(self.cors_allow_origin.as_deref()?).ok()?;
+        Some(CorsPolicy::new(allow_origin))
+    }
+
+    // Whether requests ending in a slash list directories of the local repositories
+    pub fn allow_directory_listing(&self) -> bool {
+        self.allow_directory_listing
+    }
+
+    // Cache-Control for clients, if a release max age is configured. Snapshots and metadata
+    // are given the snapshot max age, which requires revalidation if zero
+    pub fn cache_control(&self) -> Option<CacheControlPolicy> {
+        self.release_max_age.map(|release_max_age| {
+            CacheControlPolicy::new(release_max_age, self.snapshot_max_age, self.override_cache_control)
+        })
+    }
+
+    pub fn compress_responses(&self) -> bool {
+        self.compress_responses
+    }
+
+    // Serves per-repository request counters at /stats
+    pub fn stats_enabled(&self) -> bool {
+        self.stats_enabled
+    }
+
+    // Where the stats are written on shutdown
+    pub fn stats_snapshot_path(&self) -> Option<&Path> {
+        self.stats_snapshot_path.as_deref()
+    }
+
+    // Reports an estimate of the distinct artifacts requested within about this window at /stats
+    pub fn distinct_paths_window(&self) -> Option<Duration> {
+        self.distinct_paths_window
+    }
+
+    // Serves the busiest client addresses at /stats/clients
+    pub fn top_clients_enabled(&self) -> bool {
+        self.top_clients_enabled
+    }
+
+    // Counts requests and bytes per value of this header, such as X-Team, at /stats/tenants.
+    // Requests without it are counted as unknown
+    pub fn tenant_header(&self) -> Result<Option<HeaderName>, ProxyError> {
+        self.tenant_header
+            .as_ref()
+            .map(|name| HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| ProxyError::InvalidRequestHeader(name.clone())))
+            .transpose()
+    }
+
+    // Where a line is appended for every artifact served
+    pub fn audit_log_path(&self) -> Option<&Path> {
+        self.audit_log_path.as_deref()
+    }
+
+    // Logs the SHA-256 of each body served, to diagnose corrupted artifacts
+    pub fn log_response_hashes(&self) -> bool {
+        self.log_response_hashes
+    }
+
+    // Requests taking longer than this to resolve are logged as warnings, with the repository
+    // which served them
+    pub fn slow_request_threshold(&self) -> Option<Duration> {
+        self.slow_request_threshold
+    }
+
+    // Where trace spans are sent over OTLP/HTTP, by default a collector on this host; None
+    // unless tracing is enabled
+    pub fn tracing_endpoint(&self) -> Option<Url> {
+        self.tracing_enabled.then(|| self.tracing_endpoint.clone().unwrap_or_else(|| {
+            Url::parse("http://localhost:4318/v1/traces").expect("The default collector URL is valid")
+        }))
+    }
+
+    // Serves the effective configuration, without credentials, at /debug/config
+    pub fn debug_endpoint_enabled(&self) -> bool {
+        self.debug_endpoint_enabled
+    }
+
+    // Reports which repositories have an artifact at /resolve?path=<gav>
+    pub fn resolve_endpoint_enabled(&self) -> bool {
+        self.resolve_endpoint_enabled
+    }
+
+    // Lets requests choose a single repository with the X-Proxy-Repository header or repo query parameter
+    pub fn allow_repository_pinning(&self) -> bool {
+        self.allow_repository_pinning
+    }
+
+    // Warns when repositories serve different copies of the same metadata, serving the
+    // copy most agree on rather than merging them
+    pub fn detect_metadata_divergence(&self) -> bool {
+        self.detect_metadata_divergence
+    }
+
+    // Computes .sha1 and .md5 files which no repository has from the files they checksum
+    pub fn synthesize_checksums(&self) -> bool {
+        self.synthesize_checksums
+    }
+
+    // Fetches the .sha1 and .md5 of served artifacts into the memory cache ahead of their requests
+    pub fn prefetch_checksums(&self) -> bool {
+        self.prefetch_checksums
+    }
+
+    // Serves .sha1 and .md5 files as just the lowercase hash, whatever format the repository uses
+    pub fn normalize_checksum_responses(&self) -> bool {
+        self.normalize_checksum_responses
+    }
+
+    // Serves copies from the disk cache when every repository fails, instead of responding with 502
+    pub fn serve_stale_on_error(&self) -> bool {
+        self.serve_stale_on_error
+    }
+
+    // When every repository answers 404, they are asked once more after this delay, for mirrors
+    // which are slow to replicate newly published artifacts. Every missing artifact is delayed by it
+    pub fn recheck_404_after(&self) -> Option<Duration> {
+        self.recheck_404_after
+    }
+
+    // Lists the repositories checked and what each answered in 404 and 502 responses. This
+    // reveals the repositories' URLs, so it is meant for debugging rather than public proxies
+    pub fn verbose_not_found(&self) -> bool {
+        self.verbose_not_found
+    }
+
+    // Serves only local repositories and the disk cache, never contacting remote repositories
+    pub fn offline_mode(&self) -> bool {
+        self.offline_mode
+    }
+
+    // Collapses repeated slashes in request paths, such as //org//apache/, instead of rejecting them
+    pub fn normalize_slashes(&self) -> bool {
+        self.normalize_slashes
+    }
+
+    // Answers 404 for paths which cannot name a file in the maven layout, without contacting repositories
+    pub fn validate_coordinates(&self) -> bool {
+        self.validate_coordinates
+    }
+
+    // Consecutive failures after which a repository is skipped; None never skips repositories
+    pub fn circuit_breaker_threshold(&self) -> Option<u32> {
+        self.circuit_breaker_threshold
+    }
+
+    pub fn circuit_breaker_cooldown(&self) -> Duration {
+        self.circuit_breaker_cooldown
+    }
+
+    // Where the circuit breaker's skipped repositories are saved on shutdown and restored from
+    // on startup
+    pub fn persist_repository_state_path(&self) -> Option<&Path> {
+        self.persist_repository_state_path.as_deref()
+    }
+
+    // Repositories which must be reachable and not skipped by the circuit breaker for the deep
+    // health check to pass; None requires every repository to be reachable
+    pub fn readiness_min_healthy_repositories(&self) -> Option<usize> {
+        self.readiness_min_healthy_repositories
+    }
+
+    // A cache of small files holding up to memory_cache_capacity bytes; None if not configured
+    pub fn memory_cache(&self) -> Option<MemoryCache> {
+        self.memory_cache_capacity.map(|capacity| MemoryCache::new(
+            capacity, self.memory_cache_max_file_size, self.memory_cache_ttl, self.memory_cache_snapshot_ttl))
+    }
+
+    // Answers HEAD requests for cached files without contacting the repositories
+    pub fn head_from_cache(&self) -> bool {
+        self.head_from_cache
+    }
+
+    // Answers requests for a range of a file in the disk cache by slicing the cached copy
+    pub fn ranges_from_cache(&self) -> bool {
+        self.ranges_from_cache
+    }
+
+    // Where copies of served artifacts are kept, for revalidation; created if missing
+    pub fn disk_cache_directory(&self) -> Option<&Path> {
+        self.disk_cache_directory.as_deref()
+    }
+
+    // The disk cache's least recently used copies are evicted beyond cache_max_bytes, and
+    // copies older than cache_max_age are evicted regardless
+    pub fn eviction_limits(&self) -> EvictionLimits {
+        EvictionLimits::new(self.cache_max_bytes, self.cache_max_age)
+    }
+
+    pub fn cache_eviction_interval(&self) -> Duration {
+        self.cache_eviction_interval
+    }
+
+    pub fn rate_limit_per_second(&self) -> Option<u32> {
+        self.rate_limit_per_second
+    }
+
+    // Requests from a single client address beyond this many in flight are answered with 429
+    pub fn max_inflight_per_client(&self) -> Option<usize> {
+        self.max_inflight_per_client
+    }
+
+    // The reverse proxies whose forwarded headers name the client; None if they are not trusted
+    pub fn trusted_proxies(&self) -> Option<TrustedProxies> {
+        if !self.trust_forwarded_headers {
+            return None;
+        }
+        let cidrs = self.trusted_proxy_cidrs.iter()
+            .filter_map(|cidr| Cidr::from_str(cidr).ok())
+            .collect();
+        Some(TrustedProxies::new(cidrs))
+    }
+
+    // The combined rate at which artifacts are sent to clients; None is unlimited
+    pub fn max_bandwidth_bytes_per_sec(&self) -> Option<u64> {
+        self.max_bandwidth_bytes_per_sec
+    }
+
+    // Requests with more headers are refused
+    pub fn max_header_count(&self) -> usize {
+        self.max_header_count
+    }
+
+    // The total size of request header names and values, in bytes
+    pub fn max_header_bytes(&self) -> usize {
+        self.max_header_bytes
+    }
+
+    // Requests with a longer path and query string, in bytes, are refused
+    pub fn max_path_length(&self) -> usize {
+        self.max_path_length
+    }
+
+    // In bytes
+    pub fn max_artifact_size(&self) -> Option<u64> {
+        self.max_artifact_size
+    }
+
+    // Responses with a smaller Content-Length are read fully before being sent, rather than streamed
+    pub fn buffer_threshold_bytes(&self) -> Option<u64> {
+        self.buffer_threshold_bytes
+    }
+
+    pub fn path_prefix(&self) -> Option<&str> {
+        self.path_prefix.as_deref()
+    }
+
+    // When unset, the HTTP_PROXY and HTTPS_PROXY environment variables are used instead
+    pub fn http_proxy(&self) -> Option<&Url> {
+        self.http_proxy.as_ref()
+    }
+
+    pub fn https_proxy(&self) -> Option<&Url> {
+        self.https_proxy.as_ref()
+    }
+
+    // A PEM file of root certificates trusted in addition to the system roots
+    pub fn ca_bundle(&self) -> Option<&Path> {
+        self.ca_bundle.as_deref()
+    }
+
+    // Dangerous: accepts any certificate from upstream repositories, allowing them to be impersonated
+    pub fn insecure_skip_tls_verify(&self) -> bool {
+        self.insecure_skip_tls_verify
+    }
+
+    // The certificate chain and private key to serve HTTPS with, if both are configured
+    pub fn tls_identity(&self) -> Option<(&Path, &Path)> {
+        self.tls_cert_path.as_deref().zip(self.tls_key_path.as_deref())
+    }
+
+    pub fn favicon_path(&self) -> Option<&Path> {
+        self.favicon_path.as_deref()
+    }
+
+    // Bodies served at exact paths without contacting any repository, by default a /robots.txt
+    // disallowing all crawling
+    pub fn static_responses(&self) -> StaticResponses {
+        StaticResponses::new(self.static_responses.clone())
+    }
+
+    // Used to render the bodies of error responses
+    pub fn error_page_template(&self) -> Option<&Path> {
+        self.error_page_template.as_deref()
+    }
+
+    // Required as a bearer token by the admin endpoints, which are disabled without it
+    pub fn admin_secret(&self) -> Option<&str> {
+        self.admin_secret.as_deref()
+    }
+
+    pub fn publish_repository(&self) -> Result<Option<Repository>, ProxyError> {
+        self.publish_repository
+            .as_ref()
+            .map(RepositoryConfig::to_repository)
+            .transpose()
+    }
+
+    fn load_default() -> Self {
+        let maven_central = Url::parse("https://repo1.maven.org/maven2").expect("The Maven Central URL is valid");
+        let repositories = vec![RepositoryConfig::new(maven_central)];
+        Self {
+            port: 8080,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            dual_stack: true,
+            listen_backlog: 1024,
+            reuse_address: true,
+            tcp_nodelay: false,
+            http1_keepalive: true,
+            server_idle_timeout: None,
+            multi_thread_runtime: false,
+            worker_threads: None,
+            repositories,
+            fallback_repositories: Vec::new(),
+            groups: Vec::new(),
+            repository_rules: Vec::new(),
+            repository_weights: Vec::new(),
+            local_repositories: Vec::new(),
+            allow_file_repositories: false,
+            allow_empty_repositories: false,
+            reject_duplicate_repositories: false,
+            allowed_group_prefixes: Vec::new(),
+            log_level: log::Level::Info,
+            log_format: LogFormat::Text,
+            proxy_timeout: Duration::from_secs(15),
+            connect_timeout: Duration::from_secs(5),
+            metadata_timeout: None,
+            artifact_timeout: None,
+            timeout_jitter: Duration::ZERO,
+            request_deadline: None,
+            stream_idle_timeout: None,
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(250),
+            retry_initial_timeout: None,
+            retry_timeout_multiplier: 2,
+            retry_statuses: DEFAULT_RETRY_STATUSES.iter().map(StatusCode::as_u16).collect(),
+            retry_budget_per_second: None,
+            success_statuses: vec![200, 206, 304],
+            not_found_statuses: vec![404],
+            max_redirects: 5,
+            follow_cross_host_redirects: false,
+            redirect_cache_ttl: None,
+            prefer_order: false,
+            max_fanout: None,
+            snapshot_freshness: false,
+            resolve_snapshot_versions: false,
+            startup_check: false,
+            startup_check_strict: false,
+            max_concurrent_upstream: 64,
+            max_connections: None,
+            upstream_http2: false,
+            upstream_http1_fallback: true,
+            remember_http1_fallback: false,
+            pool_idle_timeout: Duration::from_secs(90),
+            // Unlimited, as in hyper, but small enough for TOML's signed integers
+            pool_max_idle_per_host: i64::MAX as usize,
+            negative_cache_ttl: Duration::from_secs(60),
+            shutdown_timeout: Duration::from_secs(30),
+            warmup_interval: None,
+            repository_index_reload_interval: None,
+            expose_served_by: false,
+            reject_query_strings: false,
+            reject_request_bodies: true,
+            cors_allow_origin: None,
+            allow_directory_listing: false,
+            release_max_age: None,
+            snapshot_max_age: Duration::ZERO,
+            override_cache_control: false,
+            compress_responses: false,
+            stats_enabled: false,
+            stats_snapshot_path: None,
+            distinct_paths_window: None,
+            top_clients_enabled: false,
+            tenant_header: None,
+            audit_log_path: None,
+            log_response_hashes: false,
+            slow_request_threshold: None,
+            tracing_enabled: false,
+            tracing_endpoint: None,
+            debug_endpoint_enabled: false,
+            resolve_endpoint_enabled: false,
+            allow_repository_pinning: false,
+            normalize_slashes: false,
+            validate_coordinates: false,
+            detect_metadata_divergence: false,
+            synthesize_checksums: false,
+            prefetch_checksums: false,
+            normalize_checksum_responses: false,
+            serve_stale_on_error: false,
+            recheck_404_after: None,
+            verbose_not_found: false,
+            offline_mode: false,
+            circuit_breaker_threshold: None,
+            circuit_breaker_cooldown: Duration::from_secs(30),
+            persist_repository_state_path: None,
+            readiness_min_healthy_repositories: None,
+            memory_cache_capacity: None,
+            memory_cache_max_file_size: 64 * 1024,
+            memory_cache_ttl: Duration::from_secs(300),
+            memory_cache_snapshot_ttl: Duration::from_secs(10),
+            head_from_cache: false,
+            ranges_from_cache: false,
+            disk_cache_directory: None,
+            cache_max_bytes: None,
+            cache_max_age: None,
+            cache_eviction_interval: Duration::from_secs(300),
+            rate_limit_per_second: None,
+            max_inflight_per_client: None,
+            trust_forwarded_headers: false,
+            trusted_proxy_cidrs: Vec::new(),
+            max_bandwidth_bytes_per_sec: None,
+            max_header_count: 100,
+            max_header_bytes: 32 * 1024,
+            max_path_length: 2048,
+            max_artifact_size: None,
+            buffer_threshold_bytes: None,
+            path_prefix: None,
+            http_proxy: None,
+            https_proxy: None,
+            ca_bundle: None,
+            insecure_skip_tls_verify: false,
+            publish_repository: None,
+            user_agent: None,
+            strip_request_headers: Vec::new(),
+            set_request_headers: Vec::new(),
+            path_rewrites: Vec::new(),
+            response_header_policy: ResponseHeaderPolicyConfig::PassThrough,
+            tls_cert_path: None,
+            tls_key_path: None,
+            favicon_path: None,
+            static_responses: pages::default_static_responses(),
+            error_page_template: None,
+            admin_secret: None
+        }
+    }
+
+    // Sent upstream when the client did not send a User-Agent, instead of the default
+    pub fn user_agent(&self) -> Result<Option<HeaderValue>, ProxyError> {
+        self.user_agent
+            .as_ref()
+            .map(|user_agent| {
+                HeaderValue::from_str(user_agent)
+                    .map_err(|_| ProxyError::InvalidRequestHeader("User-Agent".to_owned()))
+            })
+            .transpose()
+    }
+
+    // Header names are case-insensitive
+    pub fn request_header_rules(&self) -> Result<RequestHeaderRules, ProxyError> {
+        let header_name = |name: &String| {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| ProxyError::InvalidRequestHeader(name.clone()))
+        };
+        let strip = self.strip_request_headers
+            .iter()
+            .map(header_name)
+            .collect::<Result<_, _>>()?;
+        let set = self.set_request_headers
+            .iter()
+            .map(|(name, value)| {
+                let value = HeaderValue::from_str(value)
+                    .map_err(|_| ProxyError::InvalidRequestHeader(name.clone()))?;
+                Ok((header_name(name)?, value))
+            })
+            .collect::<Result<_, ProxyError>>()?;
+        Ok(RequestHeaderRules::new(strip, set))
+    }
+
+    // Regular expressions and their replacements, applied to incoming paths such as those of
+    // clients which use the proxy as a mirror of another repository manager
+    pub fn path_rewrites(&self) -> Result<Vec<PathRewrite>, ProxyError> {
+        self.path_rewrites
+            .iter()
+            .map(|(pattern, replacement)| PathRewrite::new(pattern, replacement)
+                .map_err(|error| ProxyError::InvalidPathRewrite { pattern: pattern.clone(), error }))
+            .collect()
+    }
+
+    pub fn response_header_policy(&self) -> Result<ResponseHeaderPolicy, ProxyError> {
+        let header_names = |names: &[String]| {
+            names.iter()
+                .map(|name| HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| ProxyError::InvalidResponseHeader(name.clone())))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match &self.response_header_policy {
+            ResponseHeaderPolicyConfig::PassThrough => ResponseHeaderPolicy::PassThrough,
+            ResponseHeaderPolicyConfig::Allow(names) => ResponseHeaderPolicy::Allow(header_names(names)?),
+            ResponseHeaderPolicyConfig::Deny(names) => ResponseHeaderPolicy::Deny(header_names(names)?)
+        })
+    }
+
+    // Checks settings which deserialize successfully but cannot be used
+    pub fn validate(&self) -> Result<(), ProxyError> {
+        if self.port == 0 {
+            return Err(ProxyError::InvalidConfig("The port must not be zero"));
+        }
+        if self.listen_backlog == 0 {
+            return Err(ProxyError::InvalidConfig("The listen backlog must not be zero"));
+        }
+        if self.server_idle_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The server idle timeout must not be zero"));
+        }
+        // Otherwise every request not served by a local repository would be answered with 404.
+        // Offline, remote repositories are never contacted anyway
+        if self.remote_repositories().next().is_none() && !self.allow_empty_repositories && !self.offline_mode {
+            return Err(ProxyError::InvalidConfig(
+                "At least one remote repository is required, unless allow_empty_repositories or offline_mode is set"));
+        }
+        if self.proxy_timeout.is_zero() {
+            return Err(ProxyError::InvalidConfig("The proxy timeout must not be zero"));
+        }
+        if self.connect_timeout.is_zero() {
+            return Err(ProxyError::InvalidConfig("The connect timeout must not be zero"));
+        }
+        if self.metadata_timeout.is_some_and(|timeout| timeout.is_zero())
+            || self.artifact_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(ProxyError::InvalidConfig("File size timeouts must not be zero"));
+        }
+        if self.stream_idle_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The stream idle timeout must not be zero"));
+        }
+        if self.worker_threads == Some(0) {
+            return Err(ProxyError::InvalidConfig("The worker thread count must not be zero"));
+        }
+        if self.worker_threads.is_some() && !self.multi_thread_runtime {
+            return Err(ProxyError::InvalidConfig("Worker threads require the multi-thread runtime"));
+        }
+        if self.request_deadline.is_some_and(|deadline| deadline.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The request deadline must not be zero"));
+        }
+        if self.retry_initial_timeout.is_some_and(|timeout| timeout.is_zero()) || self.retry_timeout_multiplier == 0 {
+            return Err(ProxyError::InvalidConfig("The initial retry timeout and its multiplier must not be zero"));
+        }
+        if self.retry_statuses.iter().any(|status| !(400..600).contains(status) || *status == 404) {
+            return Err(ProxyError::InvalidConfig("Retry statuses must be error statuses other than 404"));
+        }
+        if self.retry_budget_per_second == Some(0) {
+            return Err(ProxyError::InvalidConfig("The retry budget must not be zero"));
+        }
+        if self.success_statuses.is_empty() || self.success_statuses.iter().any(|status| !(200..400).contains(status)) {
+            return Err(ProxyError::InvalidConfig("Success statuses must be 2xx or 3xx, and at least one is required"));
+        }
+        if self.not_found_statuses.iter().any(|status| !(400..500).contains(status)) {
+            return Err(ProxyError::InvalidConfig("Not found statuses must be 4xx"));
+        }
+        if self.cors_allow_origin.as_deref().is_some_and(|origin| origin.is_empty() || HeaderValue::from_str(origin).is_err()) {
+            return Err(ProxyError::InvalidConfig("The CORS origin must be * or an origin such as https://example.com"));
+        }
+        if self.max_fanout == Some(0) {
+            return Err(ProxyError::InvalidConfig("The maximum fan-out must not be zero"));
+        }
+        if self.max_connections == Some(0) {
+            return Err(ProxyError::InvalidConfig("The connection limit must not be zero"));
+        }
+        if self.warmup_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The warmup interval must not be zero"));
+        }
+        if self.repository_index_reload_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The repository index reload interval must not be zero"));
+        }
+        if self.memory_cache_capacity == Some(0) || self.memory_cache_max_file_size == 0 {
+            return Err(ProxyError::InvalidConfig("The memory cache capacity and maximum file size must not be zero"));
+        }
+        if self.stats_snapshot_path.is_some() && !self.stats_enabled {
+            return Err(ProxyError::InvalidConfig("The stats snapshot requires stats to be enabled"));
+        }
+        if self.remember_http1_fallback && !(self.upstream_http2 && self.upstream_http1_fallback) {
+            return Err(ProxyError::InvalidConfig("Remembering the HTTP/1.1 fallback requires upstream HTTP/2 and the fallback"));
+        }
+        if self.distinct_paths_window.is_some() && !self.stats_enabled {
+            return Err(ProxyError::InvalidConfig("Counting distinct paths requires stats to be enabled"));
+        }
+        if self.slow_request_threshold.is_some_and(|threshold| threshold.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The slow request threshold must not be zero"));
+        }
+        if self.tracing_enabled && !cfg!(feature = "otlp") {
+            return Err(ProxyError::InvalidConfig("Tracing requires the otlp feature"));
+        }
+        if self.tracing_endpoint.is_some() && !self.tracing_enabled {
+            return Err(ProxyError::InvalidConfig("The tracing endpoint requires tracing to be enabled"));
+        }
+        if self.tracing_endpoint.as_ref().is_some_and(|endpoint| !matches!(endpoint.scheme(), "http" | "https")) {
+            return Err(ProxyError::InvalidConfig("The tracing endpoint must be an HTTP or HTTPS URL"));
+        }
+        if self.recheck_404_after.is_some_and(|delay| delay.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The delay before rechecking a 404 must not be zero"));
+        }
+        if self.distinct_paths_window.is_some_and(|window| window.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The distinct paths window must not be zero"));
+        }
+        if self.prefetch_checksums && self.memory_cache_capacity.is_none() {
+            return Err(ProxyError::InvalidConfig("Prefetching checksums requires the memory cache"));
+        }
+        if self.serve_stale_on_error && self.disk_cache_directory.is_none() {
+            return Err(ProxyError::InvalidConfig("Serving stale copies requires the disk cache"));
+        }
+        if self.offline_mode && self.disk_cache_directory.is_none() {
+            return Err(ProxyError::InvalidConfig("Offline mode requires the disk cache"));
+        }
+        if self.ranges_from_cache && self.disk_cache_directory.is_none() {
+            return Err(ProxyError::InvalidConfig("Serving ranges from the cache requires the disk cache"));
+        }
+        if !self.eviction_limits().is_unlimited() && self.disk_cache_directory.is_none() {
+            return Err(ProxyError::InvalidConfig("Cache size and age limits require the disk cache"));
+        }
+        if self.cache_max_bytes == Some(0) || self.cache_max_age.is_some_and(|age| age.is_zero())
+            || self.cache_eviction_interval.is_zero() {
+            return Err(ProxyError::InvalidConfig("The cache limits and eviction interval must not be zero"));
+        }
+        if self.redirect_cache_ttl.is_some_and(|ttl| ttl.is_zero()) {
+            return Err(ProxyError::InvalidConfig("The redirect cache TTL must not be zero"));
+        }
+        if self.redirect_cache_ttl.is_some() && self.max_redirects == 0 {
+            return Err(ProxyError::InvalidConfig("Caching redirects requires following them"));
+        }
+        if self.circuit_breaker_threshold == Some(0) {
+            return Err(ProxyError::InvalidConfig("The circuit breaker threshold must not be zero"));
+        }
+        if self.persist_repository_state_path.is_some() && self.circuit_breaker_threshold.is_none() {
+            return Err(ProxyError::InvalidConfig("Persisting the repository state requires the circuit breaker"));
+        }
+        if self.readiness_min_healthy_repositories == Some(0) {
+            return Err(ProxyError::InvalidConfig("The minimum healthy repositories for readiness must not be zero"));
+        }
+        if self.max_header_count == 0 || self.max_header_bytes == 0 {
+            return Err(ProxyError::InvalidConfig("The request header limits must not be zero"));
+        }
+        if self.max_path_length == 0 {
+            return Err(ProxyError::InvalidConfig("The maximum path length must not be zero"));
+        }
+        if self.max_bandwidth_bytes_per_sec == Some(0) {
+            return Err(ProxyError::InvalidConfig("The bandwidth limit must not be zero"));
+        }
+        if self.rate_limit_per_second == Some(0) {
+            return Err(ProxyError::InvalidConfig("The rate limit must not be zero"));
+        }
+        if self.max_inflight_per_client == Some(0) {
+            return Err(ProxyError::InvalidConfig("The maximum requests in flight per client must not be zero"));
+        }
+        if self.trust_forwarded_headers && self.trusted_proxy_cidrs.is_empty() {
+            return Err(ProxyError::InvalidConfig("Trusting forwarded headers requires trusted_proxy_cidrs"));
+        }
+        if self.trusted_proxy_cidrs.iter().any(|cidr| Cidr::from_str(cidr).is_err()) {
+            return Err(ProxyError::InvalidConfig("Trusted proxies must be addresses or networks such as 10.0.0.0/8"));
+        }
+        if self.path_prefix.as_deref().is_some_and(|prefix| !prefix.starts_with('/')) {
+            return Err(ProxyError::InvalidConfig("The path prefix must start with /"));
+        }
+        if self.static_responses.keys().any(|path| !path.starts_with('/')) {
+            return Err(ProxyError::InvalidConfig("Static response paths must start with /"));
+        }
+        if self.groups.iter().any(|group| !group.path_prefix.starts_with('/')) {
+            return Err(ProxyError::InvalidConfig("Repository group path prefixes must start with /"));
+        }
+        let repository_count = self.remote_repositories().count();
+        if self.repository_rules().iter().flat_map(RepositoryRule::indices).any(|index| index >= repository_count) {
+            return Err(ProxyError::InvalidConfig("Repository rules must refer to configured repositories"));
+        }
+        if self.admin_secret.as_deref().is_some_and(str::is_empty) {
+            return Err(ProxyError::InvalidConfig("The admin secret must not be empty"));
+        }
+        if !self.repository_weights.is_empty() && self.repository_weights.len() != repository_count {
+            return Err(ProxyError::InvalidConfig("There must be one repository weight per repository"));
+        }
+        if !self.repository_weights.is_empty() && self.repository_weights.iter().all(|&weight| weight == 0) {
+            return Err(ProxyError::InvalidConfig("At least one repository weight must not be zero"));
+        }
+        let empty_extension = |extension: &String| extension.trim_start_matches('.').is_empty();
+        if self.repositories.iter().flat_map(|repository| repository.extensions.iter().flatten()).any(empty_extension) {
+            return Err(ProxyError::InvalidConfig("Repository extensions must not be empty"));
+        }
+        if self.allow_file_repositories {
+            for repository in self.repositories.iter().filter(|repository| repository.is_file()) {
+                let url = repository.parse_url()?;
+                if !url.to_file_path().is_ok_and(|directory| directory.is_dir()) {
+                    return Err(ProxyError::InvalidRepositoryUrl {
+                        url: repository.url.clone(),
+                        reason: "file:// repositories must be existing directories".to_string()
+                    });
+                }
+            }
+        }
+        if self.local_repositories.iter().any(|directory| !directory.is_dir()) {
+            return Err(ProxyError::InvalidConfig("Local repositories must be existing directories"));
+        }
+        let invalid_group = |prefix: &String| {
+            prefix.is_empty() || prefix.contains('/') || prefix.split('.').any(str::is_empty)
+        };
+        if self.allowed_group_prefixes.iter().any(invalid_group) {
+            return Err(ProxyError::InvalidConfig("Allowed group prefixes must be groupIds such as com.example"));
+        }
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            return Err(ProxyError::InvalidConfig("The TLS certificate and key must be configured together"));
+        }
+        if self.ca_bundle.as_deref().is_some_and(|ca_bundle| !ca_bundle.is_file()) {
+            return Err(ProxyError::InvalidConfig("The CA bundle does not exist"));
+        }
+        if self.favicon_path.as_deref().is_some_and(|favicon_path| !favicon_path.is_file()) {
+            return Err(ProxyError::InvalidConfig("The favicon does not exist"));
+        }
+        if self.error_page_template.as_deref().is_some_and(|template| !template.is_file()) {
+            return Err(ProxyError::InvalidConfig("The error page template does not exist"));
+        }
+        self.repositories()?;
+        self.fallback_repositories()?;
+        self.check_duplicate_repositories()?;
+        self.groups()?;
+        self.publish_repository()?;
+        self.request_header_rules()?;
+        self.response_header_policy()?;
+        self.path_rewrites()?;
+        self.user_agent()?;
+        self.tenant_header()?;
+        Ok(())
+    }
+
+    // The format is chosen by the file extension: .toml, .json, or otherwise RON
+    pub fn load_from(path: &Path) -> eyre::Result<Config> {
+        let format = ConfigFormat::from_path(path);
+        if !path.exists() {
+            let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+            if let Some(parent) = parent.filter(|parent| !parent.is_dir()) {
+                return Err(std::io::Error::new(ErrorKind::NotFound, format!(
+                    "Cannot create config {}: directory {} does not exist",
+                    path.display(), parent.display())).into());
+            }
+            println!("Config {} does not exist; creating default config...", path.display());
+            let mut write_options = OpenOptions::new();
+            write_options.write(true).create_new(true);
+            let writer = write_options.open(path)?;
+            format.write(writer, &Self::load_default())?;
+        }
+        let reader = BufReader::new(File::open(path)?);
+        format.read(path, reader)
+    }
 }
 
-impl Config {
-    pub fn port(&self) -> u16 {
-        self.port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Ron,
+    Toml,
+    Json
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Self {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Ron
+        }
     }
 
-    pub fn repositories(&self) -> Vec<Uri> {
-        let repos: &Vec<Uri> = &self.repositories
-            .iter()
-            .map(|url| Uri::from_str(url.as_str()).expect("URL should be validated by config load"))
-            .collect();
-        repos.clone()
+    // Parse errors name the config and, where known, the line and column
+    fn read(&self, path: &Path, mut reader: BufReader<File>) -> eyre::Result<Config> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        let reason = match self {
+            ConfigFormat::Ron => match ron::de::from_str(&contents) {
+                Ok(config) => return Ok(config),
+                // RON reports an unknown position as line 0
+                Err(error) if error.position.line == 0 => error.code.to_string(),
+                Err(error) => format!("line {}, column {}: {}", error.position.line, error.position.col, error.code)
+            },
+            // Both already include the line and column in their messages
+            ConfigFormat::Toml => match toml::from_str(&contents) {
+                Ok(config) => return Ok(config),
+                Err(error) => error.to_string()
+            },
+            ConfigFormat::Json => match serde_json::from_str(&contents) {
+                Ok(config) => return Ok(config),
+                Err(error) => error.to_string()
+            }
+        };
+        Err(ProxyError::InvalidConfigSyntax { path: path.to_owned(), reason }.into())
     }
 
-    pub fn log_level(&self) -> log::Level {
-        self.log_level
+    fn write(&self, mut writer: File, config: &Config) -> eyre::Result<()> {
+        match self {
+            ConfigFormat::Ron => to_writer_pretty(writer, config, Default::default())?,
+            // TOML requires plain values to come before tables, which a Value orders correctly
+            ConfigFormat::Toml => writer.write_all(toml::to_string_pretty(&toml::Value::try_from(config)?)?.as_bytes())?,
+            ConfigFormat::Json => serde_json::to_writer_pretty(writer, config)?
+        }
+        Ok(())
     }
+}
 
-    pub fn proxy_timeout(&self) -> Duration {
-        self.proxy_timeout
+// Constructs a Config in memory, such as for tests or when embedding the proxy
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    config: Config
+}
+
+impl ConfigBuilder {
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
     }
 
-    fn load_default() -> Self {
-        let repositories: Vec<Url> = vec!(Url::parse("https://repo1.maven.org/maven2").unwrap());
+    pub fn repositories(mut self, repositories: Vec<Url>) -> Self {
+        self.config.repositories = repositories.into_iter().map(RepositoryConfig::new).collect();
+        self
+    }
+
+    pub fn log_level(mut self, log_level: log::Level) -> Self {
+        self.config.log_level = log_level;
+        self
+    }
+
+    pub fn proxy_timeout(mut self, proxy_timeout: Duration) -> Self {
+        self.config.proxy_timeout = proxy_timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<Config, ProxyError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct RepositoryConfig {
+    url: String,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    timeout: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extensions: Option<Vec<String>>,
+    // Applied to requested paths before joining them to the URL, as (pattern, replacement)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    path_rewrites: Vec<(String, String)>,
+    // INSECURE: contacts an https repository over plain HTTP. Only for mirrors on trusted
+    // internal networks which serve both, never for public repositories
+    #[serde(default)]
+    downgrade_to_http: bool,
+    // Sends HEAD requests as GET, for mirrors which answer HEAD wrongly
+    #[serde(default)]
+    head_as_get: bool,
+    // Appended to a URL given without a path, such as "/maven2" for a host serving the usual
+    // Maven Central layout. URLs which already have a path are used as they are
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_path: Option<String>,
+    // Sent only to this repository, such as X-JFrog-Art-Api or Authorization
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<(String, String)>,
+    // A file listing the paths the repository has, one per line. Other paths are never
+    // requested from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index_path: Option<PathBuf>
+}
+
+impl RepositoryConfig {
+    fn new(url: Url) -> Self {
         Self {
-            port: 8080,
-            repositories,
-            log_level: log::Level::Info,
-            proxy_timeout: Duration::from_secs(15)
+            url: url.to_string(),
+            timeout: None,
+            extensions: None,
+            path_rewrites: Vec::new(),
+            downgrade_to_http: false,
+            head_as_get: false,
+            base_path: None,
+            headers: Vec::new(),
+            index_path: None
         }
     }
 
-    pub fn load_from(path: &Path) -> ron::Result<Config> {
-        if !path.exists() {
-            println!("Config {} does not exist; creating default config...", path.display());
-            let mut write_options = OpenOptions::new();
-            write_options.write(true).create_new(true);
-            let writer = write_options.open(path)?;
-            to_writer_pretty(writer, &Self::load_default(), Default::default())?;
+    fn is_file(&self) -> bool {
+        Url::parse(self.url.trim()).is_ok_and(|url| url.scheme() == "file")
+    }
+
+    // Requires an absolute http or https URL, or a file URL, with trailing slashes removed
+    fn parse_url(&self) -> Result<Url, ProxyError> {
+        let invalid = |reason: &str| ProxyError::InvalidRepositoryUrl {
+            url: self.url.clone(),
+            reason: reason.to_string()
+        };
+        let mut url = match Url::parse(self.url.trim()) {
+            Ok(url) => url,
+            Err(url::ParseError::RelativeUrlWithoutBase) => {
+                return Err(invalid("the URL must be absolute, starting with http:// or https://"));
+            },
+            Err(error) => return Err(invalid(&error.to_string()))
+        };
+        match url.scheme() {
+            "http" | "https" | "file" => {},
+            _ => return Err(invalid("only http:// and https:// repositories are supported"))
         }
-        let reader = BufReader::new(File::open(path)?);
-        from_reader(reader)
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(invalid("the URL must not have a query string or fragment"));
+        }
+        let mut path = url.path().trim_end_matches('/').to_string();
+        match &self.base_path {
+            Some(base_path) if path.is_empty() && url.scheme() != "file" => {
+                if !base_path.starts_with('/') || base_path.contains(['?', '#']) {
+                    return Err(invalid("the base path must start with / and must not have a query string or fragment"));
+                }
+                path = base_path.trim_end_matches('/').to_string();
+            },
+            _ => {}
+        }
+        url.set_path(if path.is_empty() { "/" } else { &path });
+        Ok(url)
+    }
+
+    // file:// repositories are only usable as local repositories, see Config::repositories
+    fn to_repository(&self) -> Result<Repository, ProxyError> {
+        let url = self.parse_url()?;
+        if url.scheme() == "file" {
+            return Err(ProxyError::InvalidRepositoryUrl {
+                url: self.url.clone(),
+                reason: "file:// repositories require allow_file_repositories".to_string()
+            });
+        }
+        let uri = Uri::from_str(url.as_str()).map_err(|error| {
+            ProxyError::InvalidRepositoryUri { url: self.url.clone(), error }
+        })?;
+        let mut repository = Repository::new(uri);
+        if let Some(timeout) = self.timeout {
+            repository = repository.with_timeout(timeout);
+        }
+        if let Some(extensions) = &self.extensions {
+            repository = repository.with_extensions(extensions.clone());
+        }
+        if !self.path_rewrites.is_empty() {
+            let path_rewrites = self.path_rewrites
+                .iter()
+                .map(|(pattern, replacement)| PathRewrite::new(pattern, replacement)
+                    .map_err(|error| ProxyError::InvalidPathRewrite { pattern: pattern.clone(), error }))
+                .collect::<Result<_, _>>()?;
+            repository = repository.with_path_rewrites(path_rewrites);
+        }
+        if self.downgrade_to_http {
+            log::warn!("Repository {} is contacted over plain HTTP, which is insecure outside trusted networks",
+                       repository.redacted_url());
+            repository = repository.with_http_downgrade(true);
+        }
+        if self.head_as_get {
+            repository = repository.with_head_as_get(true);
+        }
+        if !self.headers.is_empty() {
+            let headers = self.headers
+                .iter()
+                .map(|(name, value)| {
+                    let invalid = || ProxyError::InvalidRequestHeader(name.clone());
+                    let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+                    let value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+                    Ok((name, value))
+                })
+                .collect::<Result<_, ProxyError>>()?;
+            repository = repository.with_headers(headers);
+        }
+        if let Some(index_path) = &self.index_path {
+            let index = RepositoryIndex::load(index_path).map_err(|error| {
+                ProxyError::UnreadableRepositoryIndex { path: index_path.clone(), error }
+            })?;
+            repository = repository.with_index(Arc::new(index));
+        }
+        Ok(repository)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct RepositoryGroupConfig {
+    path_prefix: String,
+    #[serde(deserialize_with = "deserialize_repositories")]
+    repositories: Vec<RepositoryConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefer_order: Option<bool>
+}
+
+impl RepositoryGroupConfig {
+    fn to_group(&self) -> Result<RepositoryGroup, ProxyError> {
+        let repositories = self.repositories
+            .iter()
+            .map(RepositoryConfig::to_repository)
+            .collect::<Result<_, _>>()?;
+        let group = RepositoryGroup::new(self.path_prefix.clone(), repositories);
+        Ok(match self.prefer_order {
+            Some(prefer_order) => group.with_prefer_order(prefer_order),
+            None => group
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct RepositoryRuleConfig {
+    pattern: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allow: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny: Vec<usize>
+}
+
+impl RepositoryRuleConfig {
+    fn to_rule(&self) -> RepositoryRule {
+        RepositoryRule::new(PathPattern::new(&self.pattern), self.allow.clone(), self.deny.clone())
+    }
+}
+
+// Either passes all upstream response headers, or only those allowed, or all but those denied
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum ResponseHeaderPolicyConfig {
+    PassThrough,
+    Allow(Vec<String>),
+    Deny(Vec<String>)
+}
+
+// Repositories may be given either as a plain URL or with additional settings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RepositoryEntry {
+    Url(String),
+    Config(RepositoryConfig)
+}
+
+fn deserialize_repositories<'de, D>(deserializer: D) -> Result<Vec<RepositoryConfig>, D::Error>
+    where D: Deserializer<'de> {
+
+    let entries: Vec<RepositoryEntry> = Vec::deserialize(deserializer)?;
+    Ok(entries.into_iter().map(|entry| match entry {
+        RepositoryEntry::Url(url) => RepositoryConfig {
+            url,
+            timeout: None,
+            extensions: None,
+            path_rewrites: Vec::new(),
+            downgrade_to_http: false,
+            head_as_get: false,
+            base_path: None,
+            headers: Vec::new(),
+            index_path: None
+        },
+        RepositoryEntry::Config(config) => config
+    }).collect())
+}
+
+// Statuses are checked when validating, so none are dropped in practice
+fn status_codes(statuses: &[u16]) -> Vec<StatusCode> {
+    statuses.iter()
+        .filter_map(|status| StatusCode::from_u16(*status).ok())
+        .collect()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::load_default()
     }
 }
 
@@ -96,6 +1676,26 @@ impl From<DurationSerializable> for Duration {
     }
 }
 
+mod optional_duration {
+    use super::*;
+    use serde::Serializer;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct DurationWrapper(#[serde(with = "DurationSerializable")] Duration);
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer {
+        duration.map(DurationWrapper).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where D: Deserializer<'de> {
+        let wrapper: Option<DurationWrapper> = Option::deserialize(deserializer)?;
+        Ok(wrapper.map(|DurationWrapper(duration)| duration))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,9 +1707,11 @@ mod tests {
     fn load_default_config() {
         let config = Config::load_default();
         assert_eq!(8080, config.port);
-        let repos: Vec<Uri> = vec![Uri::from_str("https://repo1.maven.org/maven2").unwrap()];
-        assert_eq!(repos, config.repositories());
+        let repos: Vec<Repository> = vec![Uri::from_str("https://repo1.maven.org/maven2").unwrap().into()];
+        assert_eq!(repos, config.repositories().unwrap());
         assert_eq!(log::Level::Info, config.log_level());
+        assert!(!config.insecure_skip_tls_verify());
+        assert_eq!(ListenerOptions::default(), config.listener_options());
     }
 
     #[test]
@@ -144,4 +1746,623 @@ mod tests {
         Ok(())
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn write_new_config_in_each_format() -> Result<()> {
+        let temp_dir = tempdir()?;
+        for file_name in &["config.toml", "config.json", "config.ron"] {
+            let config_path = temp_dir.path().join(file_name);
+            Config::load_from(&config_path)?;
+            assert_eq!(Config::load_default(), Config::load_from(&config_path)?, "Reloading {}", file_name);
+        }
+        let written = std::fs::read_to_string(temp_dir.path().join("config.json"))?;
+        serde_json::from_str::<serde_json::Value>(&written)?;
+        Ok(())
+    }
+
+    #[test]
+    fn equivalent_config_formats() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let sources = [
+            ("config.ron", r#"(
+                port: 9000,
+                repositories: [
+                    "https://repo1.maven.org/maven2",
+                    (url: "https://internal.example.com/maven2", timeout: Some((secs: 2, nanos: 0)))
+                ],
+                proxy_timeout: (secs: 10, nanos: 0),
+                log_level: Debug,
+                max_artifact_size: Some(1048576)
+            )"#),
+            ("config.toml", r#"
+                port = 9000
+                log_level = "Debug"
+                max_artifact_size = 1048576
+                proxy_timeout = { secs = 10, nanos = 0 }
+                repositories = [
+                    "https://repo1.maven.org/maven2",
+                    { url = "https://internal.example.com/maven2", timeout = { secs = 2, nanos = 0 } }
+                ]
+            "#),
+            ("config.json", r#"{
+                "port": 9000,
+                "repositories": [
+                    "https://repo1.maven.org/maven2",
+                    {"url": "https://internal.example.com/maven2", "timeout": {"secs": 2, "nanos": 0}}
+                ],
+                "proxy_timeout": {"secs": 10, "nanos": 0},
+                "log_level": "Debug",
+                "max_artifact_size": 1048576
+            }"#)
+        ];
+        let mut configs = Vec::new();
+        for (file_name, source) in &sources {
+            let config_path = temp_dir.path().join(file_name);
+            std::fs::write(&config_path, source)?;
+            configs.push(Config::load_from(&config_path)?);
+        }
+        assert_eq!(9000, configs[0].port());
+        assert_eq!(2, configs[0].repositories()?.len());
+        assert_eq!(configs[0], configs[1]);
+        assert_eq!(configs[0], configs[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() -> Result<()> {
+        let config: Config = ron::de::from_str("(port: 9090)")?;
+        assert_eq!(9090, config.port());
+        assert_eq!(Config::load_default().max_retries(), config.max_retries());
+        assert_eq!(Config::load_default().retry_backoff(), config.retry_backoff());
+        assert_eq!(LogFormat::Text, config.log_format());
+        assert!(!config.snapshot_freshness());
+        Ok(())
+    }
+
+    #[test]
+    fn publish_repository() -> Result<()> {
+        assert_eq!(None, Config::load_default().publish_repository()?);
+        let config: Config = ron::de::from_str(
+            "(publish_repository: Some((url: \"https://repo.example.com/releases\")))")?;
+        let expected: Repository = Uri::from_static("https://repo.example.com/releases").into();
+        assert_eq!(Some(expected), config.publish_repository()?);
+        Ok(())
+    }
+
+    #[test]
+    fn fallback_repositories() -> Result<()> {
+        assert!(Config::load_default().fallback_repositories()?.is_empty());
+        let config: Config = ron::de::from_str(r#"(
+            fallback_repositories: ["https://vault.example.com/maven/", (url: "https://archive.example.com/maven2")]
+        )"#)?;
+        config.validate()?;
+        let expected: Vec<Repository> = vec![
+            Uri::from_static("https://vault.example.com/maven").into(),
+            Uri::from_static("https://archive.example.com/maven2").into()
+        ];
+        assert_eq!(expected, config.fallback_repositories()?);
+        assert_eq!(1, config.repositories()?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn repository_groups() -> Result<()> {
+        assert!(Config::load_default().groups()?.is_empty());
+        let config: Config = ron::de::from_str(r#"(
+            groups: [
+                (path_prefix: "/releases", repositories: ["https://repo1.maven.org/maven2"]),
+                (path_prefix: "/snapshots", repositories: [(url: "https://repo.example.com/snapshots")],
+                 prefer_order: Some(true))
+            ]
+        )"#)?;
+        let groups = config.groups()?;
+        assert_eq!(vec![
+            RepositoryGroup::new("/releases".to_owned(),
+                                 vec![Uri::from_static("https://repo1.maven.org/maven2").into()]),
+            RepositoryGroup::new("/snapshots".to_owned(),
+                                 vec![Uri::from_static("https://repo.example.com/snapshots").into()])
+                .with_prefer_order(true)
+        ], groups);
+        Ok(())
+    }
+
+    #[test]
+    fn builder_defaults() -> Result<()> {
+        assert_eq!(Config::load_default(), Config::builder().build()?);
+        Ok(())
+    }
+
+    #[test]
+    fn builder_overrides() -> Result<()> {
+        let config = Config::builder()
+            .port(9000)
+            .repositories(vec![Url::parse("https://repo.example.com/releases")?])
+            .log_level(log::Level::Debug)
+            .proxy_timeout(Duration::from_secs(3))
+            .build()?;
+        assert_eq!(9000, config.port());
+        let expected: Vec<Repository> = vec![Uri::from_static("https://repo.example.com/releases").into()];
+        assert_eq!(expected, config.repositories()?);
+        assert_eq!(log::Level::Debug, config.log_level());
+        assert_eq!(Duration::from_secs(3), config.proxy_timeout());
+        Ok(())
+    }
+
+    #[test]
+    fn builder_validation() -> Result<()> {
+        Config::builder().repositories(vec![]).build().expect_err("No repositories");
+        Config::builder().proxy_timeout(Duration::ZERO).build().expect_err("Zero timeout");
+        Config::builder()
+            .repositories(vec![Url::parse("data:text/plain,maven")?])
+            .build()
+            .expect_err("Repository URL is not a valid URI");
+        Ok(())
+    }
+
+    #[test]
+    fn validate_default_config() -> Result<()> {
+        Config::load_default().validate()?;
+        let config: Config = ron::de::from_str(r#"(
+            groups: [(path_prefix: "/snapshots", repositories: ["https://repo.example.com/snapshots"])],
+            path_prefix: Some("/maven"),
+            rate_limit_per_second: Some(10),
+            trust_forwarded_headers: true,
+            trusted_proxy_cidrs: ["10.0.0.0/8", "fd00::/8", "192.168.1.7"],
+            publish_repository: Some((url: "https://repo.example.com/releases"))
+        )"#)?;
+        config.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_invalid_configs() -> Result<()> {
+        for (ron, reason) in &[
+            ("(port: 0)", "Zero port"),
+            ("(listen_backlog: 0)", "Zero listen backlog"),
+            ("(server_idle_timeout: Some((secs: 0, nanos: 0)))", "Zero server idle timeout"),
+            ("(repositories: [])", "No repositories"),
+            ("(proxy_timeout: (secs: 0, nanos: 0))", "Zero timeout"),
+            ("(connect_timeout: (secs: 0, nanos: 0))", "Zero connect timeout"),
+            ("(metadata_timeout: Some((secs: 0, nanos: 0)))", "Zero metadata timeout"),
+            ("(artifact_timeout: Some((secs: 0, nanos: 0)))", "Zero artifact timeout"),
+            ("(stream_idle_timeout: Some((secs: 0, nanos: 0)))", "Zero stream idle timeout"),
+            ("(request_deadline: Some((secs: 0, nanos: 0)))", "Zero request deadline"),
+            ("(retry_timeout_multiplier: 0)", "Zero retry timeout multiplier"),
+            ("(retry_statuses: [429, 404])", "Retrying not found"),
+            ("(retry_statuses: [200])", "Retrying success"),
+            ("(retry_budget_per_second: Some(0))", "Zero retry budget"),
+            ("(success_statuses: [])", "No success statuses"),
+            ("(success_statuses: [200, 404])", "Not found as success"),
+            ("(not_found_statuses: [500])", "Server error as not found"),
+            ("(rate_limit_per_second: Some(0))", "Zero rate limit"),
+            ("(max_inflight_per_client: Some(0))", "Zero requests in flight per client"),
+            ("(trust_forwarded_headers: true)", "Trusted forwarded headers without proxies"),
+            ("(trusted_proxy_cidrs: [\"10.0.0.0/33\"])", "Invalid trusted proxy network"),
+            ("(max_bandwidth_bytes_per_sec: Some(0))", "Zero bandwidth limit"),
+            ("(max_header_count: 0)", "Zero header count"),
+            ("(max_path_length: 0)", "Zero path length"),
+            ("(circuit_breaker_threshold: Some(0))", "Zero circuit breaker threshold"),
+            ("(redirect_cache_ttl: Some((secs: 0, nanos: 0)))", "Zero redirect cache TTL"),
+            ("(max_redirects: 0, redirect_cache_ttl: Some((secs: 60, nanos: 0)))", "Redirect cache without redirects"),
+            ("(persist_repository_state_path: Some(\"repositories.json\"))", "Repository state without circuit breaker"),
+            ("(readiness_min_healthy_repositories: Some(0))", "Zero minimum healthy repositories"),
+            ("(memory_cache_capacity: Some(0))", "Zero memory cache capacity"),
+            ("(memory_cache_max_file_size: 0)", "Zero memory cache file size"),
+            ("(warmup_interval: Some((secs: 0, nanos: 0)))", "Zero warmup interval"),
+            ("(repository_index_reload_interval: Some((secs: 0, nanos: 0)))", "Zero index reload interval"),
+            ("(max_connections: Some(0))", "Zero connection limit"),
+            ("(max_fanout: Some(0))", "Zero fan-out"),
+            ("(multi_thread_runtime: true, worker_threads: Some(0))", "Zero worker threads"),
+            ("(worker_threads: Some(4))", "Worker threads without the multi-thread runtime"),
+            ("(path_prefix: Some(\"maven\"))", "Relative path prefix"),
+            ("(groups: [(path_prefix: \"snapshots\", repositories: []) ])", "Relative group prefix"),
+            ("(ca_bundle: Some(\"/nonexistent/ca.pem\"))", "Missing CA bundle"),
+            ("(tls_cert_path: Some(\"/etc/proxy/cert.pem\"))", "TLS certificate without key"),
+            ("(tls_key_path: Some(\"/etc/proxy/key.pem\"))", "TLS key without certificate"),
+            ("(local_repositories: [\"/nonexistent/maven\"])", "Missing local repository"),
+            ("(repositories: [(url: \"https://repo.example.com\", extensions: Some([\".\"]))])", "Empty extension"),
+            ("(allowed_group_prefixes: [\"com/example\"])", "Group prefix as a path"),
+            ("(allowed_group_prefixes: [\"\"])", "Empty group prefix"),
+            ("(repository_rules: [(pattern: \"com/**\", allow: [1])])", "Rule refers to a missing repository"),
+            ("(repository_weights: [1, 2])", "More weights than repositories"),
+            ("(repository_weights: [0])", "All weights are zero"),
+            ("(favicon_path: Some(\"/nonexistent/favicon.ico\"))", "Missing favicon"),
+            ("(static_responses: {\"robots.txt\": \"\"})", "Relative static response path"),
+            ("(prefetch_checksums: true)", "Prefetching checksums without a memory cache"),
+            ("(serve_stale_on_error: true)", "Stale copies without a disk cache"),
+            ("(offline_mode: true)", "Offline without a disk cache"),
+            ("(ranges_from_cache: true)", "Cached ranges without a disk cache"),
+            ("(cors_allow_origin: Some(\"\"))", "Empty CORS origin"),
+            ("(cache_max_bytes: Some(1048576))", "Cache size limit without a disk cache"),
+            ("(disk_cache_directory: Some(\"/tmp\"), cache_max_bytes: Some(0))", "Zero cache size limit"),
+            ("(cache_eviction_interval: (secs: 0, nanos: 0))", "Zero eviction interval"),
+            ("(error_page_template: Some(\"/nonexistent/error.html\"))", "Missing error page template"),
+            ("(stats_snapshot_path: Some(\"stats.json\"))", "Stats snapshot without stats"),
+            ("(distinct_paths_window: Some((secs: 3600, nanos: 0)))", "Distinct paths without stats"),
+            ("(recheck_404_after: Some((secs: 0, nanos: 0)))", "Zero 404 recheck delay"),
+            ("(slow_request_threshold: Some((secs: 0, nanos: 0)))", "Zero slow request threshold"),
+            ("(tracing_endpoint: Some(\"http://localhost:4318/v1/traces\"))", "Tracing endpoint without tracing"),
+            ("(tracing_enabled: true, tracing_endpoint: Some(\"ftp://localhost/traces\"))", "Non-HTTP tracing endpoint"),
+            ("(remember_http1_fallback: true)", "Remembering the HTTP/1.1 fallback without HTTP/2"),
+            ("(stats_enabled: true, distinct_paths_window: Some((secs: 0, nanos: 0)))", "Zero distinct paths window"),
+            ("(admin_secret: Some(\"\"))", "Empty admin secret"),
+            ("(repositories: [\"data:text/plain,maven\"])", "Repository URL is not a valid URI"),
+            ("(publish_repository: Some((url: \"data:text/plain,maven\")))", "Publish URL is not a valid URI"),
+            ("(strip_request_headers: [\"Bad Header\"])", "Invalid header name"),
+            ("(set_request_headers: [(\"User-Agent\", \"line\\nbreak\")])", "Invalid header value"),
+            ("(path_rewrites: [(\"^/(unclosed\", \"/\")])", "Invalid path rewrite pattern"),
+            ("(repositories: [(url: \"https://repo.example.com\", path_rewrites: [(\"^/(unclosed\", \"/\")])])",
+             "Invalid repository path rewrite pattern"),
+            ("(response_header_policy: Deny([\"Bad Header\"]))", "Invalid response header name"),
+            ("(user_agent: Some(\"line\\nbreak\"))", "Invalid user agent")
+        ] {
+            let config: Config = ron::de::from_str(ron)?;
+            config.validate().expect_err(reason);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn request_header_rules() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            strip_request_headers: ["authorization"],
+            set_request_headers: [("User-Agent", "rust-maven-proxy")]
+        )"#)?;
+        assert_eq!(RequestHeaderRules::new(
+            vec![hyper::header::AUTHORIZATION],
+            vec![(hyper::header::USER_AGENT, HeaderValue::from_static("rust-maven-proxy"))]
+        ), config.request_header_rules()?);
+        Ok(())
+    }
+
+    #[test]
+    fn path_rewrites() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            path_rewrites: [("^/nexus/content/groups/[^/]+/", "/")]
+        )"#)?;
+        let rewrites = config.path_rewrites()?;
+        assert_eq!(1, rewrites.len());
+        assert_eq!("^/nexus/content/groups/[^/]+/", rewrites[0].pattern());
+        assert_eq!("/", rewrites[0].replacement());
+        Ok(())
+    }
+
+    #[test]
+    fn response_header_policy() -> Result<()> {
+        assert_eq!(ResponseHeaderPolicy::PassThrough, Config::load_default().response_header_policy()?);
+        let config: Config = ron::de::from_str(r#"(response_header_policy: Deny(["Server", "X-Cache"]))"#)?;
+        assert_eq!(ResponseHeaderPolicy::Deny(vec![hyper::header::SERVER, HeaderName::from_static("x-cache")]),
+                   config.response_header_policy()?);
+        let config: Config = ron::de::from_str(r#"(response_header_policy: Allow(["X-Checksum-Sha1"]))"#)?;
+        assert_eq!(ResponseHeaderPolicy::Allow(vec![HeaderName::from_static("x-checksum-sha1")]),
+                   config.response_header_policy()?);
+        Ok(())
+    }
+
+    #[test]
+    fn repository_rules() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            repositories: ["https://internal.example.com/maven", "https://repo1.maven.org/maven2"],
+            repository_rules: [
+                (pattern: "com/mycompany/**", allow: [0]),
+                (pattern: "org/banned/**", deny: [1])
+            ]
+        )"#)?;
+        config.validate()?;
+        assert_eq!(vec![
+            RepositoryRule::new(PathPattern::new("com/mycompany/**"), vec![0], vec![]),
+            RepositoryRule::new(PathPattern::new("org/banned/**"), vec![], vec![1])
+        ], config.repository_rules());
+        Ok(())
+    }
+
+    #[test]
+    fn repository_weights() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            repositories: ["https://mirror1.example.com/maven2", "https://mirror2.example.com/maven2"],
+            repository_weights: [3, 1]
+        )"#)?;
+        config.validate()?;
+        let weights: Vec<_> = config.repositories()?.iter().map(Repository::weight).collect();
+        assert_eq!(vec![Some(3), Some(1)], weights);
+        assert!(Config::load_default().repositories()?.iter().all(|repository| repository.weight().is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn json_log_format() -> Result<()> {
+        let config: Config = ron::de::from_str("(log_format: Json)")?;
+        assert_eq!(LogFormat::Json, config.log_format());
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_config() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let config_path = temp_dir.path().join("config.ron");
+        std::fs::write(&config_path, "(\n    port: 8080,\n    repositories: [\"https://repo1.maven.org/maven2\"\n)\n")?;
+        let error = Config::load_from(&config_path).expect_err("Malformed config");
+        let message = error.to_string();
+        assert!(message.contains(&config_path.display().to_string()), "{}", message);
+        assert!(message.contains("line 4, column"), "{}", message);
+
+        let config_path = temp_dir.path().join("config.json");
+        std::fs::write(&config_path, "{\"port\": 8080,}")?;
+        let message = Config::load_from(&config_path).expect_err("Malformed config").to_string();
+        assert!(message.contains("line 1 column"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_config_directory() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let config_path = temp_dir.path().join("missing").join("config.ron");
+        Config::load_from(&config_path).expect_err("Parent directory does not exist");
+        assert!(!config_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn plain_and_detailed_repositories() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            repositories: [
+                "https://repo1.maven.org/maven2",
+                (url: "https://internal.example.com/maven2", timeout: Some((secs: 2, nanos: 0))),
+                (url: "https://binaries.example.com/maven2", extensions: Some(["jar", "war"])),
+                (url: "https://artifactory.example.com", path_rewrites: [("^/", "/artifactory/libs-release/")]),
+                (url: "https://mirror.internal/maven2", downgrade_to_http: true),
+                (url: "https://legacy.example.com/maven2", head_as_get: true)
+            ]
+        )"#)?;
+        let expected: Vec<Repository> = vec![
+            Uri::from_str("https://repo1.maven.org/maven2")?.into(),
+            Repository::new(Uri::from_str("https://internal.example.com/maven2")?)
+                .with_timeout(Duration::from_secs(2)),
+            Repository::new(Uri::from_str("https://binaries.example.com/maven2")?)
+                .with_extensions(vec!["jar".to_owned(), "war".to_owned()]),
+            Repository::new(Uri::from_str("https://artifactory.example.com")?)
+                .with_path_rewrites(vec![PathRewrite::new("^/", "/artifactory/libs-release/")?]),
+            Repository::new(Uri::from_str("https://mirror.internal/maven2")?)
+                .with_http_downgrade(true),
+            Repository::new(Uri::from_str("https://legacy.example.com/maven2")?)
+                .with_head_as_get(true)
+        ];
+        assert_eq!(expected, config.repositories()?);
+        Ok(())
+    }
+
+    #[test]
+    fn repository_timeout_round_trip() -> Result<()> {
+        let mut config = Config::load_default();
+        config.repositories[0].timeout = Some(Duration::from_millis(1500));
+        let serialized = ron::ser::to_string(&config)?;
+        assert_eq!(config, ron::de::from_str(&serialized)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bind_address() -> Result<()> {
+        let defaults = Config::load_default();
+        assert_eq!(IpAddr::V4(Ipv4Addr::LOCALHOST), defaults.bind_address());
+        assert!(defaults.dual_stack());
+        let config: Config = ron::de::from_str("(bind_address: \"::\", dual_stack: false)")?;
+        assert_eq!(IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED), config.bind_address());
+        assert!(!config.dual_stack());
+        assert_eq!(config, ron::de::from_str(&ron::ser::to_string(&config)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn pool_settings_round_trip() -> Result<()> {
+        let config: Config = ron::de::from_str(
+            "(pool_idle_timeout: (secs: 30, nanos: 0), pool_max_idle_per_host: 8)")?;
+        assert_eq!(Duration::from_secs(30), config.pool_idle_timeout());
+        assert_eq!(8, config.pool_max_idle_per_host());
+        let serialized = ron::ser::to_string(&config)?;
+        assert_eq!(config, ron::de::from_str(&serialized)?);
+
+        let defaults = Config::load_default();
+        assert_eq!(defaults, ron::de::from_str(&ron::ser::to_string(&defaults)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn url_not_usable_as_uri() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(repositories: ["data:text/plain,maven"])"#)?;
+        match config.repositories() {
+            Err(ProxyError::InvalidRepositoryUrl { url, .. }) => assert_eq!("data:text/plain,maven", url),
+            other => panic!("Expected invalid repository URL, got {:?}", other)
+        }
+        Ok(())
+    }
+
+    fn repository_config(url: &str) -> RepositoryConfig {
+        RepositoryConfig {
+            url: url.to_string(),
+            timeout: None,
+            extensions: None,
+            path_rewrites: Vec::new(),
+            downgrade_to_http: false,
+            head_as_get: false,
+            base_path: None,
+            headers: Vec::new(),
+            index_path: None
+        }
+    }
+
+    #[test]
+    fn repository_headers() -> Result<()> {
+        let config: Config = ron::de::from_str(r#"(
+            repositories: [
+                (url: "https://private.example.com/maven2", headers: [("X-JFrog-Art-Api", "secret-key")]),
+                "https://repo1.maven.org/maven2"
+            ]
+        )"#)?;
+        let repositories = config.repositories()?;
+        let mut headers = hyper::HeaderMap::new();
+        repositories[0].apply_headers(&mut headers);
+        assert_eq!("secret-key", headers["x-jfrog-art-api"]);
+        let mut headers = hyper::HeaderMap::new();
+        repositories[1].apply_headers(&mut headers);
+        assert!(headers.is_empty());
+
+        let config: Config = ron::de::from_str(r#"(
+            repositories: [(url: "https://private.example.com/maven2", headers: [("Authorization", "line
+break")])]
+        )"#)?;
+        config.validate().expect_err("Invalid header value");
+        Ok(())
+    }
+
+    #[test]
+    fn repository_index() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let index_path = directory.path().join("index.txt");
+        std::fs::write(&index_path, "/org/example/1.0/example-1.0.jar\n")?;
+        let config = |index_path: &Path| -> Result<Config> {
+            Ok(ron::de::from_str(&format!(
+                "(repositories: [(url: \"https://mirror.example.com/maven2\", index_path: Some({:?}))])", index_path))?)
+        };
+        let repositories = config(&index_path)?.repositories()?;
+        let index = repositories[0].index().expect("The index is loaded");
+        assert!(index.contains("/org/example/1.0/example-1.0.jar"));
+        assert!(!index.contains("/org/example/1.0/example-1.0.pom"));
+
+        let missing = config(&directory.path().join("missing.txt"))?;
+        missing.validate().expect_err("Missing index");
+        Ok(())
+    }
+
+    #[test]
+    fn normalize_repository_urls() -> Result<()> {
+        for (url, normalized) in [
+            ("https://repo1.maven.org/maven2", "https://repo1.maven.org/maven2"),
+            ("https://repo1.maven.org/maven2/", "https://repo1.maven.org/maven2"),
+            ("https://repo1.maven.org/maven2//", "https://repo1.maven.org/maven2"),
+            ("HTTPS://Repo1.Maven.org/maven2", "https://repo1.maven.org/maven2"),
+            (" http://localhost:8081/repository/releases/ ", "http://localhost:8081/repository/releases"),
+            ("https://repo.example.com", "https://repo.example.com/"),
+            ("https://repo.example.com///", "https://repo.example.com/")
+        ] {
+            assert_eq!(Uri::from_str(normalized)?, *repository_config(url).to_repository()?.uri(), "{}", url);
+        }
+        for url in [
+            "repo1.maven.org/maven2",
+            "/maven2",
+            "ftp://repo.example.com/maven2",
+            "https://repo.example.com/maven2?token=abc",
+            "https://repo.example.com/maven2#releases",
+            "https://",
+            "file:///var/maven"
+        ] {
+            match repository_config(url).to_repository() {
+                Err(error @ ProxyError::InvalidRepositoryUrl { .. }) => {
+                    assert!(error.to_string().contains(url), "{} does not name {}", error, url);
+                },
+                other => panic!("Expected {} to be rejected, got {:?}", url, other)
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn file_repository_urls() {
+        for url in ["file:///var/maven", "FILE:///var/maven", " file:///var/maven", "file:/var/maven"] {
+            assert!(repository_config(url).is_file(), "{}", url);
+        }
+        for url in ["https://file.example.com/maven2", "/var/maven", "file"] {
+            assert!(!repository_config(url).is_file(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn repository_base_path() -> Result<()> {
+        let with_base_path = |url: &str, base_path: &str| RepositoryConfig {
+            base_path: Some(base_path.to_string()),
+            ..repository_config(url)
+        };
+        for (url, base_path, expected) in [
+            ("https://internal.example.com", "/maven2", "https://internal.example.com/maven2"),
+            ("https://internal.example.com/", "/repository/maven-public/", "https://internal.example.com/repository/maven-public"),
+            ("https://internal.example.com/releases", "/maven2", "https://internal.example.com/releases")
+        ] {
+            assert_eq!(Uri::from_str(expected)?, *with_base_path(url, base_path).to_repository()?.uri(), "{}", url);
+        }
+        for base_path in ["maven2", "/maven2?token=abc"] {
+            with_base_path("https://internal.example.com", base_path).to_repository().expect_err(base_path);
+        }
+
+        let config: Config = ron::de::from_str(r#"(
+            repositories: [(url: "https://internal.example.com", base_path: Some("/maven2"))]
+        )"#)?;
+        let repositories = config.repositories()?;
+        assert_eq!(Uri::from_static("https://internal.example.com/maven2"), *repositories[0].uri());
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_repositories() -> Result<()> {
+        for (repositories, duplicates) in [
+            (r#"["https://repo1.maven.org/maven2", "https://repo1.maven.org/maven2"]"#, vec!["https://repo1.maven.org/maven2"]),
+            (r#"["https://repo1.maven.org/maven2/", "https://REPO1.maven.org/maven2"]"#, vec!["https://repo1.maven.org/maven2"]),
+            (r#"["https://repo1.maven.org/maven2", "https://repo1.maven.org/maven2/releases"]"#, vec![]),
+            (r#"["https://repo1.maven.org/maven2", "https://mirror.example.com/maven2"]"#, vec![])
+        ] {
+            let config: Config = ron::de::from_str(&format!("(repositories: {})", repositories))?;
+            assert_eq!(duplicates, config.duplicate_repositories(), "{}", repositories);
+            // Duplicates are only warned about by default
+            config.validate()?;
+            let config: Config = ron::de::from_str(
+                &format!("(reject_duplicate_repositories: true, repositories: {})", repositories))?;
+            assert_eq!(duplicates.is_empty(), config.validate().is_ok(), "{}", repositories);
+        }
+        let config: Config = ron::de::from_str(r#"(
+            repositories: ["https://repo1.maven.org/maven2"],
+            fallback_repositories: ["https://repo1.maven.org/maven2/"]
+        )"#)?;
+        assert_eq!(vec!["https://repo1.maven.org/maven2"], config.duplicate_repositories());
+        Ok(())
+    }
+
+    #[test]
+    fn file_repositories() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let file_url = Url::from_directory_path(directory.path()).unwrap();
+        let ron = format!(r#"(repositories: ["https://repo1.maven.org/maven2", "{}"], repository_weights: [1])"#, file_url);
+        let config: Config = ron::de::from_str(&ron)?;
+        config.validate().expect_err("file:// repositories are disabled by default");
+
+        let config: Config = ron::de::from_str(&ron.replace("(repositories", "(allow_file_repositories: true, repositories"))?;
+        config.validate()?;
+        assert_eq!(vec![Repository::new(Uri::from_static("https://repo1.maven.org/maven2")).with_weight(1)],
+                   config.repositories()?);
+        assert_eq!(1, config.local_repositories().len());
+
+        let missing = ron.replace(&file_url.to_string(), "file:///nonexistent/maven")
+            .replace("(repositories", "(allow_file_repositories: true, repositories");
+        let config: Config = ron::de::from_str(&missing)?;
+        config.validate().expect_err("Missing file:// repository");
+        Ok(())
+    }
+
+    #[test]
+    fn allow_empty_repositories() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let ron = format!(r#"(repositories: [], local_repositories: ["{}"])"#, directory.path().display());
+        let config: Config = ron::de::from_str(&ron)?;
+        config.validate().expect_err("No remote repositories");
+        let file_url = Url::from_directory_path(directory.path()).unwrap();
+        let only_files = format!(r#"(allow_file_repositories: true, repositories: ["{}"])"#, file_url);
+        let config: Config = ron::de::from_str(&only_files)?;
+        config.validate().expect_err("Only file:// repositories");
+
+        let config: Config = ron::de::from_str(&ron.replace("(repositories", "(allow_empty_repositories: true, repositories"))?;
+        config.validate()?;
+        assert!(config.repositories()?.is_empty());
+        assert_eq!(1, config.local_repositories().len());
+
+        let offline = format!(r#"(offline_mode: true, disk_cache_directory: Some("{}"), repositories: [])"#, directory.path().display());
+        let config: Config = ron::de::from_str(&offline)?;
+        config.validate()?;
+        Ok(())
+    }
+}
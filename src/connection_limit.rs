@@ -0,0 +1,183 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use futures_util::future::poll_fn;
+use futures_util::{ready, Future, Stream};
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, Sleep};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+// An accepted connection, holding its share of the connection limit until closed
+pub struct LimitedConnection {
+    remote_addr: SocketAddr,
+    stream: ConnectionStream,
+    // The number of requests on this connection being handled
+    busy: Arc<AtomicUsize>,
+    idle_timeout: Option<IdleTimeout>,
+    _permit: Option<OwnedSemaphorePermit>
+}
+
+// Closes connections which neither transfer data nor have a request being handled for the timeout
+struct IdleTimeout {
+    timeout: Duration,
+    sleep: Pin<Box<Sleep>>
+}
+
+// The TLS handshake is completed on first use, by the task serving the connection,
+// so that slow handshakes do not hold up accepting other connections
+enum ConnectionStream {
+    Plain(AddrStream),
+    Handshaking(Box<tokio_rustls::Accept<AddrStream>>),
+    Tls(Box<TlsStream<AddrStream>>)
+}
+
+impl LimitedConnection {
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    // Counts requests being handled, during which the connection is not considered idle
+    pub fn busy(&self) -> Arc<AtomicUsize> {
+        self.busy.clone()
+    }
+
+    // Any transfer restarts the idle timeout. Waiting to read fails once the timeout has passed,
+    // unless a request is being handled
+    fn poll_idle<T>(&mut self, context: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        let idle_timeout = match &mut self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return poll
+        };
+        if poll.is_ready() || self.busy.load(Ordering::SeqCst) > 0 {
+            let deadline = Instant::now() + idle_timeout.timeout;
+            idle_timeout.sleep.as_mut().reset(deadline);
+            return poll;
+        }
+        match idle_timeout.sleep.as_mut().poll(context) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "Connection is idle"))),
+            Poll::Pending => Poll::Pending
+        }
+    }
+
+    fn poll_handshake(&mut self, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let ConnectionStream::Handshaking(accept) = &mut self.stream {
+            let stream = ready!(Pin::new(accept).poll(context))?;
+            self.stream = ConnectionStream::Tls(Box::new(stream));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Accepts connections only while fewer than the limit are open. Once the limit is
+// reached, further connections wait in the listen backlog until one is closed
+pub fn limit_connections(incoming: AddrIncoming,
+                         limit: Option<usize>,
+                         tls_acceptor: Option<TlsAcceptor>,
+                         idle_timeout: Option<Duration>) -> impl Stream<Item=io::Result<LimitedConnection>> {
+    let semaphore = limit.map(|limit| Arc::new(Semaphore::new(limit)));
+    futures_util::stream::unfold((incoming, semaphore, tls_acceptor), |(mut incoming, semaphore, tls_acceptor)| async move {
+        // The semaphore is never closed, so acquiring a permit cannot fail
+        let permit = match &semaphore {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None
+        };
+        let stream = poll_fn(|context| Pin::new(&mut incoming).poll_accept(context)).await?;
+        let connection = stream.map(|stream| LimitedConnection {
+            remote_addr: stream.remote_addr(),
+            stream: match &tls_acceptor {
+                Some(tls_acceptor) => ConnectionStream::Handshaking(Box::new(tls_acceptor.accept(stream))),
+                None => ConnectionStream::Plain(stream)
+            },
+            busy: Arc::new(AtomicUsize::new(0)),
+            idle_timeout: idle_timeout.map(|timeout| IdleTimeout {
+                timeout,
+                sleep: Box::pin(tokio::time::sleep(timeout))
+            }),
+            _permit: permit
+        });
+        Some((connection, (incoming, semaphore, tls_acceptor)))
+    })
+}
+
+impl LimitedConnection {
+    fn poll_read_stream(&mut self, context: &mut Context<'_>, buffer: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_handshake(context))?;
+        match &mut self.stream {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_read(context, buffer),
+            ConnectionStream::Tls(stream) => Pin::new(stream).poll_read(context, buffer),
+            ConnectionStream::Handshaking(_) => unreachable!("Handshake is complete")
+        }
+    }
+
+    fn poll_write_stream(&mut self, context: &mut Context<'_>, buffer: &[u8]) -> Poll<io::Result<usize>> {
+        ready!(self.poll_handshake(context))?;
+        match &mut self.stream {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_write(context, buffer),
+            ConnectionStream::Tls(stream) => Pin::new(stream).poll_write(context, buffer),
+            ConnectionStream::Handshaking(_) => unreachable!("Handshake is complete")
+        }
+    }
+}
+
+impl AsyncRead for LimitedConnection {
+    fn poll_read(mut self: Pin<&mut Self>,
+                 context: &mut Context<'_>,
+                 buffer: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let poll = self.poll_read_stream(context, buffer);
+        self.poll_idle(context, poll)
+    }
+}
+
+impl AsyncWrite for LimitedConnection {
+    fn poll_write(mut self: Pin<&mut Self>,
+                  context: &mut Context<'_>,
+                  buffer: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = self.poll_write_stream(context, buffer);
+        self.poll_idle(context, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_handshake(context))?;
+        match &mut self.stream {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_flush(context),
+            ConnectionStream::Tls(stream) => Pin::new(stream).poll_flush(context),
+            ConnectionStream::Handshaking(_) => unreachable!("Handshake is complete")
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_handshake(context))?;
+        match &mut self.stream {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_shutdown(context),
+            ConnectionStream::Tls(stream) => Pin::new(stream).poll_shutdown(context),
+            ConnectionStream::Handshaking(_) => unreachable!("Handshake is complete")
+        }
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Body, HeaderMap, Method, Request, Response, StatusCode, http};
+use hyper::header::{HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+                    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY};
+use eyre::Result;
+use crate::request::AllowedMethod;
+
+// The request headers which browser tools may send, beyond those always allowed
+const ALLOWED_HEADERS: &str = "Authorization, If-Modified-Since, If-None-Match, Range, X-Request-Id";
+// The response headers which browser tools may read, beyond those always exposed
+const EXPOSED_HEADERS: &str = "Content-Length, ETag, X-Request-Id, X-Served-By";
+// How long browsers may remember a preflight response, in seconds
+const MAX_AGE_SECS: u32 = 600;
+
+// Lets browser-based tools on the allowed origin, or any origin for *, read responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsPolicy {
+    allow_origin: HeaderValue
+}
+
+impl CorsPolicy {
+    pub fn new(allow_origin: HeaderValue) -> Self {
+        Self {
+            allow_origin
+        }
+    }
+
+    // Browsers send OPTIONS with the method they intend to use before cross-origin requests
+    pub fn is_preflight(request: &Request<Body>) -> bool {
+        request.method() == Method::OPTIONS
+            && request.headers().contains_key(ORIGIN)
+            && request.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    }
+
+    pub fn preflight_response(&self,
+                              version: http::version::Version,
+                              enabled: &[AllowedMethod]) -> Result<Response<Body>> {
+        let methods = enabled.iter()
+            .map(|method| Method::from(method).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut response = Response::builder()
+            .version(version)
+            .status(StatusCode::NO_CONTENT)
+            .header(ACCESS_CONTROL_ALLOW_METHODS, methods)
+            .header(ACCESS_CONTROL_ALLOW_HEADERS, ALLOWED_HEADERS)
+            .header(ACCESS_CONTROL_MAX_AGE, MAX_AGE_SECS)
+            .body(Body::empty())?;
+        self.apply(response.headers_mut());
+        Ok(response)
+    }
+
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin.clone());
+        headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSED_HEADERS));
+        // Caches must not serve a response allowing one origin to another
+        if self.allow_origin != "*" {
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preflight_detection() -> Result<()> {
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .header(ORIGIN, "https://browser.example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())?;
+        assert!(CorsPolicy::is_preflight(&preflight));
+        let options = Request::builder().method(Method::OPTIONS).body(Body::empty())?;
+        assert!(!CorsPolicy::is_preflight(&options));
+        Ok(())
+    }
+
+    #[test]
+    fn vary_on_specific_origin() {
+        let mut headers = HeaderMap::new();
+        CorsPolicy::new(HeaderValue::from_static("*")).apply(&mut headers);
+        assert_eq!("*", headers[ACCESS_CONTROL_ALLOW_ORIGIN]);
+        assert!(!headers.contains_key(VARY));
+
+        let mut headers = HeaderMap::new();
+        CorsPolicy::new(HeaderValue::from_static("https://browser.example.com")).apply(&mut headers);
+        assert_eq!("https://browser.example.com", headers[ACCESS_CONTROL_ALLOW_ORIGIN]);
+        assert_eq!("Origin", headers[VARY]);
+    }
+}
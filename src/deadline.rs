@@ -0,0 +1,100 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::time::{Duration, Instant};
+use rand::Rng;
+
+// The time by which a client request must be answered, shared by every upstream request made for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn after(start: Instant, duration: Duration) -> Self {
+        Self(start + duration)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining_at(Instant::now())
+    }
+
+    fn remaining_at(&self, now: Instant) -> Duration {
+        self.0.saturating_duration_since(now)
+    }
+}
+
+// Randomly lengthens the timeout by up to the jitter, so that requests started together do not all
+// time out together, then shortens it to the time remaining before the deadline
+pub fn effective_timeout<R: Rng>(timeout: Duration,
+                                 jitter: Duration,
+                                 deadline: Option<Deadline>,
+                                 rng: &mut R) -> Duration {
+    effective_timeout_at(timeout, jitter, deadline, rng, Instant::now())
+}
+
+fn effective_timeout_at<R: Rng>(timeout: Duration,
+                                jitter: Duration,
+                                deadline: Option<Deadline>,
+                                rng: &mut R,
+                                now: Instant) -> Duration {
+    let jittered = if jitter.is_zero() {
+        timeout
+    } else {
+        timeout.saturating_add(jitter.mul_f64(rng.gen::<f64>()))
+    };
+    match deadline {
+        Some(deadline) => jittered.min(deadline.remaining_at(now)),
+        None => jittered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn jitter_within_range() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let timeout = Duration::from_secs(5);
+        let jitter = Duration::from_millis(500);
+        let timeouts: Vec<Duration> = (0..100)
+            .map(|_| effective_timeout(timeout, jitter, None, &mut rng))
+            .collect();
+        assert!(timeouts.iter().all(|&effective| effective >= timeout && effective <= timeout + jitter));
+        assert!(timeouts.iter().any(|&effective| effective != timeouts[0]), "Timeouts are not randomized");
+        assert_eq!(timeout, effective_timeout(timeout, Duration::ZERO, None, &mut rng));
+    }
+
+    #[test]
+    fn shortened_by_deadline() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let start = Instant::now();
+        let deadline = Deadline::after(start, Duration::from_secs(10));
+        let timeout = Duration::from_secs(5);
+        let jitter = Duration::from_secs(1);
+        for elapsed in [0, 4, 7, 10, 20] {
+            let now = start + Duration::from_secs(elapsed);
+            let effective = effective_timeout_at(timeout, jitter, Some(deadline), &mut rng, now);
+            let remaining = Duration::from_secs(10u64.saturating_sub(elapsed));
+            assert!(effective <= remaining, "{:?} exceeds the {:?} remaining", effective, remaining);
+            assert!(effective >= timeout.min(remaining));
+        }
+    }
+}
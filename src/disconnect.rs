@@ -0,0 +1,104 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Body, Response};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderValue, CONTENT_LENGTH};
+use crate::body_observer::{self, BodyObserver};
+
+// Calls on_disconnect with the number of bytes sent if the body is dropped before it
+// has been fully sent, which happens when the client disconnects. The wrapped body,
+// and with it any upstream connection it is read from, is dropped at the same time
+pub fn watch_body<F>(mut response: Response<Body>, on_disconnect: F) -> Response<Body>
+    where F: FnOnce(u64) + Send + 'static {
+
+    let length = HttpBody::size_hint(response.body()).exact();
+    // The length would otherwise be lost by wrapping the body
+    if let Some(length) = length.filter(|_| !response.headers().contains_key(CONTENT_LENGTH)) {
+        response.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(length));
+    }
+    body_observer::observe_body(response, Disconnect {
+        on_disconnect: Some(on_disconnect)
+    })
+}
+
+struct Disconnect<F> {
+    on_disconnect: Option<F>
+}
+
+impl<F: FnOnce(u64) + Send + 'static> BodyObserver for Disconnect<F> {
+    fn data(&mut self, _data: &Bytes) {}
+
+    fn complete(&mut self, _sent: u64) {}
+
+    fn abandoned(&mut self, sent: u64) {
+        if let Some(on_disconnect) = self.on_disconnect.take() {
+            on_disconnect(sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use eyre::Result;
+
+    fn watched(body: Body) -> (Response<Body>, Arc<AtomicU64>) {
+        let disconnected = Arc::new(AtomicU64::new(u64::MAX));
+        let recorder = disconnected.clone();
+        let response = watch_body(Response::new(body), move |sent| recorder.store(sent, Ordering::SeqCst));
+        (response, disconnected)
+    }
+
+    #[tokio::test]
+    async fn fully_sent() -> Result<()> {
+        let (response, disconnected) = watched(Body::from("artifact"));
+        assert_eq!("8", response.headers()[CONTENT_LENGTH]);
+        assert_eq!("artifact", hyper::body::to_bytes(response.into_body()).await?);
+        assert_eq!(u64::MAX, disconnected.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn announced_length_sent() -> Result<()> {
+        let (response, disconnected) = watched(Body::from("artifact"));
+        let mut body = response.into_body();
+        assert_eq!("artifact", body.data().await.expect("The body has data")?);
+        drop(body);
+        assert_eq!(u64::MAX, disconnected.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dropped_mid_body() -> Result<()> {
+        let (mut sender, body) = Body::channel();
+        let (response, disconnected) = watched(body);
+        assert!(!response.headers().contains_key(CONTENT_LENGTH));
+        let mut body = response.into_body();
+        sender.send_data(Bytes::from_static(b"first")).await?;
+        assert_eq!("first", body.data().await.expect("The body has data")?);
+        drop(body);
+        assert_eq!(5, disconnected.load(Ordering::SeqCst));
+        // The wrapped body is released too
+        assert!(sender.send_data(Bytes::from_static(b"second")).await.is_err());
+        Ok(())
+    }
+}
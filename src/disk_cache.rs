@@ -0,0 +1,631 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use hyper::{Body, HeaderMap, Response, StatusCode};
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use crate::body_observer::{self, BodyObserver};
+use crate::byte_range::ByteRange;
+use crate::conditional::{self, ResponseStore, StoredRange, StoredResponse};
+use crate::local_repository;
+
+// Files being written have this extension until they are complete, and are never read
+const PARTIAL_EXTENSION: &str = "part";
+const HEADERS_EXTENSION: &str = "headers";
+// Only the headers needed to serve and revalidate a stored copy are kept
+const STORED_HEADERS: [HeaderName; 3] = [CONTENT_TYPE, LAST_MODIFIED, ETAG];
+// Chunks waiting to be written for a single copy. A writer which falls further behind the client
+// abandons its copy, rather than holding the rest of the body in memory
+const WRITE_QUEUE_CHUNKS: usize = 64;
+
+// Keeps copies of served artifacts on disk, written as they are streamed to clients.
+// A copy only becomes visible once its whole body has been received
+#[derive(Debug)]
+pub struct DiskCache {
+    directory: PathBuf,
+    // Distinguishes concurrent writes of the same file
+    write_counter: AtomicU64,
+    writes: Mutex<Vec<PendingWrite>>,
+    // When each copy was last read, by the path of its body
+    accessed: Mutex<HashMap<PathBuf, SystemTime>>
+}
+
+// How large and how old the disk cache may grow. Either limit may be absent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvictionLimits {
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>
+}
+
+impl EvictionLimits {
+    pub fn new(max_bytes: Option<u64>, max_age: Option<Duration>) -> Self {
+        Self {
+            max_bytes,
+            max_age
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_age.is_none()
+    }
+}
+
+// What a single eviction removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Evicted {
+    pub copies: usize,
+    pub bytes: u64
+}
+
+#[derive(Debug)]
+struct StoredCopy {
+    body_path: PathBuf,
+    size: u64,
+    stored: SystemTime,
+    last_used: SystemTime
+}
+
+#[derive(Debug)]
+struct PendingWrite {
+    partial_path: PathBuf,
+    // Set once the whole body has been handed to the writer
+    complete: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    task: JoinHandle<()>
+}
+
+impl DiskCache {
+    // Partial files left behind by an earlier crash are removed
+    pub fn open(directory: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(directory)?;
+        remove_partial_files(directory)?;
+        Ok(Self {
+            directory: directory.to_owned(),
+            write_counter: AtomicU64::new(0),
+            writes: Mutex::new(Vec::new()),
+            accessed: Mutex::new(HashMap::new())
+        })
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        let digest = Sha1::digest(key.as_bytes());
+        let name: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.directory.join(name)
+    }
+
+    // Copies successful, unencoded responses to disk while they are served
+    pub fn store(&self, key: &str, response: Response<Body>) -> Response<Body> {
+        if response.status() != StatusCode::OK || response.headers().contains_key(CONTENT_ENCODING) {
+            return response;
+        }
+        let destination = self.path_for(key);
+        let write_number = self.write_counter.fetch_add(1, Ordering::Relaxed);
+        let partial_path = destination.with_extension(format!("{}.{}", write_number, PARTIAL_EXTENSION));
+        let headers: BTreeMap<String, String> = STORED_HEADERS.iter()
+            .filter_map(|name| {
+                let value = response.headers().get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_owned()))
+            })
+            .collect();
+
+        let (sender, receiver) = mpsc::channel(WRITE_QUEUE_CHUNKS);
+        let complete = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn(write_file(partial_path.clone(), destination, headers, receiver,
+                                           complete.clone(), finished.clone()));
+        let mut writes = self.writes.lock().unwrap();
+        writes.retain(|write| !write.finished.load(Ordering::SeqCst));
+        writes.push(PendingWrite {
+            partial_path,
+            complete: complete.clone(),
+            finished,
+            task
+        });
+        drop(writes);
+
+        body_observer::observe_body(response, Tee {
+            sender: Some(sender),
+            complete
+        })
+    }
+
+    // Streams only the part of a stored copy asked for by the client's Range header. None when the
+    // range is ignored, so that the whole body is fetched and streamed as usual. Blocks while opening
+    // the copy, like get
+    pub fn get_range(&self, key: &str, range: &HeaderValue, if_range: Option<&HeaderValue>) -> Option<StoredRange> {
+        let path = self.path_for(key);
+        let mut file = std::fs::File::open(&path).ok()?;
+        let length = file.metadata().ok()?.len();
+        let headers = read_headers(&path)?;
+        let range = conditional::requested_range(&headers, length, range, if_range);
+        let body = match range {
+            ByteRange::Whole => return None,
+            ByteRange::Partial { first, last } => {
+                file.seek(SeekFrom::Start(first)).ok()?;
+                local_repository::file_body(tokio::fs::File::from_std(file).take(last - first + 1))
+            },
+            ByteRange::Unsatisfiable => Body::empty()
+        };
+        self.accessed.lock().unwrap().insert(path, SystemTime::now());
+        Some(StoredRange::new(headers, range, body, length))
+    }
+
+    // Returns whether a stored copy was removed. A write still in progress stores its copy once complete
+    pub fn remove(&self, key: &str) -> io::Result<bool> {
+        remove_copy(&self.path_for(key))
+    }
+
+    // Returns the number of stored copies removed
+    pub fn clear(&self) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if is_stored_body(&path) && remove_copy(&path)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    // Removes copies stored longer ago than the maximum age, then the least recently used copies
+    // until the rest fit within the maximum size. Copies not read since startup were last used
+    // when they were stored
+    pub fn evict(&self, limits: EvictionLimits) -> io::Result<Evicted> {
+        self.evict_at(limits, SystemTime::now())
+    }
+
+    fn evict_at(&self, limits: EvictionLimits, now: SystemTime) -> io::Result<Evicted> {
+        let mut copies = self.stored_copies()?;
+        let mut evicted = Evicted::default();
+        let mut evict = |copy: &StoredCopy| -> io::Result<()> {
+            if remove_copy(&copy.body_path)? {
+                evicted.copies += 1;
+                evicted.bytes += copy.size;
+            }
+            Ok(())
+        };
+        if let Some(max_age) = limits.max_age {
+            let (expired, fresh): (Vec<StoredCopy>, Vec<StoredCopy>) = copies.into_iter()
+                .partition(|copy| now.duration_since(copy.stored).is_ok_and(|age| age > max_age));
+            for copy in &expired {
+                evict(copy)?;
+            }
+            copies = fresh;
+        }
+        if let Some(max_bytes) = limits.max_bytes {
+            copies.sort_by_key(|copy| copy.last_used);
+            let mut total: u64 = copies.iter().map(|copy| copy.size).sum();
+            let mut least_recently_used = 0;
+            while total > max_bytes && least_recently_used < copies.len() {
+                let copy = &copies[least_recently_used];
+                evict(copy)?;
+                total -= copy.size;
+                least_recently_used += 1;
+            }
+            copies.drain(..least_recently_used);
+        }
+        // Forget copies which no longer exist, however they were removed
+        let remaining: HashSet<&PathBuf> = copies.iter().map(|copy| &copy.body_path).collect();
+        self.accessed.lock().unwrap().retain(|path, _| remaining.contains(path));
+        Ok(evicted)
+    }
+
+    fn stored_copies(&self) -> io::Result<Vec<StoredCopy>> {
+        let accessed = self.accessed.lock().unwrap().clone();
+        let mut copies = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let body_path = entry?.path();
+            if !is_stored_body(&body_path) {
+                continue;
+            }
+            let metadata = match std::fs::metadata(&body_path) {
+                Ok(metadata) => metadata,
+                // Removed since the directory was read
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error)
+            };
+            let headers_size = std::fs::metadata(body_path.with_extension(HEADERS_EXTENSION))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            let stored = metadata.modified()?;
+            let last_used = accessed.get(&body_path).copied().unwrap_or(stored).max(stored);
+            copies.push(StoredCopy {
+                body_path,
+                size: metadata.len() + headers_size,
+                stored,
+                last_used
+            });
+        }
+        Ok(copies)
+    }
+
+    // Runs until aborted, logging what each eviction removed
+    pub async fn evict_periodically(self: Arc<Self>, limits: EvictionLimits, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            let cache = self.clone();
+            match tokio::task::spawn_blocking(move || cache.evict(limits)).await {
+                Ok(Ok(evicted)) if evicted.copies > 0 => log::info!(
+                    "Evicted {} copies totalling {} bytes from the disk cache", evicted.copies, evicted.bytes),
+                Ok(Ok(_)) => log::debug!("Evicted nothing from the disk cache"),
+                Ok(Err(error)) => log::warn!("Unable to evict from the disk cache: {}", error),
+                Err(error) => log::warn!("Disk cache eviction failed: {}", error)
+            }
+        }
+    }
+
+    // Called on shutdown. Writes which have received their whole body are completed, and
+    // any others are abandoned, so that no partial file remains
+    pub async fn finalize(&self) -> io::Result<()> {
+        let writes = std::mem::take(&mut *self.writes.lock().unwrap());
+        for write in writes {
+            if !write.complete.load(Ordering::SeqCst) {
+                write.task.abort();
+            }
+            let _ = write.task.await;
+            match tokio::fs::remove_file(&write.partial_path).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        remove_partial_files(&self.directory)
+    }
+}
+
+// Hands each chunk of a response body to its writer. The writer is told the body is complete
+// by the sender being dropped once complete is set, so a failed body, or one the writer could
+// not keep up with, is never completed and its partial file is discarded
+struct Tee {
+    sender: Option<mpsc::Sender<Bytes>>,
+    complete: Arc<AtomicBool>
+}
+
+impl BodyObserver for Tee {
+    fn data(&mut self, data: &Bytes) {
+        if let Some(sender) = &self.sender {
+            if let Err(mpsc::error::TrySendError::Full(_)) = sender.try_send(data.clone()) {
+                log::debug!("Abandoning a copy for the disk cache, since writing it fell behind the client");
+                self.sender = None;
+            }
+        }
+    }
+
+    fn complete(&mut self, _sent: u64) {
+        if self.sender.take().is_some() {
+            self.complete.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn failed(&mut self, _sent: u64) {
+        self.sender = None;
+    }
+}
+
+impl ResponseStore for DiskCache {
+    fn get(&self, key: &str) -> Option<StoredResponse> {
+        let path = self.path_for(key);
+        // The body is moved into place last, so its presence means the copy is complete
+        let body = std::fs::read(&path).ok()?;
+        self.accessed.lock().unwrap().insert(path.clone(), SystemTime::now());
+        Some(StoredResponse::new(read_headers(&path)?, Bytes::from(body)))
+    }
+}
+
+fn read_headers(body_path: &Path) -> Option<HeaderMap> {
+    let headers = std::fs::read(body_path.with_extension(HEADERS_EXTENSION)).ok()?;
+    let headers: BTreeMap<String, String> = serde_json::from_slice(&headers).ok()?;
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        header_map.insert(name, HeaderValue::from_str(&value).ok()?);
+    }
+    Some(header_map)
+}
+
+async fn write_file(partial_path: PathBuf,
+                    destination: PathBuf,
+                    headers: BTreeMap<String, String>,
+                    receiver: mpsc::Receiver<Bytes>,
+                    complete: Arc<AtomicBool>,
+                    finished: Arc<AtomicBool>) {
+    match write_chunks(&partial_path, &destination, headers, receiver, &complete).await {
+        Ok(true) => log::trace!("Stored {} in the disk cache", destination.display()),
+        Ok(false) => {
+            let _ = tokio::fs::remove_file(&partial_path).await;
+        },
+        Err(error) => {
+            log::warn!("Unable to write {} to the disk cache: {}", destination.display(), error);
+            let _ = tokio::fs::remove_file(&partial_path).await;
+        }
+    }
+    finished.store(true, Ordering::SeqCst);
+}
+
+// Returns whether the body was complete and moved into place
+async fn write_chunks(partial_path: &Path,
+                      destination: &Path,
+                      headers: BTreeMap<String, String>,
+                      mut receiver: mpsc::Receiver<Bytes>,
+                      complete: &AtomicBool) -> io::Result<bool> {
+    let mut file = tokio::fs::File::create(partial_path).await?;
+    while let Some(data) = receiver.recv().await {
+        file.write_all(&data).await?;
+    }
+    // Otherwise the response was dropped or failed before it was fully sent
+    if !complete.load(Ordering::SeqCst) {
+        return Ok(false);
+    }
+    file.sync_all().await?;
+    drop(file);
+    let headers_path = destination.with_extension(HEADERS_EXTENSION);
+    let partial_headers_path = partial_path.with_extension(format!("{}.{}", HEADERS_EXTENSION, PARTIAL_EXTENSION));
+    tokio::fs::write(&partial_headers_path, serde_json::to_vec(&headers)?).await?;
+    tokio::fs::rename(&partial_headers_path, &headers_path).await?;
+    tokio::fs::rename(partial_path, destination).await?;
+    Ok(true)
+}
+
+// Stored bodies are named by the digest of their key, without an extension
+fn is_stored_body(path: &Path) -> bool {
+    path.extension().is_none() && path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.len() == 40 && name.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+// The body is removed first, so that the copy is never read without its headers
+fn remove_copy(body_path: &Path) -> io::Result<bool> {
+    let removed = remove_if_present(body_path)?;
+    remove_if_present(&body_path.with_extension(HEADERS_EXTENSION))?;
+    Ok(removed)
+}
+
+fn remove_if_present(path: &Path) -> io::Result<bool> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error)
+    }
+}
+
+fn remove_partial_files(directory: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == PARTIAL_EXTENSION) {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::HttpBody;
+    use eyre::Result;
+
+    const KEY: &str = "/org/example/1.0/example-1.0.jar";
+
+    fn artifact(body: Body) -> Response<Body> {
+        Response::builder()
+            .header(LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+            .body(body)
+            .unwrap()
+    }
+
+    fn cached_files(directory: &Path) -> Result<Vec<PathBuf>> {
+        Ok(std::fs::read_dir(directory)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<_>>()?)
+    }
+
+    #[tokio::test]
+    async fn stored_once_fully_served() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let response = cache.store(KEY, artifact(Body::from("artifact")));
+        assert_eq!(None, cache.get(KEY));
+        assert_eq!("artifact", hyper::body::to_bytes(response.into_body()).await?);
+        cache.finalize().await?;
+
+        let stored = cache.get(KEY).expect("The artifact was stored");
+        let mut headers = HeaderMap::new();
+        headers.insert(LAST_MODIFIED, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(StoredResponse::new(headers, Bytes::from_static(b"artifact")), stored);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ranges_read_from_copy() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let response = cache.store(KEY, artifact(Body::from("0123456789")));
+        hyper::body::to_bytes(response.into_body()).await?;
+        cache.finalize().await?;
+
+        let range = |range: &'static str| {
+            cache.get_range(KEY, &HeaderValue::from_static(range), None)
+                .map(|stored| stored.into_response(hyper::Version::HTTP_11))
+        };
+        let response = range("bytes=2-5").expect("The artifact was stored");
+        assert_eq!(StatusCode::PARTIAL_CONTENT, response.status());
+        assert_eq!("bytes 2-5/10", response.headers()["Content-Range"]);
+        assert_eq!("4", response.headers()["Content-Length"]);
+        assert_eq!("Wed, 21 Oct 2015 07:28:00 GMT", response.headers()[LAST_MODIFIED]);
+        assert_eq!("2345", hyper::body::to_bytes(response.into_body()).await?);
+        let response = range("bytes=6-").expect("The artifact was stored");
+        assert_eq!("6789", hyper::body::to_bytes(response.into_body()).await?);
+
+        // Ranges which are ignored are left to be fetched whole
+        assert!(range("bytes=0-1,4-5").is_none());
+        assert!(cache.get_range("/org/example/1.0/example-1.0.pom", &HeaderValue::from_static("bytes=2-5"), None).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsuccessful_responses_not_stored() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let mut response = artifact(Body::from("missing"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        hyper::body::to_bytes(cache.store(KEY, response).into_body()).await?;
+        cache.finalize().await?;
+        assert!(cached_files(directory.path())?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shutdown_mid_write() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let (mut sender, body) = Body::channel();
+        let mut body = cache.store(KEY, artifact(body)).into_body();
+        sender.send_data(Bytes::from_static(b"first half")).await?;
+        body.data().await.expect("A chunk was sent")?;
+        // Give the writer time to create the partial file
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!cached_files(directory.path())?.is_empty());
+
+        cache.finalize().await?;
+        assert!(cached_files(directory.path())?.is_empty());
+        assert_eq!(None, cache.get(KEY));
+        // The rest of the body still reaches the client
+        sender.send_data(Bytes::from_static(b"second half")).await?;
+        drop(sender);
+        assert_eq!("second half", hyper::body::to_bytes(body).await?);
+        assert_eq!(None, cache.get(KEY));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aborted_response_not_stored() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let (mut sender, body) = Body::channel();
+        let mut body = cache.store(KEY, artifact(body)).into_body();
+        sender.send_data(Bytes::from_static(b"partial")).await?;
+        body.data().await.expect("A chunk was sent")?;
+        sender.abort();
+        assert!(body.data().await.expect("The error is reported").is_err());
+        drop(body);
+        cache.finalize().await?;
+        assert!(cached_files(directory.path())?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writer_falling_behind_abandons_copy() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        // Every chunk is ready at once, so the writer never runs while the body is read
+        let chunks = (0..WRITE_QUEUE_CHUNKS * 2).map(|_| Ok::<_, io::Error>(Bytes::from_static(b"chunk")));
+        let response = cache.store(KEY, artifact(Body::wrap_stream(futures_util::stream::iter(chunks))));
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        assert_eq!(WRITE_QUEUE_CHUNKS * 2 * 5, body.len());
+        cache.finalize().await?;
+        assert!(cached_files(directory.path())?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purged_copies() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        for key in [KEY, "/org/example/1.0/example-1.0.pom"] {
+            hyper::body::to_bytes(cache.store(key, artifact(Body::from("artifact"))).into_body()).await?;
+        }
+        cache.finalize().await?;
+        std::fs::write(directory.path().join("stats.json"), "{}")?;
+
+        assert!(cache.remove(KEY)?);
+        assert!(!cache.remove(KEY)?);
+        assert_eq!(None, cache.get(KEY));
+        assert_eq!(1, cache.clear()?);
+        // Files which are not stored copies are left alone
+        assert_eq!(vec![directory.path().join("stats.json")], cached_files(directory.path())?);
+        Ok(())
+    }
+
+    async fn store_fully(cache: &DiskCache, key: &str) -> Result<()> {
+        hyper::body::to_bytes(cache.store(key, artifact(Body::from("artifact"))).into_body()).await?;
+        cache.finalize().await?;
+        // Keeps the stored times of copies apart even with coarse timestamps
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evict_old_copies() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        store_fully(&cache, KEY).await?;
+        let limits = EvictionLimits::new(None, Some(Duration::from_secs(3600)));
+        assert_eq!(Evicted::default(), cache.evict(limits)?);
+        assert!(cache.get(KEY).is_some());
+
+        let later = SystemTime::now() + Duration::from_secs(7200);
+        assert_eq!(1, cache.evict_at(limits, later)?.copies);
+        assert_eq!(None, cache.get(KEY));
+        assert!(cached_files(directory.path())?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evict_least_recently_used() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let cache = DiskCache::open(directory.path())?;
+        let keys = ["/org/example/1.0/first.jar", "/org/example/1.0/second.jar", "/org/example/1.0/third.jar"];
+        for key in keys {
+            store_fully(&cache, key).await?;
+        }
+        // Reading the oldest copy makes the second the least recently used
+        assert!(cache.get(keys[0]).is_some());
+        let copy_size = cache.stored_copies()?[0].size;
+
+        let evicted = cache.evict(EvictionLimits::new(Some(2 * copy_size), None))?;
+        assert_eq!(Evicted { copies: 1, bytes: copy_size }, evicted);
+        assert!(cache.get(keys[0]).is_some());
+        assert_eq!(None, cache.get(keys[1]));
+        assert!(cache.get(keys[2]).is_some());
+
+        let evicted = cache.evict(EvictionLimits::new(Some(copy_size), None))?;
+        assert_eq!(1, evicted.copies);
+        assert_eq!(None, cache.get(keys[0]));
+        assert!(cache.get(keys[2]).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn leftover_partial_files_removed() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("0123.0.part"), "partial")?;
+        std::fs::write(directory.path().join("4567"), "complete")?;
+        DiskCache::open(directory.path())?;
+        assert_eq!(vec![directory.path().join("4567")], cached_files(directory.path())?);
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 2^12 registers, for a standard error of about 1.6%
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+// A HyperLogLog sketch, estimating how many distinct items were inserted in a fixed 4 KiB
+#[derive(Debug, Clone)]
+struct Sketch {
+    registers: Vec<u8>
+}
+
+impl Default for Sketch {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS]
+        }
+    }
+}
+
+impl Sketch {
+    fn insert(&mut self, item: &str) {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // The position of the first set bit after the index bits. The marker bit bounds it
+        // for hashes whose remaining bits are all zero
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    fn merge(&mut self, other: &Sketch) {
+        for (register, &other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(other);
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        let registers = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / registers);
+        let sum: f64 = self.registers.iter().map(|&register| 2f64.powi(-i32::from(register))).sum();
+        let raw = alpha * registers * registers / sum;
+        let empty = self.registers.iter().filter(|&&register| register == 0).count();
+        // Counting the empty registers is more accurate for small counts
+        let estimate = if raw <= 2.5 * registers && empty > 0 {
+            registers * (registers / empty as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+// Estimates how many distinct paths were requested recently, for sizing caches. A sketch is
+// kept for the current and the previous window, so the estimate covers between one and two
+// windows of requests
+#[derive(Debug)]
+pub struct DistinctPaths {
+    window: Duration,
+    state: Mutex<Windows>
+}
+
+#[derive(Debug)]
+struct Windows {
+    current: Sketch,
+    previous: Sketch,
+    started: Instant
+}
+
+impl Windows {
+    fn rotate(&mut self, now: Instant, window: Duration) {
+        let elapsed = now.saturating_duration_since(self.started);
+        if elapsed >= window * 2 {
+            self.previous = Sketch::default();
+            self.current = Sketch::default();
+            self.started = now;
+        } else if elapsed >= window {
+            self.previous = std::mem::take(&mut self.current);
+            self.started += window;
+        }
+    }
+}
+
+impl DistinctPaths {
+    pub fn new(window: Duration) -> Self {
+        Self::new_at(window, Instant::now())
+    }
+
+    fn new_at(window: Duration, now: Instant) -> Self {
+        Self {
+            window,
+            state: Mutex::new(Windows {
+                current: Sketch::default(),
+                previous: Sketch::default(),
+                started: now
+            })
+        }
+    }
+
+    pub fn record(&self, path: &str) {
+        self.record_at(path, Instant::now());
+    }
+
+    fn record_at(&self, path: &str, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.rotate(now, self.window);
+        state.current.insert(path);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        self.estimate_at(Instant::now())
+    }
+
+    fn estimate_at(&self, now: Instant) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.rotate(now, self.window);
+        let mut merged = state.current.clone();
+        merged.merge(&state.previous);
+        merged.estimate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_within(expected: u64, actual: u64, error: f64) {
+        let difference = (actual as f64 - expected as f64).abs();
+        assert!(difference <= expected as f64 * error, "Expected about {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn estimate_distinct_paths() {
+        let window = Duration::from_secs(60);
+        let now = Instant::now();
+        let distinct_paths = DistinctPaths::new_at(window, now);
+        assert_eq!(0, distinct_paths.estimate_at(now));
+        for _ in 0..3 {
+            for artifact in 0..50_000 {
+                distinct_paths.record_at(&format!("/org/example/artifact-{}/1.0/artifact-{}-1.0.jar", artifact, artifact), now);
+            }
+            // Three standard errors
+            assert_within(50_000, distinct_paths.estimate_at(now), 0.05);
+        }
+        let small = DistinctPaths::new_at(window, now);
+        for artifact in 0..100 {
+            small.record_at(&format!("/org/example/example/{}/example-{}.pom", artifact, artifact), now);
+            small.record_at(&format!("/org/example/example/{}/example-{}.pom", artifact, artifact), now);
+        }
+        assert_within(100, small.estimate_at(now), 0.05);
+    }
+
+    #[test]
+    fn rolling_window() {
+        let window = Duration::from_secs(60);
+        let now = Instant::now();
+        let distinct_paths = DistinctPaths::new_at(window, now);
+        for artifact in 0..1000 {
+            distinct_paths.record_at(&format!("/org/example/old/{}/old-{}.jar", artifact, artifact), now);
+        }
+        let later = now + window + Duration::from_secs(1);
+        for artifact in 0..1000 {
+            distinct_paths.record_at(&format!("/org/example/new/{}/new-{}.jar", artifact, artifact), later);
+        }
+        // The previous window is still counted
+        assert_within(2000, distinct_paths.estimate_at(later), 0.05);
+        assert_within(1000, distinct_paths.estimate_at(later + window), 0.05);
+        assert_eq!(0, distinct_paths.estimate_at(later + window * 2));
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::RwLock;
+use hyper::{Body, Client, Request, Response};
+use hyper::client::connect::Connect;
+use crate::error::ProxyError;
+
+// Whether the request failed at the HTTP/2 protocol level, such as a reset stream or a
+// connection closed by the repository with an error. I/O errors are not protocol errors
+pub fn is_http2_protocol_error(error: &hyper::Error) -> bool {
+    let mut source = error.source();
+    while let Some(error) = source {
+        if let Some(h2_error) = error.downcast_ref::<h2::Error>() {
+            return !h2_error.is_io();
+        }
+        source = error.source();
+    }
+    false
+}
+
+// Repeats requests over HTTP/1.1 when a repository which advertises HTTP/2 mishandles it
+pub struct Http1Fallback<C> {
+    client: Client<C>,
+    // Whether repositories which failed over HTTP/2 are contacted over HTTP/1.1 from then on
+    remember: bool,
+    downgraded: RwLock<HashSet<String>>
+}
+
+impl<C> Http1Fallback<C> where C: Connect + Clone + Send + Sync + 'static {
+    // The client must only offer HTTP/1.1
+    pub fn new(client: Client<C>, remember: bool) -> Self {
+        Self {
+            client,
+            remember,
+            downgraded: RwLock::new(HashSet::new())
+        }
+    }
+
+    // Sends the request with the client, then once more over HTTP/1.1, rebuilt by the given
+    // function, if that fails at the HTTP/2 protocol level
+    pub async fn request(&self,
+                         client: &Client<C>,
+                         repository: &str,
+                         request: Request<Body>,
+                         rebuild: impl Fn() -> Result<Request<Body>, ProxyError>) -> Result<Response<Body>, ProxyError> {
+        if self.downgraded.read().unwrap().contains(repository) {
+            return Ok(self.client.request(request).await?);
+        }
+        match client.request(request).await {
+            Err(error) if is_http2_protocol_error(&error) => {
+                log::warn!("Repository {} failed over HTTP/2, trying again over HTTP/1.1: {}", repository, error);
+                if self.remember {
+                    self.downgraded.write().unwrap().insert(repository.to_owned());
+                }
+                Ok(self.client.request(rebuild()?).await?)
+            },
+            result => Ok(result?)
+        }
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::io::Write;
+use hyper::{Body, HeaderMap, Response, StatusCode};
+use hyper::header::{HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, VARY};
+use hyper::body::Bytes;
+use flate2::Compression;
+use flate2::write::{GzDecoder, GzEncoder, ZlibDecoder};
+use futures_util::StreamExt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Gzip,
+    Deflate
+}
+
+impl ContentCoding {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentCoding::Gzip)
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Some(ContentCoding::Deflate)
+        } else {
+            None
+        }
+    }
+
+    fn token(&self) -> &'static str {
+        match self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate"
+        }
+    }
+
+    // Whether the client advertised support for this coding in Accept-Encoding
+    fn accepted_by(&self, request_headers: &HeaderMap) -> bool {
+        request_headers.get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|entry| {
+                let mut params = entry.split(';');
+                let coding = params.next().unwrap_or_default().trim();
+                let rejected = params.any(|param| {
+                    param.trim().strip_prefix("q=")
+                        .and_then(|quality| quality.trim().parse::<f32>().ok())
+                        .is_some_and(|quality| quality <= 0.0)
+                });
+                !rejected && (coding.eq_ignore_ascii_case(self.token()) || coding == "*")
+            })
+    }
+}
+
+// Passes compressed responses through when the client supports the coding,
+// otherwise decodes them
+pub fn negotiate(request_headers: &HeaderMap, response: Response<Body>) -> Response<Body> {
+    match ContentCoding::from_headers(response.headers()) {
+        Some(coding) if !coding.accepted_by(request_headers) => decode(response),
+        _ => response
+    }
+}
+
+// Removes any gzip or deflate content coding from the response
+pub fn decode(response: Response<Body>) -> Response<Body> {
+    // Partial content cannot be decoded independently of the rest of the body
+    if response.status() == StatusCode::PARTIAL_CONTENT {
+        return response;
+    }
+    let coding = match ContentCoding::from_headers(response.headers()) {
+        Some(coding) => coding,
+        None => return response
+    };
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(CONTENT_ENCODING);
+    parts.headers.remove(CONTENT_LENGTH);
+    // Byte ranges of the encoded body no longer line up with the decoded one
+    parts.headers.remove(ACCEPT_RANGES);
+    Response::from_parts(parts, decode_body(body, coding))
+}
+
+// Compresses text responses with gzip for clients which accept it. Artifacts such as jars
+// are already compressed, so other content types are left alone
+pub fn compress(request_headers: &HeaderMap, response: Response<Body>) -> Response<Body> {
+    let compressible = response.status() == StatusCode::OK
+        && !response.headers().contains_key(CONTENT_ENCODING)
+        && response.headers().get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(is_compressible_type)
+        && ContentCoding::Gzip.accepted_by(request_headers);
+    if !compressible {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.remove(ACCEPT_RANGES);
+    parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts.headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    // The compressed body differs byte for byte, so only a weak validator still applies
+    let weak_etag = parts.headers.get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+    if let Some(weak_etag) = weak_etag {
+        parts.headers.insert(ETAG, weak_etag);
+    }
+    Response::from_parts(parts, gzip_body(body))
+}
+
+fn is_compressible_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    media_type.starts_with("text/") || media_type == "application/xml" || media_type.ends_with("+xml")
+}
+
+fn gzip_body(body: Body) -> Body {
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let stream = futures_util::stream::unfold(Some((body, encoder)), |state| async move {
+        let (mut body, mut encoder) = state?;
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                let output = encoder.write_all(&chunk)
+                    .map(|()| Bytes::from(std::mem::take(encoder.get_mut())));
+                let next = output.is_ok().then_some((body, encoder));
+                Some((output, next))
+            },
+            Some(Err(error)) => Some((Err(std::io::Error::other(error)), None)),
+            None => Some((encoder.finish().map(Bytes::from), None))
+        }
+    });
+    Body::wrap_stream(stream)
+}
+
+enum Decoder {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>)
+}
+
+impl Decoder {
+    fn new(coding: ContentCoding) -> Self {
+        match coding {
+            ContentCoding::Gzip => Decoder::Gzip(GzDecoder::new(Vec::new())),
+            ContentCoding::Deflate => Decoder::Deflate(ZlibDecoder::new(Vec::new()))
+        }
+    }
+
+    // Returns whatever output the chunk produced
+    fn write(&mut self, chunk: &[u8]) -> std::io::Result<Bytes> {
+        let output = match self {
+            Decoder::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            },
+            Decoder::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(output)))
+    }
+
+    fn finish(self) -> std::io::Result<Bytes> {
+        let output = match self {
+            Decoder::Gzip(decoder) => decoder.finish()?,
+            Decoder::Deflate(decoder) => decoder.finish()?
+        };
+        Ok(Bytes::from(output))
+    }
+}
+
+fn decode_body(body: Body, coding: ContentCoding) -> Body {
+    let stream = futures_util::stream::unfold(Some((body, Decoder::new(coding))), |state| async move {
+        let (mut body, mut decoder) = state?;
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                let output = decoder.write(&chunk);
+                let next = output.is_ok().then_some((body, decoder));
+                Some((output, next))
+            },
+            Some(Err(error)) => Some((Err(std::io::Error::other(error)), None)),
+            None => Some((decoder.finish(), None))
+        }
+    });
+    Body::wrap_stream(stream)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use eyre::Result;
+
+    pub fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn encoded_response(coding: &'static str, body: Vec<u8>) -> Response<Body> {
+        Response::builder()
+            .header(CONTENT_ENCODING, coding)
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[test]
+    fn accepted_codings() {
+        assert!(ContentCoding::Gzip.accepted_by(&accept_encoding("gzip, deflate, br")));
+        assert!(ContentCoding::Gzip.accepted_by(&accept_encoding("GZIP;q=0.5")));
+        assert!(ContentCoding::Deflate.accepted_by(&accept_encoding("*")));
+        assert!(!ContentCoding::Gzip.accepted_by(&accept_encoding("gzip;q=0, deflate")));
+        assert!(!ContentCoding::Gzip.accepted_by(&accept_encoding("identity")));
+        assert!(!ContentCoding::Gzip.accepted_by(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn passthrough_when_accepted() -> Result<()> {
+        let compressed = gzip(b"<metadata/>");
+        let response = negotiate(&accept_encoding("gzip"), encoded_response("gzip", compressed.clone()));
+        assert_eq!("gzip", response.headers()[CONTENT_ENCODING]);
+        assert_eq!(compressed, hyper::body::to_bytes(response.into_body()).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode_gzip() -> Result<()> {
+        let response = negotiate(&HeaderMap::new(), encoded_response("gzip", gzip(b"<metadata/>")));
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!("<metadata/>", hyper::body::to_bytes(response.into_body()).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode_deflate_in_chunks() -> Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&b"0123456789".repeat(100))?;
+        let compressed = encoder.finish()?;
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = compressed.chunks(7).map(|chunk| Ok(chunk.to_vec())).collect();
+        let response = Response::builder()
+            .header(CONTENT_ENCODING, "deflate")
+            .body(Body::wrap_stream(futures_util::stream::iter(chunks)))?;
+        let decoded = hyper::body::to_bytes(decode(response).into_body()).await?;
+        assert_eq!(b"0123456789".repeat(100), decoded.to_vec());
+        Ok(())
+    }
+
+    fn text_response(content_type: &'static str, body: &'static str) -> Response<Body> {
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_LENGTH, body.len())
+            .header(ACCEPT_RANGES, "bytes")
+            .header(ETAG, "\"abc\"")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compress_for_gzip_client() -> Result<()> {
+        let response = compress(&accept_encoding("gzip, deflate"), text_response("text/xml", "<project/>"));
+        assert_eq!("gzip", response.headers()[CONTENT_ENCODING]);
+        assert_eq!("accept-encoding", response.headers()[VARY]);
+        assert_eq!("W/\"abc\"", response.headers()[ETAG]);
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+        assert!(response.headers().get(ACCEPT_RANGES).is_none());
+        let decoded = decode(response);
+        assert_eq!("<project/>", hyper::body::to_bytes(decoded.into_body()).await?);
+        Ok(())
+    }
+
+    #[test]
+    fn compress_only_when_useful() {
+        let uncompressed = [
+            compress(&HeaderMap::new(), text_response("application/xml", "<project/>")),
+            compress(&accept_encoding("identity"), text_response("application/xml", "<project/>")),
+            compress(&accept_encoding("gzip"), text_response("application/java-archive", "PK"))
+        ];
+        for response in &uncompressed {
+            assert!(response.headers().get(CONTENT_ENCODING).is_none());
+            assert_eq!("bytes", response.headers()[ACCEPT_RANGES]);
+            assert_eq!("\"abc\"", response.headers()[ETAG]);
+        }
+        let already_encoded = compress(&accept_encoding("gzip"), encoded_response("deflate", b"data".to_vec()));
+        assert_eq!("deflate", already_encoded.headers()[CONTENT_ENCODING]);
+    }
+
+    #[tokio::test]
+    async fn corrupt_body() {
+        let response = decode(encoded_response("gzip", b"not gzip".to_vec()));
+        hyper::body::to_bytes(response.into_body()).await.expect_err("Body is not gzip");
+    }
+}
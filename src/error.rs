@@ -0,0 +1,114 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt::{Display, Formatter};
+use std::error::Error;
+use std::path::PathBuf;
+use tokio::time::error::Elapsed;
+use hyper::http::uri::InvalidUri;
+
+#[derive(Debug)]
+pub enum ProxyError {
+    Http(hyper::http::Error),
+    Hyper(hyper::Error),
+    Timeout(Elapsed),
+    InvalidRepositoryUri { url: String, error: InvalidUri },
+    InvalidRepositoryUrl { url: String, reason: String },
+    ArtifactTooLarge { limit: u64 },
+    InvalidConfig(&'static str),
+    InvalidConfigSyntax { path: PathBuf, reason: String },
+    InvalidRequestHeader(String),
+    InvalidResponseHeader(String),
+    InvalidPathRewrite { pattern: String, error: regex::Error },
+    UnreadableRepositoryIndex { path: PathBuf, error: std::io::Error }
+}
+
+impl ProxyError {
+    // Whether the error is a connection-level failure which may succeed if attempted again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ProxyError::Hyper(error) => {
+                error.is_connect() || error.is_closed() || error.is_incomplete_message()
+            },
+            _ => false
+        }
+    }
+}
+
+impl Display for ProxyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::Http(error) => write!(f, "Failed to build request: {}", error),
+            ProxyError::Hyper(error) => write!(f, "HTTP error: {}", error),
+            ProxyError::Timeout(_) => write!(f, "Timed out"),
+            ProxyError::InvalidRepositoryUri { url, error } => write!(
+                f, "Repository URL {} cannot be used as a request URI: {}", url, error),
+            ProxyError::InvalidRepositoryUrl { url, reason } => write!(
+                f, "Invalid repository URL {:?}: {}", url, reason),
+            ProxyError::ArtifactTooLarge { limit } => write!(
+                f, "Artifact exceeds the maximum size of {} bytes", limit),
+            ProxyError::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            ProxyError::InvalidConfigSyntax { path, reason } => write!(
+                f, "Cannot parse config {}: {}", path.display(), reason),
+            ProxyError::InvalidRequestHeader(header) => write!(f, "Invalid request header {:?}", header),
+            ProxyError::InvalidResponseHeader(header) => write!(f, "Invalid response header {:?}", header),
+            ProxyError::InvalidPathRewrite { pattern, error } => write!(
+                f, "Invalid path rewrite pattern {:?}: {}", pattern, error),
+            ProxyError::UnreadableRepositoryIndex { path, error } => write!(
+                f, "Cannot read repository index {}: {}", path.display(), error)
+        }
+    }
+}
+
+impl Error for ProxyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProxyError::Http(error) => Some(error),
+            ProxyError::Hyper(error) => Some(error),
+            ProxyError::Timeout(error) => Some(error),
+            ProxyError::InvalidRepositoryUri { error, .. } => Some(error),
+            ProxyError::InvalidPathRewrite { error, .. } => Some(error),
+            ProxyError::UnreadableRepositoryIndex { error, .. } => Some(error),
+            ProxyError::InvalidRepositoryUrl { .. }
+            | ProxyError::ArtifactTooLarge { .. }
+            | ProxyError::InvalidConfig(_)
+            | ProxyError::InvalidConfigSyntax { .. }
+            | ProxyError::InvalidRequestHeader(_)
+            | ProxyError::InvalidResponseHeader(_) => None
+        }
+    }
+}
+
+impl From<hyper::http::Error> for ProxyError {
+    fn from(error: hyper::http::Error) -> Self {
+        ProxyError::Http(error)
+    }
+}
+
+impl From<hyper::Error> for ProxyError {
+    fn from(error: hyper::Error) -> Self {
+        ProxyError::Hyper(error)
+    }
+}
+
+impl From<Elapsed> for ProxyError {
+    fn from(error: Elapsed) -> Self {
+        ProxyError::Timeout(error)
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::net::IpAddr;
+use std::str::FromStr;
+use hyper::HeaderMap;
+use crate::headers::{X_FORWARDED_FOR, X_REAL_IP};
+
+// A network such as 10.0.0.0/8 or fd00::/8. A plain address is a network of that address alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_length: u32
+}
+
+impl FromStr for Cidr {
+    type Err = ();
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_length) = match cidr.trim().split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (cidr.trim(), None)
+        };
+        let network: IpAddr = address.parse().map_err(|_| ())?;
+        let max_length = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length.parse().map_err(|_| ())?,
+            None => max_length
+        };
+        if prefix_length > max_length {
+            return Err(());
+        }
+        Ok(Self {
+            network,
+            prefix_length
+        })
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_length).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            },
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_length).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            },
+            _ => false
+        }
+    }
+}
+
+// Finds the real client behind trusted reverse proxies. Forwarded headers are only believed when
+// the connection comes from a trusted proxy, since anyone else could send them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedProxies {
+    cidrs: Vec<Cidr>
+}
+
+impl TrustedProxies {
+    pub fn new(cidrs: Vec<Cidr>) -> Self {
+        Self {
+            cidrs
+        }
+    }
+
+    fn trusts(&self, address: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(address))
+    }
+
+    // X-Forwarded-For is read from the right, since each proxy appends the address it received
+    // the request from. The first address not belonging to a trusted proxy is the client
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusts(peer) {
+            return peer;
+        }
+        let forwarded_for: Vec<IpAddr> = headers.get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|address| address.trim().parse().ok())
+            .collect();
+        if let Some(first) = forwarded_for.first() {
+            // When every hop is a trusted proxy, the first is the client
+            return *forwarded_for.iter()
+                .rev()
+                .find(|address| !self.trusts(**address))
+                .unwrap_or(first);
+        }
+        headers.get(X_REAL_IP)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn cidr(cidr: &str) -> Cidr {
+        Cidr::from_str(cidr).expect("Valid CIDR")
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().expect("Valid address")
+    }
+
+    #[test]
+    fn parse_cidrs() {
+        assert!(cidr("10.0.0.0/8").contains(ip("10.200.3.4")));
+        assert!(!cidr("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(cidr("192.168.1.7").contains(ip("192.168.1.7")));
+        assert!(!cidr("192.168.1.7").contains(ip("192.168.1.8")));
+        assert!(cidr("0.0.0.0/0").contains(ip("203.0.113.5")));
+        assert!(cidr("fd00::/8").contains(ip("fd12::1")));
+        assert!(!cidr("fd00::/8").contains(ip("10.0.0.1")));
+        for invalid in ["10.0.0.0/33", "::/129", "10.0.0.0/", "example.com", "10.0.0.0/8/8"] {
+            assert!(Cidr::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn forwarded_client() {
+        let proxies = TrustedProxies::new(vec![cidr("10.0.0.0/8")]);
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("198.51.100.1, 203.0.113.5, 10.0.0.3"));
+        assert_eq!(ip("203.0.113.5"), proxies.client_ip(ip("10.0.0.2"), &headers));
+        // Headers from untrusted peers are ignored
+        assert_eq!(ip("192.0.2.9"), proxies.client_ip(ip("192.0.2.9"), &headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.5, 10.0.0.3"));
+        assert_eq!(ip("10.0.0.5"), proxies.client_ip(ip("10.0.0.2"), &headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REAL_IP, HeaderValue::from_static("203.0.113.5"));
+        assert_eq!(ip("203.0.113.5"), proxies.client_ip(ip("10.0.0.2"), &headers));
+        assert_eq!(ip("10.0.0.2"), proxies.client_ip(ip("10.0.0.2"), &HeaderMap::new()));
+    }
+}
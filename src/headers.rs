@@ -0,0 +1,333 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt::{self, Debug, Formatter};
+use std::time::Duration;
+use hyper::{HeaderMap, Request, Response};
+use hyper::http::request;
+use hyper::header::{HeaderName, HeaderValue, ACCEPT_RANGES, AUTHORIZATION, CACHE_CONTROL, CONNECTION, CONTENT_ENCODING,
+                    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, COOKIE, ETAG, LAST_MODIFIED, SET_COOKIE, TRANSFER_ENCODING,
+                    TE, TRAILER, UPGRADE, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION};
+use crate::metadata;
+
+// Not a standard header, so hyper has no constant for it
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+pub const X_REAL_IP: &str = "x-real-ip";
+pub const X_REQUEST_ID: &str = "x-request-id";
+pub const X_SERVED_BY: &str = "x-served-by";
+pub const X_PROXY_REPOSITORY: &str = "x-proxy-repository";
+
+// Headers which apply to a single connection and must not be forwarded by proxies
+const HOP_BY_HOP: &[HeaderName] = &[
+    CONNECTION, TRANSFER_ENCODING, TE, TRAILER, UPGRADE, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION
+];
+
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    // Headers listed in the Connection header are also hop-by-hop
+    let connection_listed: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in connection_listed.iter().chain(HOP_BY_HOP) {
+        headers.remove(name);
+    }
+    headers.remove("keep-alive");
+}
+
+// Headers whose values are credentials. Values marked sensitive, such as the headers
+// configured for a repository, are treated the same way
+const CREDENTIALS: &[HeaderName] = &[AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+// Formats a request or response for logging, without the values of any credentials in its headers
+pub struct Redacted<'a, T>(pub &'a T);
+
+struct RedactedHeaders<'a>(&'a HeaderMap);
+
+impl Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+        for (position, (name, value)) in self.0.iter().enumerate() {
+            if position > 0 {
+                f.write_str(", ")?;
+            }
+            if value.is_sensitive() || CREDENTIALS.contains(name) {
+                write!(f, "{}: <redacted>", name)?;
+            } else {
+                write!(f, "{}: {:?}", name, value)?;
+            }
+        }
+        f.write_str("}")
+    }
+}
+
+impl<B> Debug for Redacted<'_, Request<B>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", self.0.method())
+            .field("uri", self.0.uri())
+            .field("version", &self.0.version())
+            .field("headers", &RedactedHeaders(self.0.headers()))
+            .finish()
+    }
+}
+
+impl Debug for Redacted<'_, request::Parts> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parts")
+            .field("method", &self.0.method)
+            .field("uri", &self.0.uri)
+            .field("version", &self.0.version)
+            .field("headers", &RedactedHeaders(&self.0.headers))
+            .finish()
+    }
+}
+
+impl<B> Debug for Redacted<'_, Response<B>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.0.status())
+            .field("version", &self.0.version())
+            .field("headers", &RedactedHeaders(self.0.headers()))
+            .finish()
+    }
+}
+
+// Headers removed from or added to every request sent upstream, as configured by operators
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RequestHeaderRules {
+    strip: Vec<HeaderName>,
+    set: Vec<(HeaderName, HeaderValue)>
+}
+
+impl RequestHeaderRules {
+    pub fn new(strip: Vec<HeaderName>, set: Vec<(HeaderName, HeaderValue)>) -> Self {
+        Self {
+            strip,
+            set
+        }
+    }
+
+    // Set headers replace any existing values, including those which were not stripped
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        for name in &self.strip {
+            headers.remove(name);
+        }
+        for (name, value) in &self.set {
+            headers.insert(name, value.clone());
+        }
+    }
+}
+
+// Headers describing the content, which clients need even when not allowed explicitly
+const ESSENTIAL_RESPONSE_HEADERS: &[HeaderName] = &[
+    CONTENT_TYPE, CONTENT_LENGTH, CONTENT_ENCODING, CONTENT_RANGE, ACCEPT_RANGES, ETAG, LAST_MODIFIED
+];
+
+// Which upstream response headers reach clients, such as to hide Server or internal tracking headers.
+// Hop-by-hop headers are stripped regardless
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ResponseHeaderPolicy {
+    #[default]
+    PassThrough,
+    Allow(Vec<HeaderName>),
+    Deny(Vec<HeaderName>)
+}
+
+impl ResponseHeaderPolicy {
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        match self {
+            ResponseHeaderPolicy::PassThrough => {},
+            ResponseHeaderPolicy::Allow(allowed) => {
+                let denied: Vec<HeaderName> = headers.keys()
+                    .filter(|name| !allowed.contains(name) && !ESSENTIAL_RESPONSE_HEADERS.contains(name))
+                    .cloned()
+                    .collect();
+                for name in denied {
+                    headers.remove(name);
+                }
+            },
+            ResponseHeaderPolicy::Deny(denied) => {
+                for name in denied {
+                    headers.remove(name);
+                }
+            }
+        }
+    }
+}
+
+// The Cache-Control header given to clients, so that caches in front of the proxy agree on freshness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheControlPolicy {
+    release_max_age: Duration,
+    snapshot_max_age: Duration,
+    override_upstream: bool
+}
+
+impl CacheControlPolicy {
+    // Snapshots and metadata use the snapshot max age, which must be revalidated if zero.
+    // Unless overriding, a Cache-Control header sent by the upstream is kept
+    pub fn new(release_max_age: Duration, snapshot_max_age: Duration, override_upstream: bool) -> Self {
+        Self {
+            release_max_age,
+            snapshot_max_age,
+            override_upstream
+        }
+    }
+
+    fn value_for(&self, path: &str) -> HeaderValue {
+        let value = match (metadata::is_changing(path), self.snapshot_max_age.as_secs()) {
+            (true, 0) => "no-cache".to_owned(),
+            (true, max_age) => format!("public, max-age={}", max_age),
+            (false, _) => format!("public, max-age={}, immutable", self.release_max_age.as_secs())
+        };
+        HeaderValue::from_str(&value).expect("Cache-Control values are valid headers")
+    }
+
+    pub fn apply(&self, path: &str, headers: &mut HeaderMap) {
+        if self.override_upstream || !headers.contains_key(CACHE_CONTROL) {
+            headers.insert(CACHE_CONTROL, self.value_for(path));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Body;
+    use hyper::header::{SERVER, USER_AGENT};
+
+    #[test]
+    fn strip_hop_by_hop_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/java-archive".parse().unwrap());
+        headers.insert(CONTENT_LENGTH, "42".parse().unwrap());
+        headers.insert(ETAG, "\"abc\"".parse().unwrap());
+        headers.insert(LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        headers.insert(CONNECTION, "keep-alive, X-Upstream-Hop".parse().unwrap());
+        headers.insert("keep-alive", "timeout=5".parse().unwrap());
+        headers.insert("x-upstream-hop", "1".parse().unwrap());
+        headers.insert(TRANSFER_ENCODING, "chunked".parse().unwrap());
+        strip_hop_by_hop(&mut headers);
+
+        assert_eq!(4, headers.len());
+        assert!(headers.contains_key(CONTENT_TYPE));
+        assert!(headers.contains_key(CONTENT_LENGTH));
+        assert!(headers.contains_key(ETAG));
+        assert!(headers.contains_key(LAST_MODIFIED));
+    }
+
+    #[test]
+    fn cache_control_policy() {
+        let policy = CacheControlPolicy::new(Duration::from_secs(86400), Duration::ZERO, false);
+        let value = |path: &str| policy.value_for(path);
+        assert_eq!("public, max-age=86400, immutable", value("/org/example/1.0/example-1.0.jar"));
+        assert_eq!("public, max-age=86400, immutable", value("/org/example/1.0/example-1.0.jar.sha1"));
+        assert_eq!("no-cache", value("/org/example/1.0-SNAPSHOT/example-1.0-20210101.000000-1.jar"));
+        assert_eq!("no-cache", value("/org/example/example/maven-metadata.xml"));
+        assert_eq!("no-cache", value("/org/example/example/maven-metadata.xml.sha1"));
+        let short = CacheControlPolicy::new(Duration::from_secs(86400), Duration::from_secs(60), false);
+        assert_eq!("public, max-age=60", short.value_for("/org/example/example/maven-metadata.xml"));
+    }
+
+    #[test]
+    fn cache_control_respects_upstream() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, "max-age=10".parse().unwrap());
+        let path = "/org/example/1.0/example-1.0.jar";
+        CacheControlPolicy::new(Duration::from_secs(3600), Duration::ZERO, false).apply(path, &mut headers);
+        assert_eq!("max-age=10", headers[CACHE_CONTROL]);
+        CacheControlPolicy::new(Duration::from_secs(3600), Duration::ZERO, true).apply(path, &mut headers);
+        assert_eq!("public, max-age=3600, immutable", headers[CACHE_CONTROL]);
+    }
+
+    #[test]
+    fn request_header_rules() {
+        let rules = RequestHeaderRules::new(
+            vec![AUTHORIZATION],
+            vec![(USER_AGENT, HeaderValue::from_static("rust-maven-proxy"))]);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Custom abc".parse().unwrap());
+        headers.append(USER_AGENT, "first".parse().unwrap());
+        headers.append(USER_AGENT, "second".parse().unwrap());
+        headers.insert(ETAG, "\"abc\"".parse().unwrap());
+        rules.apply(&mut headers);
+
+        assert!(!headers.contains_key(AUTHORIZATION));
+        assert_eq!(vec!["rust-maven-proxy"], headers.get_all(USER_AGENT).iter().collect::<Vec<_>>());
+        assert!(headers.contains_key(ETAG));
+    }
+
+    fn upstream_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/java-archive".parse().unwrap());
+        headers.insert(CONTENT_LENGTH, "42".parse().unwrap());
+        headers.insert(ETAG, "\"abc\"".parse().unwrap());
+        headers.insert(SERVER, "nginx/1.2.3".parse().unwrap());
+        headers.insert("x-cache", "HIT".parse().unwrap());
+        headers.insert("x-checksum-sha1", "da39a3ee5e6b4b0d3255bfef95601890afd80709".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn response_header_policy() {
+        let mut headers = upstream_headers();
+        ResponseHeaderPolicy::PassThrough.apply(&mut headers);
+        assert_eq!(upstream_headers(), headers);
+
+        let mut headers = upstream_headers();
+        ResponseHeaderPolicy::Deny(vec![SERVER, HeaderName::from_static("x-cache")]).apply(&mut headers);
+        assert_eq!(4, headers.len());
+        assert!(!headers.contains_key(SERVER) && !headers.contains_key("x-cache"));
+
+        let mut headers = upstream_headers();
+        ResponseHeaderPolicy::Allow(vec![HeaderName::from_static("x-checksum-sha1")]).apply(&mut headers);
+        let mut names: Vec<&str> = headers.keys().map(HeaderName::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(vec!["content-length", "content-type", "etag", "x-checksum-sha1"], names);
+    }
+
+    #[test]
+    fn credentials_redacted() {
+        let mut custom = HeaderValue::from_static("secret-key");
+        custom.set_sensitive(true);
+        let request = Request::builder()
+            .uri("https://repo.example.com/maven2/org/example/example.jar")
+            .header(AUTHORIZATION, "Bearer secret-token")
+            .header(COOKIE, "session=secret-session")
+            .header("x-jfrog-art-api", custom)
+            .header(USER_AGENT, "Apache-Maven/3.8.4")
+            .body(Body::empty())
+            .unwrap();
+        let logged = format!("{:?}", Redacted(&request));
+        assert!(logged.contains("authorization: <redacted>"), "{}", logged);
+        assert!(logged.contains("x-jfrog-art-api: <redacted>"), "{}", logged);
+        assert!(logged.contains("user-agent: \"Apache-Maven/3.8.4\""), "{}", logged);
+        assert!(logged.contains("/maven2/org/example/example.jar"), "{}", logged);
+        assert!(!logged.contains("secret"), "{}", logged);
+        let (parts, _) = request.into_parts();
+        assert!(!format!("{:?}", Redacted(&parts)).contains("secret"));
+
+        let response = Response::builder().header(SET_COOKIE, "session=secret-session").body(()).unwrap();
+        assert_eq!("Response { status: 200, version: HTTP/1.1, headers: {set-cookie: <redacted>} }",
+                   format!("{:?}", Redacted(&response)));
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Client, Uri, Request, Response, Body, Method, StatusCode, http};
+use hyper::client::connect::Connect;
+use futures_util::future::join_all;
+use serde::Serialize;
+use tokio::time::timeout;
+use std::time::Duration;
+use eyre::Result;
+use crate::repository::Repository;
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    healthy: bool,
+    repositories: Vec<RepositoryHealth>
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryHealth {
+    url: String,
+    reachable: bool,
+    // Answered the probe, but is being skipped after failing requests
+    degraded: bool,
+    status: Option<u16>,
+    error: Option<String>
+}
+
+impl HealthReport {
+    // Issues a HEAD request to the base URL of each repository
+    pub async fn probe<C>(client: &Client<C>,
+                          repositories: &[Repository],
+                          proxy_timeout: Duration) -> Self
+        where C: Connect + Clone + Send + Sync + 'static {
+
+        let probes = repositories.iter().map(|repository| async move {
+            let mut request = Request::builder()
+                .method(Method::HEAD)
+                .uri(repository.uri().clone());
+            if let Some(headers) = request.headers_mut() {
+                repository.apply_headers(headers);
+            }
+            let request = request.body(Body::empty());
+            let probe_timeout = repository.timeout_or(proxy_timeout);
+            let result = match request {
+                Ok(request) => match timeout(probe_timeout, client.request(request)).await {
+                    Ok(Ok(response)) => Ok(response.status()),
+                    Ok(Err(error)) => Err(error.to_string()),
+                    Err(_) => Err(format!("Timed out after {:?}", probe_timeout))
+                },
+                Err(error) => Err(error.to_string())
+            };
+            RepositoryHealth::from_result(repository.uri(), result)
+        });
+        let repositories: Vec<RepositoryHealth> = join_all(probes).await;
+        Self {
+            healthy: repositories.iter().all(|repository| repository.reachable),
+            repositories
+        }
+    }
+
+    // While offline, no repository is contacted, and the proxy is ready to serve what it has stored
+    pub fn offline() -> Self {
+        Self {
+            healthy: true,
+            repositories: Vec::new()
+        }
+    }
+
+    // Marks the repositories which requests have found to be failing as degraded. With a minimum,
+    // the report is healthy while at least that many repositories are reachable and not degraded,
+    // rather than only when every repository is reachable
+    pub fn assess(mut self, is_degraded: impl Fn(&str) -> bool, min_healthy: Option<usize>) -> Self {
+        for repository in &mut self.repositories {
+            repository.degraded = repository.reachable && is_degraded(&repository.url);
+        }
+        if let Some(min_healthy) = min_healthy {
+            let healthy = self.repositories.iter()
+                .filter(|repository| repository.reachable && !repository.degraded)
+                .count();
+            self.healthy = healthy >= min_healthy;
+        }
+        self
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy
+    }
+
+    pub fn repositories(&self) -> &[RepositoryHealth] {
+        &self.repositories
+    }
+
+    pub fn into_response(self, version: http::version::Version) -> Result<Response<Body>> {
+        let status = if self.healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        Ok(Response::builder()
+            .version(version)
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&self)?))?)
+    }
+}
+
+impl RepositoryHealth {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.reachable
+    }
+
+    fn from_result(repository: &Uri, result: core::result::Result<StatusCode, String>) -> Self {
+        match result {
+            Ok(status) => {
+                if !status.is_server_error() {
+                    log::trace!("Repository {} is reachable with status {:?}", repository, status);
+                } else {
+                    log::warn!("Repository {} is unhealthy with status {:?}", repository, status);
+                }
+                Self {
+                    url: repository.to_string(),
+                    reachable: !status.is_server_error(),
+                    degraded: false,
+                    status: Some(status.as_u16()),
+                    error: None
+                }
+            },
+            Err(error) => {
+                log::warn!("Repository {} is unreachable: {}", repository, error);
+                Self {
+                    url: repository.to_string(),
+                    reachable: false,
+                    degraded: false,
+                    status: None,
+                    error: Some(error)
+                }
+            }
+        }
+    }
+}
+
+pub fn shallow_response(version: http::version::Version) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .version(version)
+        .status(StatusCode::OK)
+        .body(Body::from("OK"))?)
+}
+
+// Whether the query string requests probing of upstream repositories
+pub fn is_deep(query: Option<&str>) -> bool {
+    query.is_some_and(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .any(|(key, value)| key == "deep" && value == "true")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(states: &[(&'static str, bool)]) -> HealthReport {
+        let repositories: Vec<RepositoryHealth> = states.iter()
+            .map(|(url, reachable)| RepositoryHealth::from_result(
+                &Uri::from_static(url), if *reachable { Ok(StatusCode::OK) } else { Err("Refused".to_owned()) }))
+            .collect();
+        HealthReport {
+            healthy: repositories.iter().all(|repository| repository.reachable),
+            repositories
+        }
+    }
+
+    #[test]
+    fn minimum_healthy_repositories() {
+        let states = [
+            ("https://first.example.com/maven2", true),
+            ("https://second.example.com/maven2", true),
+            ("https://third.example.com/maven2", true),
+            ("https://fourth.example.com/maven2", false)
+        ];
+        let degraded = |url: &str| url.contains("second");
+        // Without a minimum, every repository must be reachable
+        assert!(!report(&states).assess(degraded, None).is_healthy());
+        assert!(report(&states[..3]).assess(degraded, None).is_healthy());
+
+        for (min_healthy, healthy) in [(1, true), (2, true), (3, false)] {
+            let report = report(&states).assess(degraded, Some(min_healthy));
+            assert_eq!(healthy, report.is_healthy(), "{}", min_healthy);
+            assert!(report.repositories()[1].degraded);
+        }
+        assert!(!report(&states).assess(|_| true, Some(1)).is_healthy());
+    }
+
+    #[test]
+    fn deep_query() {
+        assert!(is_deep(Some("deep=true")));
+        assert!(is_deep(Some("verbose=1&deep=true")));
+        assert!(!is_deep(Some("deep=false")));
+        assert!(!is_deep(Some("")));
+        assert!(!is_deep(None));
+    }
+}
@@ -0,0 +1,85 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::time::Duration;
+use hyper::Body;
+use hyper::body::HttpBody;
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use crate::error::ProxyError;
+
+// Waits for the first chunk of an upstream body, returning None if nothing arrives within the
+// idle timeout. The rest of the body then fails whenever it stalls for longer than the timeout
+pub async fn watch_body(mut body: Body, idle_timeout: Duration) -> Option<Body> {
+    let first = timeout(idle_timeout, body.data()).await.ok()?;
+    // Bodies which arrive whole keep their exact size, which the memory cache relies on
+    match first {
+        Some(Ok(chunk)) if body.is_end_stream() => return Some(Body::from(chunk)),
+        None => return Some(body),
+        _ => {}
+    }
+    let rest = futures_util::stream::unfold(Some(body), move |body| async move {
+        let mut body = body?;
+        match timeout(idle_timeout, body.data()).await {
+            Ok(Some(chunk)) => Some((chunk.map_err(ProxyError::from), Some(body))),
+            Ok(None) => None,
+            Err(elapsed) => {
+                log::warn!("Aborting response which stalled for {:?}", idle_timeout);
+                Some((Err(ProxyError::from(elapsed)), None))
+            }
+        }
+    });
+    let first = futures_util::stream::iter(first).map(|chunk| chunk.map_err(ProxyError::from));
+    Some(Body::wrap_stream(first.chain(rest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::Bytes;
+    use eyre::Result;
+
+    const IDLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn stalling_body(chunks: &'static [&'static str]) -> Body {
+        let chunks = futures_util::stream::iter(chunks.iter().map(|chunk| Ok::<_, std::io::Error>(Bytes::from(*chunk))));
+        Body::wrap_stream(chunks.chain(futures_util::stream::pending()))
+    }
+
+    #[tokio::test]
+    async fn whole_body_kept() -> Result<()> {
+        let body = watch_body(Body::from("complete"), IDLE_TIMEOUT).await.expect("Body arrives");
+        assert_eq!(Some(8), body.size_hint().exact());
+        assert_eq!("complete", hyper::body::to_bytes(body).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stall_before_first_chunk() {
+        assert!(watch_body(stalling_body(&[]), IDLE_TIMEOUT).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stall_mid_body() -> Result<()> {
+        let body = watch_body(stalling_body(&["partial", "body"]), IDLE_TIMEOUT).await.expect("First chunk arrives");
+        timeout(Duration::from_secs(5), hyper::body::to_bytes(body)).await?
+            .expect_err("Stalled body is aborted");
+        Ok(())
+    }
+}
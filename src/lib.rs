@@ -0,0 +1,83 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+#![forbid(unsafe_code)]
+
+mod access_log;
+pub mod audit_log;
+pub mod admin;
+pub mod app;
+mod bandwidth;
+mod body_limit;
+mod body_observer;
+mod byte_range;
+mod checksum;
+mod circuit_breaker;
+mod client_limit;
+mod coalesce;
+pub mod cli;
+pub mod conditional;
+pub mod config;
+mod connection_limit;
+pub mod cors;
+mod deadline;
+mod disconnect;
+mod distinct;
+mod downgrade;
+pub mod disk_cache;
+mod encoding;
+pub mod error;
+pub mod forwarded;
+pub mod headers;
+mod health;
+mod idle_timeout;
+pub mod local_repository;
+pub mod listener;
+pub mod logging;
+pub mod memory_cache;
+mod metadata;
+#[cfg(test)]
+mod mock;
+mod negative_cache;
+pub mod pages;
+pub mod repository;
+pub mod repository_index;
+pub mod request;
+mod request_id;
+mod rate_limit;
+pub mod redirect;
+mod resolve;
+mod response_hash;
+pub mod runtime;
+pub mod retry;
+pub mod rewrite;
+pub mod rules;
+mod stats;
+pub mod status_policy;
+pub mod telemetry;
+pub mod tls;
+mod tenants;
+mod top_clients;
+pub mod upstream_proxy;
+
+pub use app::Application;
+pub use config::Config;
+pub use error::ProxyError;
+pub use repository::Repository;
+pub use request::AllowedMethod;
@@ -0,0 +1,134 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use socket2::{Domain, Protocol, Socket, Type};
+
+// Tuning for the listening socket, for deployments accepting many connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenerOptions {
+    backlog: u32,
+    reuse_address: bool,
+    tcp_nodelay: bool
+}
+
+impl ListenerOptions {
+    // Address reuse only applies on Unix, where it matches the standard library, so restarting
+    // does not wait for old connections. Elsewhere it would allow other processes to take the port
+    pub fn new(backlog: u32, reuse_address: bool, tcp_nodelay: bool) -> Self {
+        Self {
+            backlog,
+            reuse_address,
+            tcp_nodelay
+        }
+    }
+
+    // Whether accepted connections send small writes without waiting to coalesce them
+    pub fn tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay
+    }
+}
+
+impl Default for ListenerOptions {
+    fn default() -> Self {
+        Self::new(1024, true, false)
+    }
+}
+
+// Binds a listening socket. IPv6 sockets also accept IPv4 clients when dual_stack is
+// set, where the platform supports it, and only IPv6 clients otherwise
+pub fn bind(address: SocketAddr, dual_stack: bool, options: ListenerOptions) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(options.reuse_address)?;
+    socket.set_nodelay(options.tcp_nodelay)?;
+    socket.bind(&address.into())?;
+    socket.listen(i32::try_from(options.backlog).unwrap_or(i32::MAX))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpStream};
+
+    fn only_v6(listener: &TcpListener) -> io::Result<bool> {
+        Socket::from(listener.try_clone()?).only_v6()
+    }
+
+    #[test]
+    fn bind_ipv6_loopback() -> io::Result<()> {
+        let listener = bind(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0), true, ListenerOptions::default())?;
+        let address = listener.local_addr()?;
+        assert!(address.is_ipv6());
+        TcpStream::connect(address)?;
+        Ok(())
+    }
+
+    #[test]
+    fn dual_stack_option() -> io::Result<()> {
+        let unspecified = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+        let dual = bind(unspecified, true, ListenerOptions::default())?;
+        assert!(!only_v6(&dual)?);
+        // IPv4 clients reach the IPv6 socket
+        TcpStream::connect((Ipv4Addr::LOCALHOST, dual.local_addr()?.port()))?;
+
+        let single = bind(unspecified, false, ListenerOptions::default())?;
+        assert!(only_v6(&single)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bind_ipv4() -> io::Result<()> {
+        let listener = bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0), true, ListenerOptions::default())?;
+        assert!(listener.local_addr()?.is_ipv4());
+        Ok(())
+    }
+
+    #[test]
+    fn socket_options() -> io::Result<()> {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let listener = Socket::from(bind(address, true, ListenerOptions::new(16, false, true))?);
+        assert!(listener.nodelay()?);
+        #[cfg(unix)]
+        assert!(!listener.reuse_address()?);
+
+        let listener = Socket::from(bind(address, true, ListenerOptions::default())?);
+        assert!(!listener.nodelay()?);
+        #[cfg(unix)]
+        assert!(listener.reuse_address()?);
+        Ok(())
+    }
+
+    #[test]
+    fn backlog_accepts_connections() -> io::Result<()> {
+        let listener = bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0), true, ListenerOptions::new(1, true, false))?;
+        let address = listener.local_addr()?;
+        // Connections queue in the backlog until accepted
+        let _client = TcpStream::connect(address)?;
+        listener.set_nonblocking(false)?;
+        listener.accept()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::convert::Infallible;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use hyper::{Body, Response, StatusCode, http};
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use eyre::Result;
+use crate::pages;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+// Directory listings are sent this many entries at a time
+const LISTING_BATCH_SIZE: usize = 256;
+
+// A directory laid out like a maven repository, served read-only
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalRepository {
+    base: PathBuf
+}
+
+impl LocalRepository {
+    pub fn new(base: PathBuf) -> Self {
+        Self {
+            base
+        }
+    }
+
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    // Joins the path onto the base directory, unless it could refer to anything outside it
+    fn resolve(&self, gav_path: &str) -> Option<PathBuf> {
+        let relative = gav_path.trim_start_matches('/');
+        let only_names = relative.split('/').all(|segment| !matches!(segment, "" | "." | ".."))
+            && Path::new(relative).components().all(|component| matches!(component, Component::Normal(_)));
+        if !only_names || relative.contains('\\') || relative.contains('%') {
+            return None;
+        }
+        Some(self.base.join(relative))
+    }
+
+    // Responds with the file at the path, or None if the directory does not have it
+    pub async fn response(&self, version: http::version::Version, gav_path: &str) -> Result<Option<Response<Body>>> {
+        let path = match self.resolve(gav_path) {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        let file = match tokio::fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into())
+        };
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Ok(None);
+        }
+        log::trace!("Serving {} from local repository {}", path.display(), self.base.display());
+        Ok(Some(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type(&path))
+            .header(CONTENT_LENGTH, metadata.len())
+            .body(file_body(file))?))
+    }
+
+    // Responds with an HTML listing of the directory at the path, or None if there is no such directory.
+    // Subdirectories end with a slash. The HTML is streamed, rather than built in full for large directories
+    pub async fn listing(&self, version: http::version::Version, gav_path: &str) -> Result<Option<Response<Body>>> {
+        let path = match self.resolve(gav_path.trim_end_matches('/')) {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        let mut entries = match tokio::fs::read_dir(&path).await {
+            Ok(entries) => entries,
+            Err(error) if matches!(error.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) => return Ok(None),
+            Err(error) => return Err(error.into())
+        };
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue
+            };
+            if entry.file_type().await?.is_dir() {
+                names.push(format!("{}/", name));
+            } else {
+                names.push(name);
+            }
+        }
+        names.sort();
+        let title = pages::html_escape(gav_path);
+        let header = format!(
+            "<!DOCTYPE html>\n<html><head><title>Index of {0}</title></head><body><h1>Index of {0}</h1><ul>\n",
+            title);
+        let entries = futures_util::stream::iter(names)
+            .chunks(LISTING_BATCH_SIZE)
+            .map(|batch| {
+                batch.iter()
+                    .map(|name| format!("<li><a href=\"{0}\">{0}</a></li>\n", pages::html_escape(name)))
+                    .collect::<String>()
+            });
+        let html = futures_util::stream::iter([header])
+            .chain(entries)
+            .chain(futures_util::stream::iter(["</ul></body></html>\n".to_owned()]))
+            .map(|chunk| Ok::<_, Infallible>(Bytes::from(chunk)));
+        Ok(Some(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::wrap_stream(html))?))
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("jar") | Some("war") | Some("ear") => "application/java-archive",
+        Some("pom") | Some("xml") => "application/xml",
+        Some("sha1") | Some("sha256") | Some("sha512") | Some("md5") | Some("asc") => "text/plain",
+        Some("zip") => "application/zip",
+        Some("json") | Some("module") => "application/json",
+        _ => "application/octet-stream"
+    }
+}
+
+// Streams a file, or part of one, in chunks rather than reading it whole
+pub fn file_body(file: impl AsyncRead + Send + Unpin + 'static) -> Body {
+    let stream = futures_util::stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(Bytes::from(buffer)), Some(file)))
+            },
+            Err(error) => Some((Err(error), None))
+        }
+    });
+    Body::wrap_stream(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_rejects_traversal() {
+        let repository = LocalRepository::new(PathBuf::from("/srv/maven"));
+        assert_eq!(Some(PathBuf::from("/srv/maven/org/example/1.0/example-1.0.jar")),
+                   repository.resolve("/org/example/1.0/example-1.0.jar"));
+        assert_eq!(None, repository.resolve("/org/../../etc/passwd"));
+        assert_eq!(None, repository.resolve("/org/./example"));
+        assert_eq!(None, repository.resolve("/org/%2e%2e/example"));
+        assert_eq!(None, repository.resolve("/org\\..\\example"));
+    }
+
+    #[tokio::test]
+    async fn serve_file() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let artifact_directory = directory.path().join("org/example/1.0");
+        std::fs::create_dir_all(&artifact_directory)?;
+        let content = b"0123456789".repeat(10_000);
+        std::fs::write(artifact_directory.join("example-1.0.jar"), &content)?;
+        let repository = LocalRepository::new(directory.path().to_owned());
+
+        let response = repository.response(http::version::Version::HTTP_11, "/org/example/1.0/example-1.0.jar").await?
+            .expect("File exists");
+        assert_eq!("application/java-archive", response.headers()[CONTENT_TYPE]);
+        assert_eq!(content.len().to_string(), response.headers()[CONTENT_LENGTH]);
+        assert_eq!(content, hyper::body::to_bytes(response.into_body()).await?.to_vec());
+
+        assert!(repository.response(http::version::Version::HTTP_11, "/org/example/2.0/example-2.0.jar").await?.is_none());
+        assert!(repository.response(http::version::Version::HTTP_11, "/org/example").await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_directory() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::create_dir_all(directory.path().join("org/example/1.0"))?;
+        std::fs::write(directory.path().join("org/example/maven-metadata.xml"), "<metadata/>")?;
+        let repository = LocalRepository::new(directory.path().to_owned());
+
+        let response = repository.listing(http::version::Version::HTTP_11, "/org/example/").await?
+            .expect("Directory exists");
+        let html = String::from_utf8(hyper::body::to_bytes(response.into_body()).await?.to_vec())?;
+        assert!(html.contains("<h1>Index of /org/example/</h1>"));
+        let directory_link = html.find("<a href=\"1.0/\">1.0/</a>").expect("Directory is listed");
+        let file_link = html.find("<a href=\"maven-metadata.xml\">").expect("File is listed");
+        assert!(directory_link < file_link);
+
+        assert!(repository.listing(http::version::Version::HTTP_11, "/org/missing/").await?.is_none());
+        assert!(repository.listing(http::version::Version::HTTP_11, "/org/example/maven-metadata.xml/").await?.is_none());
+        assert!(repository.listing(http::version::Version::HTTP_11, "/org/../../").await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_large_listing() -> Result<()> {
+        use hyper::body::HttpBody;
+
+        let directory = tempfile::tempdir()?;
+        let versions = directory.path().join("org/example");
+        for number in (0..1000).rev() {
+            std::fs::create_dir_all(versions.join(format!("{:04}", number)))?;
+            std::fs::write(versions.join(format!("{:04}.txt", number)), "")?;
+        }
+        let repository = LocalRepository::new(directory.path().to_owned());
+        let mut body = repository.listing(http::version::Version::HTTP_11, "/org/example/").await?
+            .expect("Directory exists")
+            .into_body();
+        let mut chunks = 0;
+        let mut html = String::new();
+        while let Some(chunk) = body.data().await {
+            chunks += 1;
+            html.push_str(std::str::from_utf8(&chunk?)?);
+        }
+        assert!(chunks > 2, "{} chunks", chunks);
+
+        let listed: Vec<&str> = html.lines()
+            .filter_map(|line| line.strip_prefix("<li><a href=\""))
+            .map(|line| line.split('"').next().unwrap())
+            .collect();
+        let mut expected: Vec<String> = (0..1000)
+            .flat_map(|number| [format!("{:04}/", number), format!("{:04}.txt", number)])
+            .collect();
+        expected.sort();
+        assert_eq!(expected, listed);
+        assert!(html.ends_with("</ul></body></html>\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::{Deserialize, Serialize};
+use simple_logger::SimpleLogger;
+use crate::request_id::RequestId;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json
+}
+
+// The level may be changed afterwards through Logger::set_level
+pub fn init(format: LogFormat, level: log::Level) -> Result<(), SetLoggerError> {
+    log::set_max_level(level.to_level_filter());
+    match format {
+        LogFormat::Text => {
+            let logger = TextLogger { inner: SimpleLogger::new().with_level(LevelFilter::Trace) };
+            log::set_boxed_logger(Box::new(logger))
+        },
+        LogFormat::Json => log::set_boxed_logger(Box::new(JsonLogger))
+    }
+}
+
+// Where the application logs to. By default the global logger, which filters by the global
+// maximum level, so changing the level takes effect immediately. A scoped logger has a level of
+// its own and leaves the global logger and level untouched, such as for observing what is logged
+#[derive(Clone, Default)]
+pub struct Logger {
+    scoped: Option<Arc<ScopedLogger>>
+}
+
+struct ScopedLogger {
+    level: AtomicUsize,
+    inner: Box<dyn Log>
+}
+
+// Indexed by the numeric value of each level filter
+const LEVEL_FILTERS: [LevelFilter; 6] = [
+    LevelFilter::Off, LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace
+];
+
+impl Logger {
+    pub fn scoped(inner: impl Log + 'static, level: LevelFilter) -> Self {
+        Self {
+            scoped: Some(Arc::new(ScopedLogger { level: AtomicUsize::new(level as usize), inner: Box::new(inner) }))
+        }
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        match &self.scoped {
+            Some(scoped) => LEVEL_FILTERS[scoped.level.load(Ordering::Relaxed)],
+            None => log::max_level()
+        }
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        match &self.scoped {
+            Some(scoped) => scoped.level.store(level as usize, Ordering::Relaxed),
+            None => log::set_max_level(level)
+        }
+    }
+
+    pub fn log(&self, level: Level, target: &str, args: fmt::Arguments) {
+        if level > self.level() {
+            return;
+        }
+        let record = Record::builder().level(level).target(target).args(args).build();
+        match &self.scoped {
+            Some(scoped) => scoped.inner.log(&record),
+            None => log::logger().log(&record)
+        }
+    }
+}
+
+// Prefixes messages logged while handling a request with the request ID
+struct TextLogger {
+    inner: SimpleLogger
+}
+
+impl Log for TextLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match RequestId::current() {
+            Some(request_id) => self.inner.log(&Record::builder()
+                .args(format_args!("[{}] {}", request_id, record.args()))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build()),
+            None => self.inner.log(record)
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+// Writes each record as a single line JSON object, for log shippers
+struct JsonLogger;
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = format_record(record, SystemTime::now());
+            let mut stdout = std::io::stdout().lock();
+            // Logging has nowhere to report its own failures
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+fn format_record(record: &Record, time: SystemTime) -> String {
+    let mut json = serde_json::json!({
+        "timestamp": format_timestamp(time),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string()
+    });
+    if let Some(request_id) = RequestId::current() {
+        json["request_id"] = request_id.to_string().into();
+    }
+    json.to_string()
+}
+
+// Formats as an RFC 3339 timestamp in UTC with millisecond precision
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+    // Converts days since the epoch to a civil date, see Howard Hinnant's "civil_from_days"
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year, month, day,
+            seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60,
+            since_epoch.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use serde_json::Value;
+    use crate::mock::RecordingLogger;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_timestamp(UNIX_EPOCH));
+        let time = UNIX_EPOCH + Duration::from_millis(1_634_300_000_123);
+        assert_eq!("2021-10-15T12:13:20.123Z", format_timestamp(time));
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!("2000-02-29T00:00:00.000Z", format_timestamp(leap_day));
+    }
+
+    #[test]
+    fn json_record() -> eyre::Result<()> {
+        let line = format_record(&Record::builder()
+            .level(log::Level::Warn)
+            .target("access")
+            .args(format_args!("Quote \" and\nnewline"))
+            .build(), UNIX_EPOCH);
+        assert!(!line.contains('\n'));
+        let parsed: Value = serde_json::from_str(&line)?;
+        assert_eq!("1970-01-01T00:00:00.000Z", parsed["timestamp"]);
+        assert_eq!("WARN", parsed["level"]);
+        assert_eq!("access", parsed["target"]);
+        assert_eq!("Quote \" and\nnewline", parsed["message"]);
+        assert!(parsed.get("request_id").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_logger() {
+        let recording = RecordingLogger::default();
+        let logger = Logger::scoped(recording.clone(), LevelFilter::Info);
+        logger.log(Level::Info, "test", format_args!("Kept"));
+        logger.log(Level::Debug, "test", format_args!("Filtered"));
+        logger.set_level(LevelFilter::Debug);
+        assert_eq!(LevelFilter::Debug, logger.level());
+        logger.log(Level::Debug, "test", format_args!("Kept after change"));
+        assert_eq!(vec!["Kept", "Kept after change"], recording.messages());
+    }
+
+    #[tokio::test]
+    async fn json_record_with_request_id() -> eyre::Result<()> {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(crate::headers::X_REQUEST_ID, "abc-123".parse()?);
+        let line = RequestId::from_headers(&headers).scope(async {
+            format_record(&Record::builder().args(format_args!("Message")).build(), UNIX_EPOCH)
+        }).await;
+        let parsed: Value = serde_json::from_str(&line)?;
+        assert_eq!("abc-123", parsed["request_id"]);
+        Ok(())
+    }
+}
@@ -19,42 +19,189 @@
 
 #![forbid(unsafe_code)]
 
-mod app;
-mod config;
-mod request;
-
-use app::Application;
 use hyper::Client;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::SocketAddr;
 use std::path::Path;
-use crate::config::Config;
+use std::sync::Arc;
 use eyre::Result;
-use simple_logger::SimpleLogger;
-use hyper_rustls::HttpsConnector;
+use rust_maven_proxy::{logging, runtime, tls, Application, Config};
+use rust_maven_proxy::retry::RetryPolicy;
+use rust_maven_proxy::redirect::RedirectPolicy;
+use rust_maven_proxy::admin::ConfigReload;
+use rust_maven_proxy::cli::Arguments;
+use rust_maven_proxy::upstream_proxy::ProxySettings;
+use rust_maven_proxy::pages::{ErrorPageTemplate, Favicon};
+use rust_maven_proxy::conditional::ResponseStore;
+use rust_maven_proxy::disk_cache::DiskCache;
+use rust_maven_proxy::audit_log::AuditLog;
+use rust_maven_proxy::telemetry::Tracer;
+#[cfg(feature = "otlp")]
+use rust_maven_proxy::telemetry::OtlpExporter;
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     stable_eyre::install()?;
 
-    let config_path = Path::new("config.ron");
+    let arguments = Arguments::from_env()?;
+    let config_path = arguments.config_path();
+    if arguments.validate_config() {
+        match validate_config(config_path) {
+            Ok(()) => println!("Configuration is valid"),
+            Err(error) => {
+                eprintln!("Invalid configuration {}: {:?}", config_path.display(), error);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
     println!("Loading configuration from {:?}", config_path);
-    let config = Config::load_from(config_path).expect("Failed to load config");
+    let config = match Config::load_from(config_path) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load config: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // The runtime is chosen by the config, so it is built once the config is loaded
+    runtime::build(config.multi_thread_runtime(), config.worker_threads())?
+        .block_on(serve(config_path, config))
+}
 
-    SimpleLogger::new()
-        .with_level(config.log_level().to_level_filter())
-        .init().expect("Logging initialization failure");
+async fn serve(config_path: &Path, config: Config) -> Result<()> {
+    logging::init(config.log_format(), config.log_level())
+        .expect("Logging initialization failure");
+    // After logging starts, so that warnings about the config are logged
+    config.validate()?;
 
     let port = config.port();
     log::info!("Starting rust maven proxy on port {} ... ", port);
 
     let application = {
-        let https_connector = HttpsConnector::with_native_roots();
-        let client = Client::builder().build(https_connector);
-        let repositories = config.repositories();
+        let proxy_settings = ProxySettings::resolve(config.http_proxy(), config.https_proxy())?;
+        let build_client = |http2: bool| -> Result<_> {
+            let tls_config = tls::client_config(config.ca_bundle(), http2, config.insecure_skip_tls_verify())?;
+            let https_connector = tls::https_connector(tls_config.clone(), Some(config.connect_timeout()));
+            Ok(Client::builder()
+                .pool_idle_timeout(config.pool_idle_timeout())
+                .pool_max_idle_per_host(config.pool_max_idle_per_host())
+                .http2_adaptive_window(http2)
+                .build(proxy_settings.connector(https_connector, tls_config)?))
+        };
+        let client = build_client(config.upstream_http2())?;
+        // Only needed when HTTP/2 is offered to repositories
+        let http1_client = (config.upstream_http2() && config.upstream_http1_fallback())
+            .then(|| build_client(false))
+            .transpose()?;
+        let repositories = config.repositories()?;
+        let disk_cache = config.disk_cache_directory().map(DiskCache::open).transpose()?.map(Arc::new);
+        // Spans go straight to the collector, rather than through any upstream proxy
+        #[cfg(feature = "otlp")]
+        let tracer = config.tracing_endpoint().map(|endpoint| -> Result<_> {
+            let tls_config = tls::client_config(config.ca_bundle(), false, config.insecure_skip_tls_verify())?;
+            let client = Client::builder().build(tls::https_connector(tls_config, Some(config.connect_timeout())));
+            let exporter = OtlpExporter::start(client, endpoint.as_str().parse()?);
+            Ok(Tracer::new(Arc::new(exporter)))
+        }).transpose()?;
+        // Enabling tracing without the feature is rejected by the config
+        #[cfg(not(feature = "otlp"))]
+        let tracer: Option<Tracer> = None;
         log::info!("Using repositories {:?}", &repositories);
         Application::new(client, repositories, config.proxy_timeout())
+            .with_http1_fallback(http1_client, config.remember_http1_fallback())
+            .with_fallback_repositories(config.fallback_repositories()?)
+            .with_groups(config.groups()?)
+            .with_repository_rules(config.repository_rules())
+            .with_local_repositories(config.local_repositories())
+            .with_group_allowlist(config.group_allowlist())
+            .with_retry_policy(RetryPolicy::new(config.max_retries(), config.retry_backoff())
+                .with_timeout_escalation(config.retry_initial_timeout(), config.retry_timeout_multiplier())
+                .with_retry_statuses(config.retry_statuses())
+                .with_budget(config.retry_budget_per_second()))
+            .with_redirect_policy(RedirectPolicy::new(config.max_redirects(), config.follow_cross_host_redirects()))
+            .with_redirect_cache_ttl(config.redirect_cache_ttl())
+            .with_status_policy(config.status_policy())
+            .with_prefer_order(config.prefer_order())
+            .with_max_fanout(config.max_fanout())
+            .with_snapshot_freshness(config.snapshot_freshness())
+            .with_snapshot_version_resolution(config.resolve_snapshot_versions())
+            .with_max_concurrent_upstream(config.max_concurrent_upstream())
+            .with_max_connections(config.max_connections())
+            .with_dual_stack(config.dual_stack())
+            .with_listener_options(config.listener_options())
+            .with_http1_keepalive(config.http1_keepalive())
+            .with_server_idle_timeout(config.server_idle_timeout())
+            .with_tls_acceptor(config.tls_identity().map(|(cert, key)| tls::server_acceptor(cert, key)).transpose()?)
+            .with_negative_cache_ttl(config.negative_cache_ttl())
+            .with_memory_cache(config.memory_cache())
+            .with_head_from_cache(config.head_from_cache())
+            .with_ranges_from_cache(config.ranges_from_cache())
+            .with_disk_cache(disk_cache.clone())
+            .with_cache_eviction(config.eviction_limits(), config.cache_eviction_interval())
+            .with_response_store(disk_cache.map(|disk_cache| disk_cache as Arc<dyn ResponseStore>))
+            .with_serve_stale_on_error(config.serve_stale_on_error())
+            .with_not_found_recheck(config.recheck_404_after())
+            .with_verbose_not_found(config.verbose_not_found())
+            .with_offline_mode(config.offline_mode())
+            .with_file_size_timeouts(config.metadata_timeout(), config.artifact_timeout())
+            .with_timeout_jitter(config.timeout_jitter())
+            .with_request_deadline(config.request_deadline())
+            .with_stream_idle_timeout(config.stream_idle_timeout())
+            .with_circuit_breaker(config.circuit_breaker_threshold(), config.circuit_breaker_cooldown())
+            .with_repository_state_path(config.persist_repository_state_path().map(Path::to_owned))
+            .with_readiness_min_healthy(config.readiness_min_healthy_repositories())
+            .with_shutdown_timeout(config.shutdown_timeout())
+            .with_warmup_interval(config.warmup_interval())
+            .with_index_reload_interval(config.repository_index_reload_interval())
+            .with_expose_served_by(config.expose_served_by())
+            .with_reject_query_strings(config.reject_query_strings())
+            .with_reject_request_bodies(config.reject_request_bodies())
+            .with_cors(config.cors())
+            .with_directory_listing(config.allow_directory_listing())
+            .with_response_header_policy(config.response_header_policy()?)
+            .with_cache_control(config.cache_control())
+            .with_compress_responses(config.compress_responses())
+            .with_stats(config.stats_enabled())
+            .with_stats_snapshot(config.stats_snapshot_path().map(Path::to_owned))
+            .with_distinct_paths_window(config.distinct_paths_window())
+            .with_top_clients(config.top_clients_enabled())
+            .with_tenant_header(config.tenant_header()?)
+            .with_audit_log(config.audit_log_path().map(AuditLog::open).transpose()?)
+            .with_log_response_hashes(config.log_response_hashes())
+            .with_slow_request_threshold(config.slow_request_threshold())
+            .with_tracer(tracer)
+            .with_debug_endpoint(config.debug_endpoint_enabled())
+            .with_resolve_endpoint(config.resolve_endpoint_enabled())
+            .with_repository_pinning(config.allow_repository_pinning())
+            .with_normalize_slashes(config.normalize_slashes())
+            .with_coordinate_validation(config.validate_coordinates())
+            .with_metadata_divergence_detection(config.detect_metadata_divergence())
+            .with_checksum_synthesis(config.synthesize_checksums())
+            .with_checksum_prefetch(config.prefetch_checksums())
+            .with_checksum_normalization(config.normalize_checksum_responses())
+            .with_path_rewrites(config.path_rewrites()?)
+            .with_rate_limit(config.rate_limit_per_second())
+            .with_max_inflight_per_client(config.max_inflight_per_client())
+            .with_trusted_proxies(config.trusted_proxies())
+            .with_bandwidth_limit(config.max_bandwidth_bytes_per_sec())
+            .with_header_limits(Some(config.max_header_count()), Some(config.max_header_bytes()))
+            .with_max_path_length(Some(config.max_path_length()))
+            .with_max_artifact_size(config.max_artifact_size())
+            .with_buffer_threshold(config.buffer_threshold_bytes())
+            .with_path_prefix(config.path_prefix().map(str::to_owned))
+            .with_publish_repository(config.publish_repository()?)
+            .with_request_header_rules(config.request_header_rules()?)
+            .with_user_agent(config.user_agent()?)
+            .with_favicon(config.favicon_path().map(Favicon::load).transpose()?)
+            .with_static_responses(config.static_responses())
+            .with_error_page(config.error_page_template().map(ErrorPageTemplate::load).transpose()?)
+            .with_config_reload(config.admin_secret().map(|secret| {
+                ConfigReload::new(config_path.to_owned(), secret.to_owned(), port)
+            }))
     };
-    let socket = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    if config.startup_check() {
+        application.startup_check(config.startup_check_strict()).await?;
+    }
+    let socket = SocketAddr::new(config.bind_address(), port);
     let server = application.start_on(socket, shutdown_signal());
 
     log::info!("Started server");
@@ -62,9 +209,45 @@ async fn main() -> Result<()> {
     server.await
 }
 
+// Checks everything needed to start the server, without creating a missing config or binding the port
+fn validate_config(config_path: &Path) -> Result<()> {
+    if !config_path.is_file() {
+        return Err(eyre::eyre!("Config {} does not exist", config_path.display()));
+    }
+    let config = Config::load_from(config_path)?;
+    config.validate()?;
+    tls::client_config(config.ca_bundle(), config.upstream_http2(), config.insecure_skip_tls_verify())?;
+    config.tls_identity().map(|(cert, key)| tls::server_acceptor(cert, key)).transpose()?;
+    ProxySettings::resolve(config.http_proxy(), config.https_proxy())?;
+    config.favicon_path().map(Favicon::load).transpose()?;
+    config.error_page_template().map(ErrorPageTemplate::load).transpose()?;
+    Ok(())
+}
+
 async fn shutdown_signal() {
+    let signal = wait_for_signal().await;
+    log::info!("Stopping server due to {}", signal);
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())
+        .expect("Failed to install SIGTERM handler");
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            result.expect("Failed to install CTRL+C handler");
+            "CTRL+C press"
+        },
+        _ = terminate.recv() => "SIGTERM"
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
     tokio::signal::ctrl_c()
         .await
         .expect("Failed to install CTRL+C handler");
-    log::info!("Stopping server due to CTRL+C press");
+    "CTRL+C press"
 }
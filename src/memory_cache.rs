@@ -0,0 +1,272 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use hyper::{Body, HeaderMap, Response, StatusCode, http};
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_ENCODING;
+use eyre::Result;
+use crate::coalesce::{self, SharedResponse};
+use crate::request::FileSize;
+
+// Keeps recently served small files, such as metadata, checksums and POMs, in memory
+#[derive(Debug)]
+pub struct MemoryCache {
+    // The total size of the cached bodies, in bytes
+    capacity: usize,
+    max_file_size: usize,
+    ttl: Duration,
+    snapshot_ttl: Duration,
+    state: Mutex<CacheState>
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    size: usize,
+    // Incremented on every access, to find the least recently used entry
+    clock: u64
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: SharedResponse,
+    expiry: Instant,
+    last_used: u64
+}
+
+impl CacheState {
+    // Returns whether there was an entry to remove
+    fn remove(&mut self, key: &str) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.size -= entry.response.len();
+                true
+            },
+            None => false
+        }
+    }
+}
+
+// Only small files are cached, and only for requests whose response does not depend on their headers
+pub fn is_cacheable(path: &str, headers: &HeaderMap) -> bool {
+    FileSize::classify(path) == FileSize::Small && !coalesce::is_personalized(headers)
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize, max_file_size: usize, ttl: Duration, snapshot_ttl: Duration) -> Self {
+        Self {
+            capacity,
+            max_file_size,
+            ttl,
+            snapshot_ttl,
+            state: Mutex::new(CacheState::default())
+        }
+    }
+
+    // SNAPSHOT metadata changes whenever a new build is published
+    fn ttl_for(&self, key: &str) -> Duration {
+        if key.contains("-SNAPSHOT") {
+            self.snapshot_ttl
+        } else {
+            self.ttl
+        }
+    }
+
+    pub fn get(&self, key: &str, version: http::version::Version) -> Option<Response<Body>> {
+        self.get_at(key, version, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, version: http::version::Version, now: Instant) -> Option<Response<Body>> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        match state.entries.get_mut(key) {
+            Some(entry) if entry.expiry > now => {
+                entry.last_used = clock;
+                Some(entry.response.to_response(version))
+            },
+            Some(_) => {
+                state.remove(key);
+                None
+            },
+            None => None
+        }
+    }
+
+    // Returns whether an entry was removed
+    pub fn remove(&self, key: &str) -> bool {
+        self.state.lock().unwrap().remove(key)
+    }
+
+    // Returns the number of entries removed
+    pub fn clear(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.entries.len();
+        state.entries.clear();
+        state.size = 0;
+        removed
+    }
+
+    // Caches successful responses of a known, small size which are not content-encoded, since
+    // the encoding depends on the client. The response is returned for serving either way
+    pub async fn store(&self, key: &str, response: Response<Body>) -> Result<Response<Body>> {
+        let size = response.body().size_hint().exact();
+        let cacheable = response.status() == StatusCode::OK
+            && !response.headers().contains_key(CONTENT_ENCODING)
+            && size.is_some_and(|size| size <= self.max_file_size as u64);
+        if !cacheable {
+            return Ok(response);
+        }
+        let version = response.version();
+        let shared = SharedResponse::read(response).await?;
+        self.insert_at(key, shared.clone(), Instant::now());
+        Ok(shared.to_response(version))
+    }
+
+    fn insert_at(&self, key: &str, response: SharedResponse, now: Instant) {
+        let ttl = self.ttl_for(key);
+        if ttl.is_zero() || response.len() > self.capacity {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+        if state.size + response.len() > self.capacity {
+            state.entries.retain(|_, entry| entry.expiry > now);
+            state.size = state.entries.values().map(|entry| entry.response.len()).sum();
+        }
+        while state.size + response.len() > self.capacity {
+            let least_recently_used = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+                .expect("Entries remain while the cache is over capacity");
+            state.remove(&least_recently_used);
+        }
+        state.clock += 1;
+        state.size += response.len();
+        let entry = CacheEntry {
+            response,
+            expiry: now + ttl,
+            last_used: state.clock
+        };
+        state.entries.insert(key.to_owned(), entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{HeaderValue, AUTHORIZATION};
+
+    const VERSION: http::version::Version = http::version::Version::HTTP_11;
+    const METADATA: &str = "/org/example/example/maven-metadata.xml";
+
+    fn cache(capacity: usize) -> MemoryCache {
+        MemoryCache::new(capacity, 64, Duration::from_secs(60), Duration::from_secs(5))
+    }
+
+    async fn shared(body: &'static str) -> SharedResponse {
+        SharedResponse::read(Response::new(Body::from(body))).await.unwrap()
+    }
+
+    async fn cached_body(cache: &MemoryCache, key: &str, now: Instant) -> Option<String> {
+        let response = cache.get_at(key, VERSION, now)?;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        Some(String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn cacheable_requests() {
+        let headers = HeaderMap::new();
+        assert!(is_cacheable(METADATA, &headers));
+        assert!(is_cacheable("/org/example/1.0/example-1.0.pom", &headers));
+        assert!(is_cacheable("/org/example/1.0/example-1.0.jar.sha1", &headers));
+        assert!(!is_cacheable("/org/example/1.0/example-1.0.jar", &headers));
+        let mut authorized = HeaderMap::new();
+        authorized.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        assert!(!is_cacheable(METADATA, &authorized));
+    }
+
+    #[tokio::test]
+    async fn cached_until_expiry() {
+        let cache = cache(1024);
+        let now = Instant::now();
+        cache.insert_at(METADATA, shared("<metadata/>").await, now);
+        assert_eq!(Some("<metadata/>".to_owned()), cached_body(&cache, METADATA, now + Duration::from_secs(59)).await);
+        assert_eq!(None, cached_body(&cache, METADATA, now + Duration::from_secs(60)).await);
+        assert_eq!(0, cache.state.lock().unwrap().size);
+    }
+
+    #[tokio::test]
+    async fn snapshots_expire_sooner() {
+        let cache = cache(1024);
+        let now = Instant::now();
+        let key = "/org/example/example/1.0-SNAPSHOT/maven-metadata.xml";
+        cache.insert_at(key, shared("<metadata/>").await, now);
+        assert!(cached_body(&cache, key, now + Duration::from_secs(4)).await.is_some());
+        assert!(cached_body(&cache, key, now + Duration::from_secs(5)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = cache(10);
+        let now = Instant::now();
+        cache.insert_at("/a.pom", shared("aaaa").await, now);
+        cache.insert_at("/b.pom", shared("bbbb").await, now);
+        assert!(cached_body(&cache, "/a.pom", now).await.is_some());
+        cache.insert_at("/c.pom", shared("cccc").await, now);
+        assert!(cached_body(&cache, "/a.pom", now).await.is_some());
+        assert!(cached_body(&cache, "/b.pom", now).await.is_none());
+        assert!(cached_body(&cache, "/c.pom", now).await.is_some());
+        assert_eq!(8, cache.state.lock().unwrap().size);
+    }
+
+    #[tokio::test]
+    async fn purged_entries() {
+        let cache = cache(1024);
+        let now = Instant::now();
+        cache.insert_at("/a.pom", shared("aaaa").await, now);
+        cache.insert_at("/b.pom", shared("bbbb").await, now);
+        assert!(cache.remove("/a.pom"));
+        assert!(!cache.remove("/a.pom"));
+        assert_eq!(4, cache.state.lock().unwrap().size);
+        assert_eq!(1, cache.clear());
+        assert!(cached_body(&cache, "/b.pom", now).await.is_none());
+        assert_eq!(0, cache.state.lock().unwrap().size);
+    }
+
+    #[tokio::test]
+    async fn stores_only_small_successes() -> Result<()> {
+        let cache = cache(1024);
+        cache.store("/large.pom", Response::new(Body::from("x".repeat(65)))).await?;
+        let mut not_found = Response::new(Body::from("missing"));
+        *not_found.status_mut() = StatusCode::NOT_FOUND;
+        cache.store("/missing.pom", not_found).await?;
+        let mut encoded = Response::new(Body::from("compressed"));
+        encoded.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        cache.store("/encoded.pom", encoded).await?;
+        assert!(cache.state.lock().unwrap().entries.is_empty());
+        let response = cache.store("/small.pom", Response::new(Body::from("<project/>"))).await?;
+        assert_eq!("<project/>", hyper::body::to_bytes(response.into_body()).await?);
+        assert!(cache.get("/small.pom", VERSION).is_some());
+        Ok(())
+    }
+}
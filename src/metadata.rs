@@ -0,0 +1,578 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use eyre::Result;
+use crate::checksum::ChecksumAlgorithm;
+
+const METADATA_FILE: &str = "maven-metadata.xml";
+
+pub fn is_metadata_path(path: &str) -> bool {
+    path.rsplit('/').next() == Some(METADATA_FILE)
+}
+
+// SNAPSHOTs, metadata and their checksums may be replaced upstream, unlike released files
+pub fn is_changing(path: &str) -> bool {
+    let checksummed = path.rsplit_once('.')
+        .filter(|(_, extension)| matches!(*extension, "sha1" | "md5" | "sha256" | "sha512"))
+        .map_or(path, |(checksummed, _)| checksummed);
+    path.contains("-SNAPSHOT") || is_metadata_path(checksummed)
+}
+
+// For files within a SNAPSHOT version directory, the path of the version-level metadata
+pub fn snapshot_metadata_path(path: &str) -> Option<String> {
+    let (directory, file) = path.rsplit_once('/')?;
+    if file.is_empty() || file == METADATA_FILE {
+        return None;
+    }
+    let version = directory.rsplit('/').next()?;
+    if !version.ends_with("-SNAPSHOT") {
+        return None;
+    }
+    Some(format!("{}/{}", directory, METADATA_FILE))
+}
+
+// For files within a version directory, the path of the artifact-level metadata, and the version
+pub fn artifact_metadata_path(path: &str) -> Option<(String, &str)> {
+    let (directory, file) = path.rsplit_once('/')?;
+    if file.is_empty() || file == METADATA_FILE {
+        return None;
+    }
+    let (artifact_directory, version) = directory.rsplit_once('/')?;
+    if artifact_directory.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some((format!("{}/{}", artifact_directory, METADATA_FILE), version))
+}
+
+// Splits a file named by its SNAPSHOT placeholder version, such as example-1.0-SNAPSHOT-sources.jar,
+// into its directory, artifact id, version, and the classifier and extension following the version
+fn split_snapshot_placeholder(path: &str) -> Option<(&str, &str, &str, &str)> {
+    let (directory, file) = path.rsplit_once('/')?;
+    let mut segments = directory.rsplit('/');
+    let version = segments.next().filter(|version| version.ends_with("-SNAPSHOT"))?;
+    let artifact_id = segments.next()?;
+    let rest = file.strip_prefix(artifact_id)?.strip_prefix('-')?.strip_prefix(version)?;
+    if !rest.starts_with('.') && !rest.starts_with('-') {
+        return None;
+    }
+    Some((directory, artifact_id, version, rest))
+}
+
+pub fn is_snapshot_placeholder(path: &str) -> bool {
+    split_snapshot_placeholder(path).is_some()
+}
+
+// The newest deployed build of a SNAPSHOT version, ordered by timestamp and then build number
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SnapshotBuild {
+    timestamp: String,
+    build_number: u32
+}
+
+impl SnapshotBuild {
+    // Fails for metadata without a timestamped snapshot, such as from a local repository
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::from_reader(data);
+        reader.trim_text(true);
+        let mut timestamp = None;
+        let mut build_number = None;
+        let mut element_path: Vec<String> = Vec::new();
+        let mut buffer = Vec::new();
+        loop {
+            match reader.read_event(&mut buffer)? {
+                Event::Start(element) => {
+                    element_path.push(String::from_utf8_lossy(element.name()).into_owned());
+                },
+                Event::End(_) => {
+                    element_path.pop();
+                },
+                Event::Text(text) => {
+                    let value = text.unescape_and_decode(&reader)?;
+                    let element_path: Vec<&str> = element_path.iter().map(String::as_str).collect();
+                    match element_path.as_slice() {
+                        ["metadata", "versioning", "snapshot", "timestamp"] => timestamp = Some(value),
+                        ["metadata", "versioning", "snapshot", "buildNumber"] => build_number = Some(value.parse()?),
+                        _ => {}
+                    }
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+            buffer.clear();
+        }
+        match (timestamp, build_number) {
+            (Some(timestamp), Some(build_number)) => Ok(Self {
+                timestamp,
+                build_number
+            }),
+            _ => Err(eyre::eyre!("Metadata does not describe a snapshot build"))
+        }
+    }
+
+    // The unique version of this build, such as 1.0-20210101.000000-3 for 1.0-SNAPSHOT
+    pub fn unique_version(&self, version: &str) -> String {
+        format!("{}-{}-{}", version.trim_end_matches("-SNAPSHOT"), self.timestamp, self.build_number)
+    }
+
+    // The path of this build's file for a path using the placeholder version, or None for other paths
+    pub fn resolve_path(&self, path: &str) -> Option<String> {
+        let (directory, artifact_id, version, rest) = split_snapshot_placeholder(path)?;
+        Some(format!("{}/{}-{}{}", directory, artifact_id, self.unique_version(version), rest))
+    }
+}
+
+// Artifact-level metadata, listing the available versions of an artifact
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    group_id: Option<String>,
+    artifact_id: Option<String>,
+    latest: Option<String>,
+    release: Option<String>,
+    versions: Vec<String>,
+    last_updated: Option<String>
+}
+
+impl Metadata {
+    // Fails for documents which are not artifact-level metadata, such as snapshot or plugin metadata
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::from_reader(data);
+        reader.trim_text(true);
+        let mut metadata = Self::default();
+        let mut element_path: Vec<String> = Vec::new();
+        let mut buffer = Vec::new();
+        loop {
+            match reader.read_event(&mut buffer)? {
+                Event::Start(element) => {
+                    element_path.push(String::from_utf8_lossy(element.name()).into_owned());
+                    let element_path: Vec<&str> = element_path.iter().map(String::as_str).collect();
+                    match element_path.as_slice() {
+                        ["metadata"]
+                        | ["metadata", "groupId" | "artifactId" | "version" | "versioning"]
+                        | ["metadata", "versioning", "latest" | "release" | "versions" | "lastUpdated"]
+                        | ["metadata", "versioning", "versions", "version"] => {},
+                        unsupported => return Err(eyre::eyre!(
+                            "Unsupported metadata element {}", unsupported.join("/")))
+                    }
+                },
+                Event::End(_) => {
+                    element_path.pop();
+                },
+                Event::Text(text) => {
+                    let value = text.unescape_and_decode(&reader)?;
+                    let element_path: Vec<&str> = element_path.iter().map(String::as_str).collect();
+                    match element_path.as_slice() {
+                        ["metadata", "groupId"] => metadata.group_id = Some(value),
+                        ["metadata", "artifactId"] => metadata.artifact_id = Some(value),
+                        ["metadata", "versioning", "latest"] => metadata.latest = Some(value),
+                        ["metadata", "versioning", "release"] => metadata.release = Some(value),
+                        ["metadata", "versioning", "lastUpdated"] => metadata.last_updated = Some(value),
+                        ["metadata", "versioning", "versions", "version"] => metadata.versions.push(value),
+                        _ => {}
+                    }
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+            buffer.clear();
+        }
+        if metadata.artifact_id.is_none() {
+            return Err(eyre::eyre!("Metadata does not describe an artifact"));
+        }
+        Ok(metadata)
+    }
+
+    // Combines the versions from all documents, recomputing the latest and release versions
+    pub fn merge(documents: Vec<Metadata>) -> Self {
+        let mut merged = Self::default();
+        for document in documents {
+            merged.group_id = merged.group_id.or(document.group_id);
+            merged.artifact_id = merged.artifact_id.or(document.artifact_id);
+            merged.last_updated = merged.last_updated.max(document.last_updated);
+            merged.versions.extend(document.versions);
+            merged.versions.extend(document.latest);
+            merged.versions.extend(document.release);
+        }
+        merged.versions.sort_by(|first, second| compare_versions(first, second));
+        merged.versions.dedup();
+        merged.latest = merged.versions.last().cloned();
+        merged.release = merged.versions
+            .iter()
+            .rev()
+            .find(|version| !version.ends_with("-SNAPSHOT"))
+            .cloned();
+        merged
+    }
+
+    pub fn latest(&self) -> Option<&str> {
+        self.latest.as_deref()
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metadata>\n");
+        push_element(&mut xml, 1, "groupId", self.group_id.as_deref());
+        push_element(&mut xml, 1, "artifactId", self.artifact_id.as_deref());
+        xml.push_str("  <versioning>\n");
+        push_element(&mut xml, 2, "latest", self.latest.as_deref());
+        push_element(&mut xml, 2, "release", self.release.as_deref());
+        xml.push_str("    <versions>\n");
+        for version in &self.versions {
+            push_element(&mut xml, 3, "version", Some(version));
+        }
+        xml.push_str("    </versions>\n");
+        push_element(&mut xml, 2, "lastUpdated", self.last_updated.as_deref());
+        xml.push_str("  </versioning>\n</metadata>\n");
+        xml
+    }
+}
+
+fn push_element(xml: &mut String, indent: usize, name: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        xml.push_str(&format!("{}<{}>{}</{}>\n", "  ".repeat(indent), name, escape(value), name));
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VersionItem {
+    Number(u64),
+    Qualifier(String)
+}
+
+impl VersionItem {
+    // Known qualifiers in ascending order, with the empty qualifier denoting a release
+    fn qualifier_rank(qualifier: &str) -> Option<usize> {
+        let qualifier = match qualifier {
+            "a" => "alpha",
+            "b" => "beta",
+            "m" => "milestone",
+            "cr" => "rc",
+            "ga" | "final" | "release" => "",
+            other => other
+        };
+        ["alpha", "beta", "milestone", "rc", "snapshot", "", "sp"]
+            .iter()
+            .position(|known| *known == qualifier)
+    }
+
+    fn compare_qualifiers(first: &str, second: &str) -> Ordering {
+        match (Self::qualifier_rank(first), Self::qualifier_rank(second)) {
+            (Some(first), Some(second)) => first.cmp(&second),
+            // Unknown qualifiers come after known ones, ordered lexically
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => first.cmp(second)
+        }
+    }
+
+    // Compares against a missing item, such that 1.0 equals 1.0.0 and 1.0-rc precedes 1.0
+    fn compare_to_missing(&self) -> Ordering {
+        match self {
+            VersionItem::Number(number) => number.cmp(&0),
+            VersionItem::Qualifier(qualifier) => Self::compare_qualifiers(qualifier, "")
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (VersionItem::Number(first), VersionItem::Number(second)) => first.cmp(second),
+            (VersionItem::Qualifier(first), VersionItem::Qualifier(second)) => {
+                Self::compare_qualifiers(first, second)
+            },
+            (VersionItem::Number(_), VersionItem::Qualifier(_)) => Ordering::Greater,
+            (VersionItem::Qualifier(_), VersionItem::Number(_)) => Ordering::Less
+        }
+    }
+}
+
+fn parse_version(version: &str) -> Vec<VersionItem> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut flush = |current: &mut String| {
+        if !current.is_empty() {
+            items.push(match current.parse::<u64>() {
+                Ok(number) => VersionItem::Number(number),
+                Err(_) => VersionItem::Qualifier(current.to_lowercase())
+            });
+            current.clear();
+        }
+    };
+    for character in version.chars() {
+        if character == '.' || character == '-' {
+            flush(&mut current);
+            continue;
+        }
+        // Transitions between digits and letters separate items, as in 1.0rc1
+        if current.chars().last().is_some_and(|last| last.is_ascii_digit() != character.is_ascii_digit()) {
+            flush(&mut current);
+        }
+        current.push(character);
+    }
+    flush(&mut current);
+    items
+}
+
+// An approximation of the ordering of Maven versions
+pub fn compare_versions(first: &str, second: &str) -> Ordering {
+    let first = parse_version(first);
+    let second = parse_version(second);
+    for index in 0..first.len().max(second.len()) {
+        let ordering = match (first.get(index), second.get(index)) {
+            (Some(first), Some(second)) => first.compare(second),
+            (Some(first), None) => first.compare_to_missing(),
+            (None, Some(second)) => second.compare_to_missing().reverse(),
+            (None, None) => Ordering::Equal
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+// How copies of the same metadata from several repositories compare. Mirrors of one
+// repository should serve identical copies, so any difference suggests a stale or truncated mirror
+#[derive(Debug, PartialEq, Eq)]
+pub struct Consensus {
+    // The position of the copy served by the most repositories, or of the largest on a tie
+    pub chosen: usize,
+    // The positions of copies which differ from the chosen one
+    pub divergent: Vec<usize>
+}
+
+impl Consensus {
+    pub fn find(copies: &[&[u8]]) -> Option<Self> {
+        let mut identical: HashMap<String, Vec<usize>> = HashMap::new();
+        for (position, copy) in copies.iter().enumerate() {
+            identical.entry(ChecksumAlgorithm::Sha1.hex_digest(copy)).or_default().push(position);
+        }
+        // Ties in both count and size go to the earliest copy
+        let agreed = identical.values()
+            .max_by(|first, second| {
+                first.len().cmp(&second.len())
+                    .then(copies[first[0]].len().cmp(&copies[second[0]].len()))
+                    .then(second[0].cmp(&first[0]))
+            })?;
+        let chosen = agreed[0];
+        let divergent = (0..copies.len()).filter(|position| !agreed.contains(position)).collect();
+        Some(Self {
+            chosen,
+            divergent
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn artifact_metadata(versions: &[&str], last_updated: &str) -> String {
+        let versions: String = versions
+            .iter()
+            .map(|version| format!("<version>{}</version>", version))
+            .collect();
+        format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+            <metadata modelVersion="1.1.0">
+              <groupId>org.example</groupId>
+              <artifactId>example</artifactId>
+              <versioning>
+                <versions>{}</versions>
+                <lastUpdated>{}</lastUpdated>
+              </versioning>
+            </metadata>"#, versions, last_updated)
+    }
+
+    pub fn snapshot_metadata(timestamp: &str, build_number: u32) -> String {
+        format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+            <metadata modelVersion="1.1.0">
+              <groupId>org.example</groupId>
+              <artifactId>example</artifactId>
+              <version>1.0-SNAPSHOT</version>
+              <versioning>
+                <snapshot>
+                  <timestamp>{}</timestamp>
+                  <buildNumber>{}</buildNumber>
+                </snapshot>
+                <lastUpdated>{}</lastUpdated>
+              </versioning>
+            </metadata>"#, timestamp, build_number, timestamp.replace('.', ""))
+    }
+
+    #[test]
+    fn consensus_of_copies() {
+        let complete = artifact_metadata(&["1.0", "1.1"], "20211014000000");
+        let truncated = artifact_metadata(&["1.0"], "20211014000000");
+        let find = |copies: &[&String]| {
+            Consensus::find(&copies.iter().map(|copy| copy.as_bytes()).collect::<Vec<_>>())
+        };
+        assert_eq!(None, find(&[]));
+        assert_eq!(Some(Consensus { chosen: 0, divergent: vec![] }), find(&[&complete, &complete]));
+        // The majority wins even when smaller
+        assert_eq!(Some(Consensus { chosen: 1, divergent: vec![0] }), find(&[&complete, &truncated, &truncated]));
+        // Otherwise the largest copy is chosen
+        assert_eq!(Some(Consensus { chosen: 1, divergent: vec![0] }), find(&[&truncated, &complete]));
+    }
+
+    fn merge_documents(documents: &[String]) -> Result<Metadata> {
+        let documents = documents
+            .iter()
+            .map(|document| Metadata::parse(document.as_bytes()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Metadata::merge(documents))
+    }
+
+    #[test]
+    fn metadata_paths() {
+        assert!(is_metadata_path("/org/example/example/maven-metadata.xml"));
+        assert!(!is_metadata_path("/org/example/example/maven-metadata.xml.sha1"));
+        assert!(!is_metadata_path("/org/example/example/1.0/example-1.0.pom"));
+    }
+
+    #[test]
+    fn artifact_metadata_paths() {
+        assert_eq!(Some(("/org/example/example/maven-metadata.xml".to_owned(), "1.0")),
+                   artifact_metadata_path("/org/example/example/1.0/example-1.0.jar"));
+        assert_eq!(None, artifact_metadata_path("/org/example/example/maven-metadata.xml"));
+        assert_eq!(None, artifact_metadata_path("/example.jar"));
+    }
+
+    #[test]
+    fn changing_paths() {
+        assert!(is_changing("/org/example/example/maven-metadata.xml"));
+        assert!(is_changing("/org/example/example/maven-metadata.xml.sha1"));
+        assert!(is_changing("/org/example/example/1.0-SNAPSHOT/example-1.0-20210101.120000-3.jar"));
+        assert!(!is_changing("/org/example/example/1.0/example-1.0.pom"));
+        assert!(!is_changing("/org/example/example/1.0/example-1.0.pom.sha1"));
+    }
+
+    #[test]
+    fn parse_artifact_metadata() -> Result<()> {
+        let metadata = Metadata::parse(artifact_metadata(&["1.0", "1.1"], "20210101000000").as_bytes())?;
+        assert_eq!(Some("org.example"), metadata.group_id.as_deref());
+        assert_eq!(Some("example"), metadata.artifact_id.as_deref());
+        assert_eq!(vec!["1.0", "1.1"], metadata.versions);
+        assert_eq!(Some("20210101000000"), metadata.last_updated.as_deref());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_overlapping_versions() -> Result<()> {
+        let merged = merge_documents(&[
+            artifact_metadata(&["1.0", "1.1", "1.2-SNAPSHOT"], "20210101000000"),
+            artifact_metadata(&["1.1", "1.2-SNAPSHOT", "1.2"], "20210202000000")
+        ])?;
+        assert_eq!(vec!["1.0", "1.1", "1.2-SNAPSHOT", "1.2"], merged.versions);
+        assert_eq!(Some("1.2"), merged.latest.as_deref());
+        assert_eq!(Some("1.2"), merged.release.as_deref());
+        assert_eq!(Some("20210202000000"), merged.last_updated.as_deref());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_disjoint_versions() -> Result<()> {
+        let merged = merge_documents(&[
+            artifact_metadata(&["2.0", "2.1-SNAPSHOT"], "20210303000000"),
+            artifact_metadata(&["1.0", "1.5"], "20210101000000")
+        ])?;
+        assert_eq!(vec!["1.0", "1.5", "2.0", "2.1-SNAPSHOT"], merged.versions);
+        assert_eq!(Some("2.1-SNAPSHOT"), merged.latest.as_deref());
+        assert_eq!(Some("2.0"), merged.release.as_deref());
+        Ok(())
+    }
+
+    #[test]
+    fn merged_xml_round_trip() -> Result<()> {
+        let merged = merge_documents(&[
+            artifact_metadata(&["1.0"], "20210101000000"),
+            artifact_metadata(&["1.1"], "20210101000000")
+        ])?;
+        assert_eq!(merged, Metadata::parse(merged.to_xml().as_bytes())?);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unsupported_metadata() {
+        let snapshot_metadata = r#"<metadata>
+            <groupId>org.example</groupId>
+            <artifactId>example</artifactId>
+            <version>1.0-SNAPSHOT</version>
+            <versioning><snapshot><timestamp>20210101.000000</timestamp></snapshot></versioning>
+            </metadata>"#;
+        Metadata::parse(snapshot_metadata.as_bytes()).expect_err("Snapshot metadata");
+        Metadata::parse(b"<metadata><groupId>unclosed").expect_err("Malformed metadata");
+        Metadata::parse(b"not xml at all").expect_err("Not metadata");
+    }
+
+    #[test]
+    fn snapshot_metadata_paths() {
+        assert_eq!(Some("/org/example/example/1.0-SNAPSHOT/maven-metadata.xml".to_owned()),
+                   snapshot_metadata_path("/org/example/example/1.0-SNAPSHOT/example-1.0-SNAPSHOT.jar"));
+        assert_eq!(Some("/org/example/example/1.0-SNAPSHOT/maven-metadata.xml".to_owned()),
+                   snapshot_metadata_path("/org/example/example/1.0-SNAPSHOT/example-1.0-20210101.000000-1.pom"));
+        assert_eq!(None, snapshot_metadata_path("/org/example/example/1.0-SNAPSHOT/maven-metadata.xml"));
+        assert_eq!(None, snapshot_metadata_path("/org/example/example/1.0/example-1.0.jar"));
+        assert_eq!(None, snapshot_metadata_path("/org/example/example/1.0-SNAPSHOT/"));
+    }
+
+    #[test]
+    fn parse_snapshot_builds() -> Result<()> {
+        let older = SnapshotBuild::parse(snapshot_metadata("20210101.000000", 3).as_bytes())?;
+        let newer = SnapshotBuild::parse(snapshot_metadata("20210202.000000", 1).as_bytes())?;
+        let rebuilt = SnapshotBuild::parse(snapshot_metadata("20210202.000000", 2).as_bytes())?;
+        assert!(older < newer);
+        assert!(newer < rebuilt);
+        SnapshotBuild::parse(artifact_metadata(&["1.0"], "20210101000000").as_bytes())
+            .expect_err("Artifact metadata");
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_snapshot_placeholders() -> Result<()> {
+        let build = SnapshotBuild::parse(snapshot_metadata("20210101.120000", 3).as_bytes())?;
+        assert_eq!("1.0-20210101.120000-3", build.unique_version("1.0-SNAPSHOT"));
+        let directory = "/org/example/example/1.0-SNAPSHOT";
+        assert!(is_snapshot_placeholder(&format!("{}/example-1.0-SNAPSHOT.jar", directory)));
+        assert_eq!(Some(format!("{}/example-1.0-20210101.120000-3.jar", directory)),
+                   build.resolve_path(&format!("{}/example-1.0-SNAPSHOT.jar", directory)));
+        assert_eq!(Some(format!("{}/example-1.0-20210101.120000-3-sources.jar.sha1", directory)),
+                   build.resolve_path(&format!("{}/example-1.0-SNAPSHOT-sources.jar.sha1", directory)));
+        // Files already uniquely versioned, and metadata, are left alone
+        for path in ["example-1.0-20210101.000000-1.jar", "maven-metadata.xml", "other-1.0-SNAPSHOT.jar"] {
+            assert!(!is_snapshot_placeholder(&format!("{}/{}", directory, path)), "{}", path);
+        }
+        assert!(!is_snapshot_placeholder("/org/example/example/1.0/example-1.0.jar"));
+        Ok(())
+    }
+
+    #[test]
+    fn version_ordering() {
+        let ascending = ["1.0-alpha-1", "1.0-beta", "1.0-rc1", "1.0-SNAPSHOT", "1.0", "1.0.1", "1.1", "1.10", "2.0"];
+        for window in ascending.windows(2) {
+            assert_eq!(Ordering::Less, compare_versions(window[0], window[1]),
+                       "{} should precede {}", window[0], window[1]);
+        }
+        assert_eq!(Ordering::Equal, compare_versions("1.0", "1.0.0"));
+    }
+}
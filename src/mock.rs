@@ -0,0 +1,209 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+// Mock upstream repositories for tests which drive requests through the application
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use socket2::{Domain, Socket, Type};
+use hyper::{Body, HeaderMap, Method, Request, Response, Server, StatusCode, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use eyre::Result;
+use crate::repository::Repository;
+use crate::telemetry::{SpanData, SpanExporter};
+
+// Starts a mock upstream repository on an ephemeral port, returning its base URI
+pub async fn mock_upstream<F>(handler: F) -> Result<Uri>
+    where F: Fn(Request<Body>) -> Response<Body> + Send + Sync + 'static {
+
+    mock_upstream_async(move |request| {
+        let response = handler(request);
+        async move { response }
+    }).await
+}
+
+pub async fn mock_upstream_async<F, Fut>(handler: F) -> Result<Uri>
+    where F: Fn(Request<Body>) -> Fut + Send + Sync + 'static,
+          Fut: Future<Output=Response<Body>> + Send + 'static {
+
+    let handler = Arc::new(handler);
+    let service_function = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response = handler(request);
+                async move { Ok::<_, Infallible>(response.await) }
+            }))
+        }
+    });
+    let socket = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let server = Server::try_bind(&socket)?.serve(service_function);
+    let address = server.local_addr();
+    tokio::spawn(server);
+    Ok(Uri::from_str(&format!("http://{}/maven2", address))?)
+}
+
+// A listener which never accepts connections and whose queue of pending connections is full,
+// so that further connections are neither accepted nor refused and connecting times out
+pub struct UnacceptingListener {
+    address: SocketAddr,
+    _listener: Socket,
+    _queued: Vec<std::net::TcpStream>
+}
+
+impl UnacceptingListener {
+    pub fn bind() -> Result<Self> {
+        let listener = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+        listener.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0).into())?;
+        listener.listen(0)?;
+        let address = listener.local_addr()?.as_socket().ok_or_else(|| eyre::eyre!("Not bound to an IP address"))?;
+        let mut queued = Vec::new();
+        // Once the queue is full, connection attempts are dropped rather than queued
+        for _ in 0..8 {
+            match std::net::TcpStream::connect_timeout(&address, Duration::from_millis(100)) {
+                Ok(stream) => queued.push(stream),
+                Err(_) => return Ok(Self { address, _listener: listener, _queued: queued })
+            }
+        }
+        Err(eyre::eyre!("The queue of pending connections never filled"))
+    }
+
+    pub fn uri(&self) -> Uri {
+        Uri::from_str(&format!("http://{}/maven2", self.address)).unwrap()
+    }
+}
+
+// A request received by a MockRepository
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: Method,
+    // Relative to the repository's base path
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap
+}
+
+// Serves artifacts from a fixed list of paths, recording every request it receives
+pub struct MockRepository {
+    uri: Uri,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>
+}
+
+impl MockRepository {
+    // Paths are relative to the repository, such as /org/example/1.0/example-1.0.jar
+    pub async fn serving(artifacts: &[(&str, &str)]) -> Result<Self> {
+        let artifacts: Vec<(String, String)> = artifacts.iter()
+            .map(|(path, body)| (path.to_string(), body.to_string()))
+            .collect();
+        Self::start(move |path| {
+            match artifacts.iter().find(|(artifact_path, _)| artifact_path == path) {
+                Some((_, body)) => Response::new(Body::from(body.clone())),
+                None => status_response(StatusCode::NOT_FOUND)
+            }
+        }).await
+    }
+
+    pub async fn responding_with(status: StatusCode) -> Result<Self> {
+        Self::start(move |_| status_response(status)).await
+    }
+
+    pub async fn start<F>(handler: F) -> Result<Self>
+        where F: Fn(&str) -> Response<Body> + Send + Sync + 'static {
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = received.clone();
+        let uri = mock_upstream(move |request| {
+            let path = request.uri().path().strip_prefix("/maven2").unwrap_or_default().to_owned();
+            let response = handler(&path);
+            recorder.lock().unwrap().push(ReceivedRequest {
+                method: request.method().clone(),
+                path,
+                query: request.uri().query().map(str::to_owned),
+                headers: request.headers().clone()
+            });
+            response
+        }).await?;
+        Ok(Self {
+            uri,
+            received
+        })
+    }
+
+    pub fn repository(&self) -> Repository {
+        Repository::new(self.uri.clone())
+    }
+
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+
+    pub fn received_paths(&self) -> Vec<String> {
+        self.received().into_iter().map(|request| request.path).collect()
+    }
+}
+
+// Keeps the message of every record logged to it, for use with a scoped Logger
+#[derive(Debug, Clone, Default)]
+pub struct RecordingLogger {
+    messages: Arc<Mutex<Vec<String>>>
+}
+
+impl RecordingLogger {
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+impl log::Log for RecordingLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.messages.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder().status(status).body(Body::empty()).unwrap()
+}
+
+// Keeps every exported span, in the order the spans ended
+#[derive(Debug, Default)]
+pub struct RecordingExporter {
+    spans: Mutex<Vec<SpanData>>
+}
+
+impl RecordingExporter {
+    pub fn spans(&self) -> Vec<SpanData> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl SpanExporter for RecordingExporter {
+    fn export(&self, span: SpanData) {
+        self.spans.lock().unwrap().push(span);
+    }
+}
@@ -0,0 +1,149 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CAPACITY: usize = 10_000;
+
+// Remembers paths which recently could not be found in any repository
+#[derive(Debug)]
+pub struct NegativeCache {
+    ttl: Duration,
+    expiries: Mutex<HashMap<String, Instant>>
+}
+
+impl NegativeCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            expiries: Mutex::new(HashMap::new())
+        }
+    }
+
+    // SNAPSHOT artifacts may be published at any moment, so they are never cached
+    fn ttl_for(&self, path: &str) -> Duration {
+        if path.contains("-SNAPSHOT") {
+            Duration::ZERO
+        } else {
+            self.ttl
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.contains_at(path, Instant::now())
+    }
+
+    fn contains_at(&self, path: &str, now: Instant) -> bool {
+        let mut expiries = self.expiries.lock().unwrap();
+        match expiries.get(path) {
+            Some(&expiry) if expiry > now => true,
+            Some(_) => {
+                expiries.remove(path);
+                false
+            },
+            None => false
+        }
+    }
+
+    pub fn insert(&self, path: &str) {
+        self.insert_at(path, Instant::now())
+    }
+
+    fn insert_at(&self, path: &str, now: Instant) {
+        let ttl = self.ttl_for(path);
+        if ttl.is_zero() {
+            return;
+        }
+        let mut expiries = self.expiries.lock().unwrap();
+        if expiries.len() >= CAPACITY && !expiries.contains_key(path) {
+            expiries.retain(|_, expiry| *expiry > now);
+            // Evict the entry closest to expiring if the cache is still full
+            let oldest = expiries.iter()
+                .min_by_key(|(_, expiry)| **expiry)
+                .map(|(oldest, _)| oldest.clone());
+            if expiries.len() >= CAPACITY {
+                if let Some(oldest) = oldest {
+                    expiries.remove(&oldest);
+                }
+            }
+        }
+        expiries.insert(path.to_owned(), now + ttl);
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.expiries.lock().unwrap().remove(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/org/example/1.0/example-1.0-sources.jar";
+
+    #[test]
+    fn cached_until_expiry() {
+        let cache = NegativeCache::new(Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(!cache.contains_at(PATH, now));
+        cache.insert_at(PATH, now);
+        assert!(cache.contains_at(PATH, now + Duration::from_secs(29)));
+        assert!(!cache.contains_at(PATH, now + Duration::from_secs(30)));
+        assert!(!cache.contains_at(PATH, now));
+    }
+
+    #[test]
+    fn snapshots_not_cached() {
+        let cache = NegativeCache::new(Duration::from_secs(30));
+        let path = "/org/example/1.0-SNAPSHOT/example-1.0-SNAPSHOT.jar";
+        cache.insert(path);
+        assert!(!cache.contains(path));
+    }
+
+    #[test]
+    fn disabled_with_zero_ttl() {
+        let cache = NegativeCache::new(Duration::ZERO);
+        cache.insert(PATH);
+        assert!(!cache.contains(PATH));
+    }
+
+    #[test]
+    fn removal() {
+        let cache = NegativeCache::new(Duration::from_secs(30));
+        cache.insert(PATH);
+        cache.remove(PATH);
+        assert!(!cache.contains(PATH));
+    }
+
+    #[test]
+    fn capacity_evicts_oldest() {
+        let cache = NegativeCache::new(Duration::from_secs(30));
+        let now = Instant::now();
+        for n in 0..CAPACITY {
+            cache.insert_at(&format!("/{}", n), now + Duration::from_millis(n as u64));
+        }
+        cache.insert_at("/new", now + Duration::from_secs(1));
+        assert_eq!(CAPACITY, cache.expiries.lock().unwrap().len());
+        assert!(!cache.contains_at("/0", now));
+        assert!(cache.contains_at("/1", now));
+        assert!(cache.contains_at("/new", now));
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use hyper::{Body, HeaderMap, Response, StatusCode, http};
+use hyper::body::Bytes;
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use hyper::http::response;
+use eyre::{Result, WrapErr};
+
+// A favicon loaded from disk at startup
+#[derive(Debug, Clone)]
+pub struct Favicon {
+    data: Bytes,
+    content_type: &'static str
+}
+
+impl Favicon {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .wrap_err_with(|| format!("Unable to read favicon {}", path.display()))?;
+        let content_type = match extension(path).as_deref() {
+            Some("png") => "image/png",
+            Some("svg") => "image/svg+xml",
+            Some("gif") => "image/gif",
+            _ => "image/x-icon"
+        };
+        Ok(Self {
+            data: Bytes::from(data),
+            content_type
+        })
+    }
+
+    pub fn response(&self, version: http::version::Version) -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, self.content_type)
+            .body(Body::from(self.data.clone()))?)
+    }
+}
+
+// Crawlers are asked not to index anything, since each page they request may contact every repository
+pub fn default_static_responses() -> BTreeMap<String, String> {
+    BTreeMap::from([("/robots.txt".to_owned(), "User-agent: *\nDisallow: /\n".to_owned())])
+}
+
+// Fixed bodies served at exact paths, such as /robots.txt, without contacting any repository
+#[derive(Debug, Clone)]
+pub struct StaticResponses {
+    bodies: BTreeMap<String, Bytes>
+}
+
+impl Default for StaticResponses {
+    fn default() -> Self {
+        Self::new(default_static_responses())
+    }
+}
+
+impl StaticResponses {
+    pub fn new(bodies: BTreeMap<String, String>) -> Self {
+        Self {
+            bodies: bodies.into_iter().map(|(path, body)| (path, Bytes::from(body))).collect()
+        }
+    }
+
+    pub fn response(&self, path: &str, version: http::version::Version) -> Option<Result<Response<Body>>> {
+        let body = self.bodies.get(path)?;
+        let content_type = match extension(Path::new(path)).as_deref() {
+            Some("html" | "htm") => "text/html; charset=utf-8",
+            Some("json") => "application/json",
+            Some("xml") => "application/xml",
+            _ => "text/plain; charset=utf-8"
+        };
+        Some(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body.clone()))
+            .map_err(Into::into))
+    }
+}
+
+// A template for error response bodies. The placeholders {{status}}, {{reason}}
+// and {{message}} are replaced with the details of each error
+#[derive(Debug, Clone)]
+pub struct ErrorPageTemplate {
+    template: String,
+    html: bool
+}
+
+impl ErrorPageTemplate {
+    pub fn load(path: &Path) -> Result<Self> {
+        let template = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Unable to read error page template {}", path.display()))?;
+        let html = matches!(extension(path).as_deref(), Some("html" | "htm"));
+        Ok(Self::new(template, html))
+    }
+
+    fn new(template: String, html: bool) -> Self {
+        Self {
+            template,
+            html
+        }
+    }
+
+    fn render(&self, status: StatusCode, message: &str) -> String {
+        let escape = |text: &str| if self.html { html_escape(text) } else { text.to_owned() };
+        self.template
+            .replace("{{status}}", status.as_str())
+            .replace("{{reason}}", &escape(status.canonical_reason().unwrap_or_default()))
+            .replace("{{message}}", &escape(message))
+    }
+
+    fn content_type(&self) -> &'static str {
+        if self.html {
+            "text/html; charset=utf-8"
+        } else {
+            "text/plain; charset=utf-8"
+        }
+    }
+}
+
+// The kind of error response body preferred by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Text,
+    Html,
+    Json
+}
+
+impl ErrorFormat {
+    // Chooses the supported media type with the highest quality in the Accept header,
+    // or plain text if none of them are acceptable
+    pub fn from_accept(headers: &HeaderMap) -> Self {
+        let mut preferred = (ErrorFormat::Text, 0.0);
+        let entries = headers.get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for entry in entries {
+            let mut params = entry.split(';');
+            let media_type = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let format = match media_type.as_str() {
+                "text/plain" | "text/*" | "*/*" => ErrorFormat::Text,
+                "text/html" | "application/xhtml+xml" => ErrorFormat::Html,
+                "application/json" | "application/problem+json" => ErrorFormat::Json,
+                _ => continue
+            };
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .filter_map(|quality| quality.trim().parse::<f32>().ok())
+                .next()
+                .unwrap_or(1.0);
+            if quality > preferred.1 {
+                preferred = (format, quality);
+            }
+        }
+        preferred.0
+    }
+}
+
+// Completes an error response in the client's preferred format. A configured template
+// is used for all but JSON responses, which follow the RFC 7807 problem details format
+pub fn error_response(builder: response::Builder,
+                      template: Option<&ErrorPageTemplate>,
+                      format: ErrorFormat,
+                      status: StatusCode,
+                      message: &str) -> Result<Response<Body>> {
+    let builder = builder.status(status);
+    let reason = status.canonical_reason().unwrap_or_default();
+    let (content_type, body) = match (format, template) {
+        (ErrorFormat::Json, _) => ("application/problem+json", serde_json::json!({
+            "status": status.as_u16(),
+            "title": reason,
+            "detail": message
+        }).to_string()),
+        (_, Some(template)) => (template.content_type(), template.render(status, message)),
+        (ErrorFormat::Html, None) => ("text/html; charset=utf-8", format!(
+            "<!DOCTYPE html>\n<html><head><title>{0} {1}</title></head><body><h1>{0} {1}</h1><p>{2}</p></body></html>\n",
+            status.as_str(), html_escape(reason), html_escape(message))),
+        (ErrorFormat::Text, None) => ("text/plain; charset=utf-8", message.to_owned())
+    };
+    Ok(builder
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))?)
+}
+
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            other => escaped.push(other)
+        }
+    }
+    escaped
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::Builder;
+
+    #[test]
+    fn escape_html() {
+        assert_eq!("a &amp; &lt;b&gt; &quot;c&quot; &#39;d&#39;", html_escape("a & <b> \"c\" 'd'"));
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, hyper::header::HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn negotiate_error_format() {
+        assert_eq!(ErrorFormat::Text, ErrorFormat::from_accept(&HeaderMap::new()));
+        assert_eq!(ErrorFormat::Text, ErrorFormat::from_accept(&accept("*/*")));
+        assert_eq!(ErrorFormat::Text, ErrorFormat::from_accept(&accept("image/png")));
+        assert_eq!(ErrorFormat::Html, ErrorFormat::from_accept(
+            &accept("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")));
+        assert_eq!(ErrorFormat::Json, ErrorFormat::from_accept(&accept("application/json")));
+        assert_eq!(ErrorFormat::Json, ErrorFormat::from_accept(&accept("text/html;q=0.5, application/json")));
+        assert_eq!(ErrorFormat::Json, ErrorFormat::from_accept(&accept("application/problem+json")));
+        assert_eq!(ErrorFormat::Text, ErrorFormat::from_accept(&accept("application/json;q=0, text/plain")));
+    }
+
+    #[test]
+    fn render_template() {
+        let template = ErrorPageTemplate::new(
+            "<h1>{{status}} {{reason}}</h1><p>{{message}}</p>".to_owned(), true);
+        assert_eq!("<h1>404 Not Found</h1><p>Missing &lt;artifact&gt;</p>",
+                   template.render(StatusCode::NOT_FOUND, "Missing <artifact>"));
+        let template = ErrorPageTemplate::new("{{status}}: {{message}}".to_owned(), false);
+        assert_eq!("502: <raw>", template.render(StatusCode::BAD_GATEWAY, "<raw>"));
+    }
+
+    #[test]
+    fn load_files() -> Result<()> {
+        let mut favicon_file = Builder::new().suffix(".png").tempfile()?;
+        favicon_file.write_all(b"\x89PNG")?;
+        let favicon = Favicon::load(favicon_file.path())?;
+        assert_eq!("image/png", favicon.content_type);
+
+        let mut template_file = Builder::new().suffix(".html").tempfile()?;
+        template_file.write_all(b"{{message}}")?;
+        assert!(ErrorPageTemplate::load(template_file.path())?.html);
+
+        Favicon::load(Path::new("/nonexistent/favicon.ico")).expect_err("Missing favicon");
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::sync::Mutex;
+use std::time::Duration;
+// Follows the paused clock in tests, and is otherwise the same as the standard library's
+use tokio::time::Instant;
+
+// A token bucket allowing bursts of up to one second's worth of requests
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: u32,
+    state: Mutex<BucketState>
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        Self::new_at(per_second, Instant::now())
+    }
+
+    fn new_at(per_second: u32, now: Instant) -> Self {
+        Self {
+            per_second,
+            state: Mutex::new(BucketState {
+                tokens: f64::from(per_second),
+                last_refill: now
+            })
+        }
+    }
+
+    // Takes a token, or returns how long until one becomes available
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        if self.per_second == 0 {
+            return Err(Duration::from_secs(1));
+        }
+        let rate = f64::from(self.per_second);
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(rate);
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_exceeding_limit() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new_at(5, now);
+        for _ in 0..5 {
+            assert_eq!(Ok(()), limiter.try_acquire_at(now));
+        }
+        let wait = limiter.try_acquire_at(now).expect_err("Bucket is empty");
+        assert_eq!(Duration::from_millis(200), wait);
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new_at(2, now);
+        assert_eq!(Ok(()), limiter.try_acquire_at(now));
+        assert_eq!(Ok(()), limiter.try_acquire_at(now));
+        assert!(limiter.try_acquire_at(now).is_err());
+        let later = now + Duration::from_millis(500);
+        assert_eq!(Ok(()), limiter.try_acquire_at(later));
+        assert!(limiter.try_acquire_at(later).is_err());
+        // Tokens do not accumulate beyond the limit
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!(Ok(()), limiter.try_acquire_at(much_later));
+        assert_eq!(Ok(()), limiter.try_acquire_at(much_later));
+        assert!(limiter.try_acquire_at(much_later).is_err());
+    }
+}
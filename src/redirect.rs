@@ -0,0 +1,223 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use hyper::{Body, Response, StatusCode, Uri};
+use hyper::header::LOCATION;
+use url::Url;
+
+const CACHE_CAPACITY: usize = 10_000;
+
+// Which upstream redirects are followed, instead of treating them as failures
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    max_redirects: u32,
+    follow_cross_host: bool
+}
+
+impl RedirectPolicy {
+    pub fn new(max_redirects: u32, follow_cross_host: bool) -> Self {
+        Self {
+            max_redirects,
+            follow_cross_host
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::new(0, false)
+    }
+
+    // The URI to request next, given the URIs already requested with the last one
+    // answering with the response. None if the response should be used as it is
+    pub fn next(&self, requested: &[Uri], response: &Response<Body>) -> Option<Uri> {
+        if !is_redirect(response.status()) {
+            return None;
+        }
+        let current = requested.last()?;
+        let location = response.headers().get(LOCATION)?.to_str().ok()?;
+        let target = match resolve(current, location) {
+            Some(target) => target,
+            None => {
+                log::warn!("Ignoring invalid redirect from {} to {:?}", current, location);
+                return None;
+            }
+        };
+        if requested.len() > self.max_redirects as usize {
+            log::warn!("Not following redirect from {} after {} redirects", current, self.max_redirects);
+            return None;
+        }
+        if requested.contains(&target) {
+            log::warn!("Redirect loop from {} to {}", current, target);
+            return None;
+        }
+        if !self.follow_cross_host && target.authority() != current.authority() {
+            log::warn!("Not following cross host redirect from {} to {}", current, target);
+            return None;
+        }
+        log::trace!("Following redirect from {} to {}", current, target);
+        Some(target)
+    }
+}
+
+// Remembers where permanent redirects led, so that later requests for the same upstream URI
+// go straight to the target. Chains of permanent redirects are collapsed into their final target
+#[derive(Debug)]
+pub struct RedirectCache {
+    ttl: Duration,
+    targets: Mutex<HashMap<Uri, (Uri, Instant)>>
+}
+
+impl RedirectCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            targets: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn get(&self, uri: &Uri) -> Option<Uri> {
+        self.get_at(uri, Instant::now())
+    }
+
+    fn get_at(&self, uri: &Uri, now: Instant) -> Option<Uri> {
+        let mut targets = self.targets.lock().unwrap();
+        match targets.get(uri) {
+            Some((target, expiry)) if *expiry > now => Some(target.clone()),
+            Some(_) => {
+                targets.remove(uri);
+                None
+            },
+            None => None
+        }
+    }
+
+    // Remembers the redirects followed from the first URI requested to the last, if all of
+    // them were permanent
+    pub fn insert(&self, requested: &[Uri]) {
+        self.insert_at(requested, Instant::now())
+    }
+
+    fn insert_at(&self, requested: &[Uri], now: Instant) {
+        let (first, last) = match requested {
+            [first, .., last] => (first, last),
+            _ => return
+        };
+        let mut targets = self.targets.lock().unwrap();
+        if targets.len() >= CACHE_CAPACITY && !targets.contains_key(first) {
+            targets.retain(|_, (_, expiry)| *expiry > now);
+            if targets.len() >= CACHE_CAPACITY {
+                log::debug!("Not caching the redirect from {}, the redirect cache is full", first);
+                return;
+            }
+        }
+        targets.insert(first.clone(), (last.clone(), now + self.ttl));
+    }
+
+    // Forgets a target which no longer serves the artifact
+    pub fn remove(&self, uri: &Uri) {
+        self.targets.lock().unwrap().remove(uri);
+    }
+
+    pub fn clear(&self) {
+        self.targets.lock().unwrap().clear();
+    }
+}
+
+pub fn is_permanent_redirect(status: StatusCode) -> bool {
+    matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT)
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(status,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER
+        | StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT)
+}
+
+// Locations may be relative to the redirecting URI
+fn resolve(current: &Uri, location: &str) -> Option<Uri> {
+    let target = Url::parse(&current.to_string()).ok()?.join(location).ok()?;
+    if !matches!(target.scheme(), "http" | "https") {
+        return None;
+    }
+    Uri::from_str(target.as_str()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(location: &str) -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::FOUND)
+            .header(LOCATION, location)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn resolve_locations() {
+        let current = Uri::from_static("https://repo.example.com/maven2/org/example/example.jar");
+        let policy = RedirectPolicy::new(5, true);
+        assert_eq!(Some(Uri::from_static("https://repo.example.com/maven2/org/example/moved.jar")),
+                   policy.next(&[current.clone()], &redirect("moved.jar")));
+        assert_eq!(Some(Uri::from_static("https://repo.example.com/cdn/example.jar")),
+                   policy.next(&[current.clone()], &redirect("/cdn/example.jar")));
+        assert_eq!(Some(Uri::from_static("https://cdn.example.com/example.jar")),
+                   policy.next(&[current.clone()], &redirect("https://cdn.example.com/example.jar")));
+        assert_eq!(None, policy.next(&[current.clone()], &redirect("ftp://cdn.example.com/example.jar")));
+        assert_eq!(None, policy.next(&[current], &Response::new(Body::empty())));
+    }
+
+    #[test]
+    fn redirect_limits() {
+        let first = Uri::from_static("https://repo.example.com/a");
+        let second = Uri::from_static("https://repo.example.com/b");
+        let policy = RedirectPolicy::new(1, false);
+        assert_eq!(Some(second.clone()), policy.next(&[first.clone()], &redirect("/b")));
+        // Too many redirects
+        assert_eq!(None, policy.next(&[first.clone(), second.clone()], &redirect("/c")));
+        // Loops are never followed
+        let policy = RedirectPolicy::new(5, false);
+        assert_eq!(None, policy.next(&[first.clone(), second.clone()], &redirect("/a")));
+        assert_eq!(None, policy.next(&[first.clone()], &redirect("https://cdn.example.com/a")));
+        assert_eq!(None, RedirectPolicy::none().next(&[first], &redirect("/b")));
+    }
+
+    #[test]
+    fn cached_redirects() {
+        let first = Uri::from_static("https://repo.example.com/a");
+        let second = Uri::from_static("https://repo.example.com/b");
+        let third = Uri::from_static("https://cdn.example.com/c");
+        let cache = RedirectCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert_at(&[first.clone()], now);
+        assert_eq!(None, cache.get_at(&first, now));
+        cache.insert_at(&[first.clone(), second.clone(), third.clone()], now);
+        assert_eq!(Some(third.clone()), cache.get_at(&first, now + Duration::from_secs(59)));
+        assert_eq!(None, cache.get_at(&second, now));
+        assert_eq!(None, cache.get_at(&first, now + Duration::from_secs(60)));
+
+        cache.insert_at(&[first.clone(), third], now);
+        cache.remove(&first);
+        assert_eq!(None, cache.get_at(&first, now));
+    }
+}
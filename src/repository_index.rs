@@ -0,0 +1,127 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+// The paths a repository has, as listed in a file published by its mirror, one path per line.
+// Blank lines and lines starting with # are ignored. Requests for paths not listed are never
+// sent to the repository, so the index must also list metadata files the repository serves
+pub struct RepositoryIndex {
+    path: PathBuf,
+    paths: RwLock<Arc<HashSet<String>>>
+}
+
+impl RepositoryIndex {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_owned(),
+            paths: RwLock::new(Arc::new(read_paths(path)?))
+        })
+    }
+
+    // Replaces the listed paths with those currently in the file. The previous paths are
+    // kept if the file cannot be read
+    pub fn reload(&self) -> io::Result<usize> {
+        let paths = read_paths(&self.path)?;
+        let count = paths.len();
+        *self.paths.write().unwrap() = Arc::new(paths);
+        Ok(count)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Checksums and signatures are listed if the file they belong to is, since indexes
+    // often leave them out
+    pub fn contains(&self, path: &str) -> bool {
+        let paths = self.paths.read().unwrap().clone();
+        let mut path = path;
+        loop {
+            if paths.contains(path) {
+                return true;
+            }
+            match path.rsplit_once('.') {
+                Some((base, "sha1" | "md5" | "sha256" | "sha512" | "asc")) => path = base,
+                _ => return false
+            }
+        }
+    }
+}
+
+// Indexes are compared by the file they are loaded from
+impl PartialEq for RepositoryIndex {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for RepositoryIndex {}
+
+// Indexes may list millions of paths, which are left out
+impl Debug for RepositoryIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RepositoryIndex")
+            .field("path", &self.path)
+            .field("paths", &self.paths.read().unwrap().len())
+            .finish()
+    }
+}
+
+// Paths are listed relative to the repository, with or without a leading slash
+fn read_paths(path: &Path) -> io::Result<HashSet<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| format!("/{}", line.trim_start_matches('/')))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_paths() -> eyre::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("index.txt");
+        std::fs::write(&path, "# Published nightly\n/org/example/1.0/example-1.0.jar\n\norg/example/1.0/example-1.0.pom\n")?;
+        let index = RepositoryIndex::load(&path)?;
+        assert!(index.contains("/org/example/1.0/example-1.0.jar"));
+        assert!(index.contains("/org/example/1.0/example-1.0.pom"));
+        assert!(index.contains("/org/example/1.0/example-1.0.jar.sha1"));
+        assert!(index.contains("/org/example/1.0/example-1.0.jar.asc.md5"));
+        assert!(!index.contains("/org/example/1.0/example-1.0-sources.jar"));
+        assert!(!index.contains("/org/example/1.0/example-1.0"));
+
+        std::fs::write(&path, "/org/example/2.0/example-2.0.jar\n")?;
+        assert_eq!(1, index.reload()?);
+        assert!(index.contains("/org/example/2.0/example-2.0.jar"));
+        assert!(!index.contains("/org/example/1.0/example-1.0.jar"));
+        std::fs::remove_file(&path)?;
+        assert!(index.reload().is_err());
+        assert!(index.contains("/org/example/2.0/example-2.0.jar"));
+        Ok(())
+    }
+}
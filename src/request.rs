@@ -17,16 +17,28 @@
  * and navigate to version 3 of the GNU Affero General Public License.
  */
 
-use hyper::{Method, Response, Body, http};
+use hyper::{HeaderMap, Method, Response, Body, StatusCode, http};
+use hyper::http::uri::PathAndQuery;
+use std::str::FromStr;
+use std::net::IpAddr;
 use eyre::Result;
-use crate::request::AllowedMethod::{GET, HEAD};
+use hyper::http::response;
+use crate::request::AllowedMethod::{GET, HEAD, PUT, OPTIONS};
+use crate::pages;
+use crate::pages::{ErrorFormat, ErrorPageTemplate};
+use crate::headers::X_PROXY_REPOSITORY;
 
-const ALL_ALLOWED: &[AllowedMethod] = &[GET, HEAD];
+const READ_ONLY: &[AllowedMethod] = &[GET, HEAD, OPTIONS];
+const WITH_PUBLISHING: &[AllowedMethod] = &[GET, HEAD, PUT, OPTIONS];
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AllowedMethod {
     GET,
-    HEAD
+    HEAD,
+    // Only enabled when a publish repository is configured
+    PUT,
+    // Answered by the proxy itself with the enabled methods
+    OPTIONS
 }
 
 impl From<&AllowedMethod> for Method {
@@ -34,7 +46,9 @@ impl From<&AllowedMethod> for Method {
     fn from(allowed_method: &AllowedMethod) -> Self {
         match allowed_method {
             GET => Method::GET,
-            HEAD => Method::HEAD
+            HEAD => Method::HEAD,
+            PUT => Method::PUT,
+            OPTIONS => Method::OPTIONS
         }
     }
 }
@@ -45,10 +59,20 @@ impl AllowedMethod {
         Some(match method {
             &Method::GET => GET,
             &Method::HEAD => HEAD,
+            &Method::PUT => PUT,
+            &Method::OPTIONS => OPTIONS,
             _ => return None
         })
     }
 
+    pub fn enabled(publishing: bool) -> &'static [Self] {
+        if publishing {
+            WITH_PUBLISHING
+        } else {
+            READ_ONLY
+        }
+    }
+
     fn value(&self) -> Box<str> {
         let method: Method = self.into();
         Box::from(method.as_str())
@@ -57,43 +81,379 @@ impl AllowedMethod {
 
 impl AllowedMethod {
 
-    pub fn respond_with_405(version: http::version::Version) -> Result<Response<Body>> {
+    fn with_allow_header(version: http::version::Version,
+                         enabled: &[AllowedMethod]) -> Result<response::Builder> {
         let mut response = Response::builder()
-            .version(version)
-            .status(405);
+            .version(version);
         {
             let headers = response.headers_mut().unwrap();
-            for allowed_method in ALL_ALLOWED {
+            for allowed_method in enabled {
                 let method: Method = allowed_method.into();
                 headers.append("Allow", method.as_str().parse()?);
             }
         }
-        let allowed_methods_display = ALL_ALLOWED
+        Ok(response)
+    }
+
+    pub fn respond_to_options(version: http::version::Version,
+                              enabled: &[AllowedMethod]) -> Result<Response<Body>> {
+        Ok(Self::with_allow_header(version, enabled)?
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::empty())?)
+    }
+
+    pub fn respond_with_405(version: http::version::Version,
+                            enabled: &[AllowedMethod],
+                            error_page: Option<&ErrorPageTemplate>,
+                            format: ErrorFormat) -> Result<Response<Body>> {
+        let response = Self::with_allow_header(version, enabled)?;
+        let allowed_methods_display = enabled
             .iter()
             .map(AllowedMethod::value)
             .collect::<Vec<Box<str>>>()
             .join(", ");
         let message = format!("Only {} requests are allowed to rust-maven-proxy.", allowed_methods_display);
-        Ok(response.body(Body::from(message))?)
+        pages::error_response(response, error_page, format, StatusCode::METHOD_NOT_ALLOWED, &message)
     }
 }
 
+// Attached to request extensions to record the address of the connecting client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddress {
+    ip: IpAddr,
+    // The connecting peer, which differs from the client behind a trusted reverse proxy
+    peer: IpAddr
+}
+
+impl ClientAddress {
+    pub fn new(ip: IpAddr) -> Self {
+        Self {
+            ip,
+            peer: ip
+        }
+    }
+
+    // The client as reported by the trusted proxy which connected on its behalf
+    pub fn forwarded_for(self, ip: IpAddr) -> Self {
+        Self {
+            ip,
+            ..self
+        }
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    pub fn peer(&self) -> IpAddr {
+        self.peer
+    }
+}
+
+// Removes the proxy's own base path from an incoming path, keeping the query.
+// Returns None if the path is outside of the prefix
+pub fn strip_path_prefix(prefix: &str, path_and_query: &PathAndQuery) -> Option<PathAndQuery> {
+    let prefix = prefix.trim_end_matches('/');
+    let remainder = path_and_query.path().strip_prefix(prefix)?;
+    let remainder = match remainder {
+        "" => "/",
+        remainder if remainder.starts_with('/') => remainder,
+        _ => return None
+    };
+    let stripped = match path_and_query.query() {
+        Some(query) => format!("{}?{}", remainder, query),
+        None => remainder.to_owned()
+    };
+    PathAndQuery::from_str(&stripped).ok()
+}
+
+// Collapses consecutive slashes in the path, keeping the query and any percent-encoded
+// sequences as they are. Returns None if the path has no repeated slashes
+pub fn collapse_slashes(path_and_query: &PathAndQuery) -> Option<PathAndQuery> {
+    let path = path_and_query.path();
+    if !path.contains("//") {
+        return None;
+    }
+    let mut collapsed = String::with_capacity(path.len());
+    for character in path.chars() {
+        if character != '/' || !collapsed.ends_with('/') {
+            collapsed.push(character);
+        }
+    }
+    if let Some(query) = path_and_query.query() {
+        collapsed.push('?');
+        collapsed.push_str(query);
+    }
+    PathAndQuery::from_str(&collapsed).ok()
+}
+
+// Classifies requested files by how long they may legitimately take to transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileSize {
+    // Metadata, POMs and checksums
+    Small,
+    // Archives such as jars
+    Large,
+    Unknown
+}
+
+impl FileSize {
+    pub fn classify(path: &str) -> Self {
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("xml" | "sha1" | "md5" | "sha256" | "sha512" | "asc" | "pom") => FileSize::Small,
+            Some("jar" | "zip" | "war" | "ear" | "aar") => FileSize::Large,
+            _ => FileSize::Unknown
+        }
+    }
+}
+
+// Rejects requests with more headers, or more bytes of header names and values, than allowed
+pub fn check_header_limits(headers: &HeaderMap,
+                           max_count: Option<usize>,
+                           max_bytes: Option<usize>) -> core::result::Result<(), &'static str> {
+    if max_count.is_some_and(|max_count| headers.len() > max_count) {
+        return Err("Too many request headers");
+    }
+    let size: usize = headers.iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if max_bytes.is_some_and(|max_bytes| size > max_bytes) {
+        return Err("Request headers are too large");
+    }
+    Ok(())
+}
+
+// The repository named by the X-Proxy-Repository header or the repo query parameter, if any
+pub fn repository_pin(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let header = headers.get(X_PROXY_REPOSITORY)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_owned());
+    header.or_else(|| {
+        url::form_urlencoded::parse(query?.as_bytes())
+            .find(|(key, _)| key == "repo")
+            .map(|(_, value)| value.into_owned())
+    })
+}
+
+// Rejects paths which could escape the repository base path or confuse upstreams
+pub fn validate_gav_path(path: &str) -> core::result::Result<(), &'static str> {
+    if path.contains("//") {
+        return Err("Path contains an empty segment");
+    }
+    if path.chars().any(char::is_control) {
+        return Err("Path contains control characters");
+    }
+    for segment in path.split('/') {
+        let decoded = segment.to_ascii_lowercase().replace("%2e", ".");
+        if decoded == ".." {
+            return Err("Path contains a parent directory segment");
+        }
+        if decoded.contains("%00") || decoded.contains("%2f") || decoded.contains("%5c") {
+            return Err("Path contains encoded separators or null bytes");
+        }
+    }
+    Ok(())
+}
+
+// Rejects paths which cannot name a file in the maven layout: metadata, or a file named after its
+// artifact and version within group, artifact and version directories. Directories, files at the
+// root and hidden files such as repository indexes are not judged
+pub fn validate_coordinates(path: &str) -> core::result::Result<(), &'static str> {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let (file, directories) = match segments.split_last() {
+        Some((file, directories)) if !file.is_empty() && !directories.is_empty() => (*file, directories),
+        _ => return Ok(())
+    };
+    if segments.iter().any(|segment| segment.starts_with('.')) {
+        return Ok(());
+    }
+    if file.starts_with("maven-metadata") || file.starts_with("archetype-catalog") {
+        return Ok(());
+    }
+    if !file.contains('.') {
+        return Err("The file name has no extension");
+    }
+    let (artifact, version) = match directories {
+        [_, .., artifact, version] => (*artifact, *version),
+        _ => return Err("The path lacks a group, artifact or version directory")
+    };
+    // Files in SNAPSHOT versions are named after their timestamped builds, as in 1.0-20210101.000000-1
+    let base_version = version.strip_suffix("SNAPSHOT").unwrap_or(version);
+    let named_after_version = file.strip_prefix(artifact)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .is_some_and(|rest| rest.starts_with(base_version));
+    if !named_after_version {
+        return Err("The file is not named after its artifact and version");
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn respond_with_405() -> Result<()> {
-        AllowedMethod::respond_with_405(http::version::Version::HTTP_2)?;
+        let response = AllowedMethod::respond_with_405(http::version::Version::HTTP_2, READ_ONLY, None, ErrorFormat::Text)?;
+        let allow: Vec<_> = response.headers().get_all("Allow").iter().collect();
+        assert_eq!(vec!["GET", "HEAD", "OPTIONS"], allow);
+        let response = AllowedMethod::respond_with_405(http::version::Version::HTTP_2, WITH_PUBLISHING, None, ErrorFormat::Text)?;
+        assert_eq!(4, response.headers().get_all("Allow").iter().count());
+        Ok(())
+    }
+
+    #[test]
+    fn respond_to_options() -> Result<()> {
+        let response = AllowedMethod::respond_to_options(http::version::Version::HTTP_11, READ_ONLY)?;
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        let allow: Vec<_> = response.headers().get_all("Allow").iter().collect();
+        assert_eq!(vec!["GET", "HEAD", "OPTIONS"], allow);
         Ok(())
     }
 
+    #[test]
+    fn put_requires_publishing() {
+        assert!(!AllowedMethod::enabled(false).contains(&PUT));
+        assert!(AllowedMethod::enabled(true).contains(&PUT));
+        assert!(AllowedMethod::enabled(false).contains(&OPTIONS));
+    }
+
     #[test]
     fn convert_methods() {
-        for method in &[Method::GET, Method::HEAD] {
+        for method in &[Method::GET, Method::HEAD, Method::PUT, Method::OPTIONS] {
             let allowed_method = AllowedMethod::find_from(method).unwrap();
             let back: Method = (&allowed_method).into();
             assert_eq!(method, back);
         }
     }
+
+    #[test]
+    fn classify_file_sizes() {
+        assert_eq!(FileSize::Small, FileSize::classify("/org/example/example/maven-metadata.xml"));
+        assert_eq!(FileSize::Small, FileSize::classify("/org/example/1.0/example-1.0.jar.sha1"));
+        assert_eq!(FileSize::Small, FileSize::classify("/org/example/1.0/example-1.0.pom"));
+        assert_eq!(FileSize::Large, FileSize::classify("/org/example/1.0/example-1.0.JAR"));
+        assert_eq!(FileSize::Large, FileSize::classify("/org/example/1.0/example-1.0-bin.zip"));
+        assert_eq!(FileSize::Unknown, FileSize::classify("/org/example/1.0/example-1.0.tar.gz"));
+        assert_eq!(FileSize::Unknown, FileSize::classify("/org/example.group/1.0/README"));
+    }
+
+    #[test]
+    fn header_limits() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "*/*".parse().unwrap());
+        headers.append("x-custom", "first".parse().unwrap());
+        headers.append("x-custom", "second".parse().unwrap());
+        assert_eq!(Ok(()), check_header_limits(&headers, None, None));
+        assert_eq!(Ok(()), check_header_limits(&headers, Some(3), Some(36)));
+        assert!(check_header_limits(&headers, Some(2), None).is_err());
+        assert!(check_header_limits(&headers, None, Some(35)).is_err());
+    }
+
+    #[test]
+    fn valid_gav_paths() {
+        for path in &[
+            "/org/apache/maven/plugins/maven-compiler-plugin/3.8.1/maven-compiler-plugin-3.8.1.pom",
+            "/org/example/example/maven-metadata.xml",
+            "/org/example/example/1.0-SNAPSHOT/example-1.0-20210101.000000-1.jar",
+            "/com/example/..hidden../1.0/file..name.jar"
+        ] {
+            assert_eq!(Ok(()), validate_gav_path(path), "{} should be valid", path);
+        }
+    }
+
+    #[test]
+    fn invalid_gav_paths() {
+        for path in &[
+            "/org/../../etc/passwd",
+            "/..",
+            "/org/%2e%2e/secret",
+            "/org/.%2E/secret",
+            "/org//example",
+            "/org/example\u{0}/file",
+            "/org/example\n/file",
+            "/org/example%00/file",
+            "/org/..%2fsecret"
+        ] {
+            assert!(validate_gav_path(path).is_err(), "{} should be invalid", path);
+        }
+    }
+
+    #[test]
+    fn valid_coordinates() {
+        for path in &[
+            "/org/apache/maven/plugins/maven-compiler-plugin/3.8.1/maven-compiler-plugin-3.8.1.pom",
+            "/org/example/example/1.0/example-1.0-sources.jar.sha1",
+            "/org/example/example/1.0-SNAPSHOT/example-1.0-20210101.000000-1.jar",
+            "/org/example/example/1.0-SNAPSHOT/example-1.0-SNAPSHOT.pom",
+            "/org/example/example/maven-metadata.xml",
+            "/org/example/example/1.0-SNAPSHOT/maven-metadata.xml.md5",
+            "/org/apache/maven/plugins/maven-metadata-central.xml",
+            "/archetype-catalog.xml",
+            "/.index/nexus-maven-repository-index.properties",
+            "/org/example/example/"
+        ] {
+            assert_eq!(Ok(()), validate_coordinates(path), "{} should be valid", path);
+        }
+    }
+
+    #[test]
+    fn invalid_coordinates() {
+        for path in &[
+            "/org/example/example/1.0/example",
+            "/org/example/example-1.0.jar",
+            "/example/1.0/example-1.0.jar",
+            "/org/example/example/1.0/other-1.0.jar",
+            "/org/example/example/1.0/example-2.0.jar",
+            "/org/example/example/1.0/example.jar",
+            "/org/example/example/1.0-SNAPSHOT/example-2.0-20210101.000000-1.jar"
+        ] {
+            assert!(validate_coordinates(path).is_err(), "{} should be invalid", path);
+        }
+    }
+
+    fn strip(prefix: &str, path: &str) -> Option<String> {
+        strip_path_prefix(prefix, &PathAndQuery::from_str(path).unwrap())
+            .map(|stripped| stripped.as_str().to_owned())
+    }
+
+    #[test]
+    fn strip_prefixed_paths() {
+        assert_eq!(Some("/org/example/1.0/example-1.0.jar".to_owned()),
+                   strip("/maven", "/maven/org/example/1.0/example-1.0.jar"));
+        assert_eq!(Some("/org/example?deep=true".to_owned()),
+                   strip("/maven/", "/maven/org/example?deep=true"));
+        assert_eq!(Some("/".to_owned()), strip("/maven", "/maven"));
+        assert_eq!(Some("/".to_owned()), strip("/maven", "/maven/"));
+        assert_eq!(Some("/?format=text".to_owned()), strip("/maven", "/maven?format=text"));
+    }
+
+    #[test]
+    fn strip_unprefixed_paths() {
+        assert_eq!(None, strip("/maven", "/org/example/1.0/example-1.0.jar"));
+        assert_eq!(None, strip("/maven", "/maven2/org/example"));
+        assert_eq!(None, strip("/maven", "/"));
+    }
+
+    #[test]
+    fn pin_from_header_or_query() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, repository_pin(&headers, None));
+        assert_eq!(None, repository_pin(&headers, Some("other=1")));
+        assert_eq!(Some("1".to_owned()), repository_pin(&headers, Some("repo=1")));
+        headers.insert(X_PROXY_REPOSITORY, " repo1.maven.org ".parse().unwrap());
+        assert_eq!(Some("repo1.maven.org".to_owned()), repository_pin(&headers, Some("repo=1")));
+    }
+
+    #[test]
+    fn collapse_repeated_slashes() -> Result<()> {
+        let collapse = |path: &str| collapse_slashes(&PathAndQuery::from_str(path).unwrap()).map(|path| path.to_string());
+        assert_eq!(None, collapse("/org/apache/maven/3.0/maven-3.0.pom"));
+        assert_eq!(Some("/org/apache/maven/3.0/maven-3.0.pom".to_owned()), collapse("//org//apache/maven///3.0/maven-3.0.pom"));
+        assert_eq!(Some("/org/example/a%2F%2Fb.jar?x=//y".to_owned()), collapse("/org//example/a%2F%2Fb.jar?x=//y"));
+        assert_eq!(Some("/".to_owned()), collapse("//"));
+        Ok(())
+    }
 }
@@ -0,0 +1,122 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use hyper::HeaderMap;
+use hyper::header::HeaderValue;
+use crate::headers::X_REQUEST_ID;
+
+// Longer incoming IDs are replaced, to keep log lines bounded
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+// Identifies a request across the proxy's logs and those of upstream repositories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+    // Honors an incoming X-Request-Id, otherwise generates a random UUID
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers.get(X_REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_acceptable(value))
+            .map(|value| RequestId(Arc::from(value)))
+            .unwrap_or_else(Self::generate)
+    }
+
+    fn generate() -> Self {
+        // Version 4 UUID: random apart from the version and variant bits
+        let random = rand::random::<u128>() & !(0xF000 << 64) & !(0xC000 << 48);
+        let uuid = random | (0x4000 << 64) | (0x8000 << 48);
+        let hex = format!("{:032x}", uuid);
+        RequestId(Arc::from(format!("{}-{}-{}-{}-{}",
+                                    &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])))
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        // Only visible ASCII is ever accepted or generated
+        HeaderValue::from_str(&self.0).expect("Request ID is a valid header value")
+    }
+
+    // The ID of the request being handled by the current task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    // Runs the future with this ID as the current request ID
+    pub async fn scope<F>(self, future: F) -> F::Output where F: Future {
+        CURRENT.scope(self, future).await
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn is_acceptable(value: &str) -> bool {
+    !value.is_empty() && value.len() <= MAX_LENGTH && value.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers_with(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_ID, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn incoming_id_honored() {
+        assert_eq!("abc-123", RequestId::from_headers(&headers_with("abc-123")).to_string());
+    }
+
+    #[test]
+    fn generated_uuid() {
+        let id = RequestId::from_headers(&HeaderMap::new()).to_string();
+        assert_eq!(36, id.len());
+        let groups: Vec<&str> = id.split('-').collect();
+        assert_eq!(vec![8, 4, 4, 4, 12], groups.iter().map(|group| group.len()).collect::<Vec<_>>());
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(id, RequestId::from_headers(&HeaderMap::new()).to_string());
+    }
+
+    #[test]
+    fn unacceptable_id_replaced() {
+        assert_ne!("has space", RequestId::from_headers(&headers_with("has space")).to_string());
+        assert_eq!(36, RequestId::from_headers(&headers_with("")).to_string().len());
+    }
+
+    #[tokio::test]
+    async fn current_within_scope() {
+        assert_eq!(None, RequestId::current());
+        let id = RequestId::from_headers(&headers_with("scoped"));
+        let current = id.clone().scope(async { RequestId::current() }).await;
+        assert_eq!(Some(id), current);
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Response, Body, StatusCode, http};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use serde::Serialize;
+use eyre::Result;
+use crate::repository::Repository;
+
+#[derive(Debug, Serialize)]
+pub struct ResolutionReport {
+    path: String,
+    repositories: Vec<RepositoryResolution>
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryResolution {
+    url: String,
+    status: Availability,
+    content_length: Option<u64>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Found,
+    NotFound,
+    Error
+}
+
+impl ResolutionReport {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            repositories: Vec::new()
+        }
+    }
+
+    // Entries are expected in the order the repositories are configured
+    pub fn push(&mut self, repository: &Repository, availability: Availability, response: Option<&Response<Body>>) {
+        let content_length = response
+            .and_then(|response| response.headers().get(CONTENT_LENGTH))
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse().ok());
+        self.repositories.push(RepositoryResolution {
+            url: repository.redacted_url(),
+            status: availability,
+            content_length
+        });
+    }
+
+    pub fn into_response(self, version: http::version::Version) -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_string(&self)?))?)
+    }
+}
+
+// The artifact path to resolve, from the path query parameter
+pub fn requested_path(query: Option<&str>) -> Option<String> {
+    let query = query?;
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "path")
+        .map(|(_, value)| value.into_owned())
+        .filter(|path| !path.is_empty())
+        .map(|path| if path.starts_with('/') { path } else { format!("/{}", path) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_from_query() {
+        assert_eq!(None, requested_path(None));
+        assert_eq!(None, requested_path(Some("other=1")));
+        assert_eq!(None, requested_path(Some("path=")));
+        assert_eq!(Some("/org/example/1.0/example-1.0.jar".to_string()),
+                   requested_path(Some("path=org/example/1.0/example-1.0.jar")));
+        assert_eq!(Some("/org/example/1.0/example-1.0.pom".to_string()),
+                   requested_path(Some("format=json&path=%2Forg%2Fexample%2F1.0%2Fexample-1.0.pom")));
+    }
+
+    #[test]
+    fn report_content_length() {
+        let repository = Repository::new("http://localhost/maven".parse().unwrap());
+        let response = Response::builder()
+            .header(CONTENT_LENGTH, "42")
+            .body(Body::empty())
+            .unwrap();
+        let mut report = ResolutionReport::new("/org/example/1.0/example-1.0.jar");
+        report.push(&repository, Availability::Found, Some(&response));
+        report.push(&repository, Availability::NotFound, None);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!("found", json["repositories"][0]["status"]);
+        assert_eq!(42, json["repositories"][0]["content_length"]);
+        assert_eq!("not_found", json["repositories"][1]["status"]);
+        assert!(json["repositories"][1]["content_length"].is_null());
+    }
+}
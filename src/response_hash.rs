@@ -0,0 +1,89 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Body, Response};
+use hyper::body::Bytes;
+use sha2::{Digest, Sha256};
+use crate::body_observer::{self, BodyObserver};
+
+// Hashes the body as it streams to the client, calling on_complete with the hex SHA-256 and
+// the length once all of it has been sent. Nothing is reported for bodies which fail
+pub fn hash_body(response: Response<Body>,
+                 on_complete: impl FnOnce(String, u64) + Send + 'static) -> Response<Body> {
+    body_observer::observe_body(response, Hashing {
+        hasher: Sha256::new(),
+        on_complete: Some(on_complete)
+    })
+}
+
+struct Hashing<F> {
+    hasher: Sha256,
+    // Taken once the hash is reported
+    on_complete: Option<F>
+}
+
+impl<F: FnOnce(String, u64) + Send + 'static> BodyObserver for Hashing<F> {
+    fn data(&mut self, data: &Bytes) {
+        self.hasher.update(data);
+    }
+
+    fn complete(&mut self, sent: u64) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(format!("{:x}", self.hasher.finalize_reset()), sent);
+        }
+    }
+
+    fn failed(&mut self, sent: u64) {
+        log::debug!("Not hashing a response body which failed after {} bytes", sent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use eyre::Result;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    async fn hash_of(body: Body) -> Result<Option<(String, u64)>> {
+        let hashed = Arc::new(Mutex::new(None));
+        let result = hashed.clone();
+        let response = hash_body(Response::new(body), move |hash, length| {
+            *result.lock().unwrap() = Some((hash, length));
+        });
+        hyper::body::to_bytes(response.into_body()).await?;
+        let hashed = hashed.lock().unwrap().clone();
+        Ok(hashed)
+    }
+
+    #[tokio::test]
+    async fn hash_whole_body() -> Result<()> {
+        assert_eq!(Some((HELLO_SHA256.to_owned(), 5)), hash_of(Body::from("hello")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hash_streamed_body() -> Result<()> {
+        let chunks = ["he", "l", "lo"].map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk)));
+        let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+        assert_eq!(Some((HELLO_SHA256.to_owned(), 5)), hash_of(body).await?);
+        Ok(())
+    }
+}
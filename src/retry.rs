@@ -0,0 +1,244 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::{Response, Body, StatusCode};
+use hyper::header::RETRY_AFTER;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+use crate::error::ProxyError;
+use crate::rate_limit::RateLimiter;
+
+// Gateway errors are the usual sign of an upstream which is briefly unavailable
+pub const DEFAULT_RETRY_STATUSES: &[StatusCode] = &[
+    StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE, StatusCode::GATEWAY_TIMEOUT
+];
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+    retry_statuses: Arc<[StatusCode]>,
+    // The timeout of the first attempt, which is multiplied for each retry
+    initial_timeout: Option<Duration>,
+    timeout_multiplier: u32,
+    // Shared by every clone of the policy, so that it limits retries across all requests
+    budget: Option<Arc<RateLimiter>>
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            retry_statuses: DEFAULT_RETRY_STATUSES.into(),
+            initial_timeout: None,
+            timeout_multiplier: 1,
+            budget: None
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    // Gives the first attempt a short timeout, so that dead repositories fail fast, and each retry
+    // a longer one, so that slow repositories still get a chance. Timed out attempts are retried
+    pub fn with_timeout_escalation(mut self, initial_timeout: Option<Duration>, multiplier: u32) -> Self {
+        self.initial_timeout = initial_timeout;
+        self.timeout_multiplier = multiplier;
+        self
+    }
+
+    // Responses with any of these statuses are retried. Not Found is never retried
+    pub fn with_retry_statuses(mut self, retry_statuses: Vec<StatusCode>) -> Self {
+        self.retry_statuses = retry_statuses.into_iter()
+            .filter(|status| *status != StatusCode::NOT_FOUND)
+            .collect();
+        self
+    }
+
+    // Allows this many retries per second across all requests, so that an outage of the
+    // repositories is not made worse by every request retrying at once
+    pub fn with_budget(mut self, retries_per_second: Option<u32>) -> Self {
+        self.budget = retries_per_second.map(|per_second| Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    // Exponential backoff: the delay doubles with each subsequent retry
+    fn backoff_for(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(1_u32.checked_shl(retry).unwrap_or(u32::MAX))
+    }
+
+    // Each attempt is given no longer than the repository's own timeout
+    fn attempt_timeout(&self, retry: u32, repository_timeout: Duration) -> Duration {
+        match self.initial_timeout {
+            Some(initial_timeout) => initial_timeout
+                .saturating_mul(self.timeout_multiplier.saturating_pow(retry))
+                .min(repository_timeout),
+            None => repository_timeout
+        }
+    }
+
+    // The time allowed for all attempts together, including the backoff between them.
+    // Without escalation, the repository's timeout covers every attempt
+    pub fn total_timeout(&self, repository_timeout: Duration) -> Duration {
+        if self.initial_timeout.is_none() {
+            return repository_timeout;
+        }
+        (0..=self.max_retries)
+            .map(|retry| self.attempt_timeout(retry, repository_timeout))
+            .chain((0..self.max_retries).map(|retry| self.backoff_for(retry)))
+            .fold(Duration::ZERO, Duration::saturating_add)
+    }
+
+    fn should_retry(&self, result: &Result<Response<Body>, ProxyError>) -> bool {
+        match result {
+            Ok(response) => self.is_retryable_status(response.status()),
+            Err(ProxyError::Timeout(_)) => self.initial_timeout.is_some(),
+            Err(error) => error.is_transient()
+        }
+    }
+
+    fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    pub async fn retry<F, Fut>(self, repository_timeout: Duration, mut attempt: F) -> Result<Response<Body>, ProxyError>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output=Result<Response<Body>, ProxyError>> {
+
+        let mut retry = 0;
+        loop {
+            let result = match self.initial_timeout {
+                Some(_) => timeout(self.attempt_timeout(retry, repository_timeout), attempt())
+                    .await
+                    .unwrap_or_else(|elapsed| Err(elapsed.into())),
+                None => attempt().await
+            };
+            if retry >= self.max_retries || !self.should_retry(&result) {
+                return result;
+            }
+            // Upstreams which ask for a delay get it, unless it would outlast the repository's timeout
+            let delay = match result.as_ref().ok().and_then(retry_after) {
+                Some(retry_after) if retry_after > repository_timeout => return result,
+                Some(retry_after) => retry_after,
+                None => self.backoff_for(retry)
+            };
+            if self.budget.as_ref().is_some_and(|budget| budget.try_acquire().is_err()) {
+                log::debug!("Not retrying, since the retry budget is spent");
+                return result;
+            }
+            match &result {
+                Ok(response) => log::debug!(
+                    "Retrying after status {:?} in {:?}", response.status(), delay),
+                Err(error) => log::debug!(
+                    "Retrying after error {:?} in {:?}", error, delay)
+            }
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+// Only the delay in seconds form of Retry-After is understood
+fn retry_after(response: &Response<Body>) -> Option<Duration> {
+    response.headers().get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(100), policy.backoff_for(0));
+        assert_eq!(Duration::from_millis(200), policy.backoff_for(1));
+        assert_eq!(Duration::from_millis(400), policy.backoff_for(2));
+    }
+
+    #[test]
+    fn escalating_timeouts() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100))
+            .with_timeout_escalation(Some(Duration::from_millis(50)), 3);
+        let repository_timeout = Duration::from_secs(1);
+        assert_eq!(Duration::from_millis(50), policy.attempt_timeout(0, repository_timeout));
+        assert_eq!(Duration::from_millis(150), policy.attempt_timeout(1, repository_timeout));
+        assert_eq!(Duration::from_millis(450), policy.attempt_timeout(2, repository_timeout));
+        assert_eq!(repository_timeout, policy.attempt_timeout(3, repository_timeout));
+        // 50 + 150 + 450 + 1000 for the attempts, and 100 + 200 + 400 between them
+        assert_eq!(Duration::from_millis(2350), policy.total_timeout(repository_timeout));
+
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        assert_eq!(repository_timeout, policy.attempt_timeout(2, repository_timeout));
+        assert_eq!(repository_timeout, policy.total_timeout(repository_timeout));
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let policy = RetryPolicy::new(1, Duration::from_millis(100));
+        assert!(policy.is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(policy.is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(policy.is_retryable_status(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!policy.is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!policy.is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+
+        let policy = policy.with_retry_statuses(vec![StatusCode::TOO_MANY_REQUESTS, StatusCode::NOT_FOUND]);
+        assert!(policy.is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!policy.is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!policy.is_retryable_status(StatusCode::BAD_GATEWAY));
+    }
+
+    // Retries an always unavailable upstream, returning how many attempts were made
+    async fn attempts(policy: &RetryPolicy) -> u32 {
+        let mut attempts = 0;
+        policy.clone().retry(Duration::from_secs(1), || {
+            attempts += 1;
+            async { Ok(Response::builder().status(StatusCode::SERVICE_UNAVAILABLE).body(Body::empty()).unwrap()) }
+        }).await.unwrap();
+        attempts
+    }
+
+    #[tokio::test]
+    async fn retry_budget() {
+        tokio::time::pause();
+        let policy = RetryPolicy::new(3, Duration::from_millis(1)).with_budget(Some(2));
+        // The two retries allowed are shared between requests
+        assert_eq!(3, attempts(&policy).await);
+        assert_eq!(1, attempts(&policy).await);
+        // Once refilled, requests are retried again
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(3, attempts(&policy).await);
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let response = |value: &str| Response::builder().header(RETRY_AFTER, value).body(Body::empty()).unwrap();
+        assert_eq!(Some(Duration::from_secs(2)), retry_after(&response("2")));
+        assert_eq!(None, retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")));
+        assert_eq!(None, retry_after(&Response::new(Body::empty())));
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::str::FromStr;
+use hyper::http::uri::PathAndQuery;
+use regex::Regex;
+
+// Rewrites incoming paths matching a regular expression. The replacement may refer
+// to capture groups, as in $1 or ${name}
+#[derive(Debug, Clone)]
+pub struct PathRewrite {
+    pattern: Regex,
+    replacement: String
+}
+
+impl PathRewrite {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.to_owned()
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+// Regexes do not implement equality, so rewrites are compared by their source
+impl PartialEq for PathRewrite {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern() == other.pattern() && self.replacement == other.replacement
+    }
+}
+
+impl Eq for PathRewrite {}
+
+// Applies the first rewrite whose pattern matches the path, keeping the query.
+// Returns None if no rewrite applies, or if the rewritten path is not a valid absolute path
+pub fn rewrite(rewrites: &[PathRewrite], path_and_query: &PathAndQuery) -> Option<PathAndQuery> {
+    let path = path_and_query.path();
+    let rewrite = rewrites.iter().find(|rewrite| rewrite.pattern.is_match(path))?;
+    let mut rewritten = rewrite.pattern.replace(path, rewrite.replacement.as_str()).into_owned();
+    if !rewritten.starts_with('/') {
+        log::warn!("Rewriting {} with {:?} does not produce an absolute path", path, rewrite.pattern());
+        return None;
+    }
+    if let Some(query) = path_and_query.query() {
+        rewritten.push('?');
+        rewritten.push_str(query);
+    }
+    PathAndQuery::from_str(&rewritten)
+        .map_err(|_| log::warn!("Rewriting {} with {:?} produces an invalid path", path, rewrite.pattern()))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    fn rewritten(rewrites: &[PathRewrite], path: &str) -> Option<String> {
+        rewrite(rewrites, &PathAndQuery::from_str(path).unwrap()).map(|path| path.to_string())
+    }
+
+    #[test]
+    fn first_matching_rewrite() -> Result<()> {
+        let rewrites = [
+            PathRewrite::new("^/nexus/content/(groups|repositories)/[^/]+/", "/")?,
+            PathRewrite::new("^/artifactory/(?P<repository>[^/]+)/", "/${repository}/")?,
+            PathRewrite::new("^/nexus/", "/unused/")?
+        ];
+        assert_eq!(Some("/org/example/1.0/example-1.0.jar".to_owned()),
+                   rewritten(&rewrites, "/nexus/content/groups/public/org/example/1.0/example-1.0.jar"));
+        assert_eq!(Some("/libs/org/example/maven-metadata.xml?x=1".to_owned()),
+                   rewritten(&rewrites, "/artifactory/libs/org/example/maven-metadata.xml?x=1"));
+        assert_eq!(None, rewritten(&rewrites, "/org/example/1.0/example-1.0.jar"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_rewritten_paths() -> Result<()> {
+        assert_eq!(None, rewritten(&[PathRewrite::new("^/", "")?], "/org/example/1.0/example-1.0.jar"));
+        assert_eq!(None, rewritten(&[PathRewrite::new("^/mirror", "/with space")?], "/mirror/org"));
+        Ok(())
+    }
+}
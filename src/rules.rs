@@ -0,0 +1,207 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt;
+use crate::repository::Repository;
+
+// A glob over the segments of a path. ** matches any number of segments,
+// while * and ? match characters within a single segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern {
+    segments: Vec<String>
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            segments: split_segments(pattern).map(str::to_owned).collect()
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = split_segments(path).collect();
+        let pattern: Vec<&str> = self.segments.iter().map(String::as_str).collect();
+        matches_segments(&pattern, &path)
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.segments.join("/"))
+    }
+}
+
+fn split_segments(path: &str) -> impl Iterator<Item=&str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn matches_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            matches_segments(&pattern[1..], path)
+                || (!path.is_empty() && matches_segments(pattern, &path[1..]))
+        },
+        (Some(segment_pattern), Some(segment)) => {
+            matches_segment(segment_pattern.as_bytes(), segment.as_bytes())
+                && matches_segments(&pattern[1..], &path[1..])
+        },
+        _ => false
+    }
+}
+
+fn matches_segment(pattern: &[u8], segment: &[u8]) -> bool {
+    match (pattern.first(), segment.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches_segment(&pattern[1..], segment)
+                || (!segment.is_empty() && matches_segment(pattern, &segment[1..]))
+        },
+        (Some(b'?'), Some(_)) => matches_segment(&pattern[1..], &segment[1..]),
+        (Some(expected), Some(actual)) => expected == actual && matches_segment(&pattern[1..], &segment[1..]),
+        _ => false
+    }
+}
+
+// Restricts which repositories are contacted for paths matching the pattern.
+// Repositories are identified by their index in the repository list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryRule {
+    pattern: PathPattern,
+    allow: Vec<usize>,
+    deny: Vec<usize>
+}
+
+impl RepositoryRule {
+    // An empty allow list allows every repository not denied
+    pub fn new(pattern: PathPattern, allow: Vec<usize>, deny: Vec<usize>) -> Self {
+        Self {
+            pattern,
+            allow,
+            deny
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.pattern.matches(path)
+    }
+
+    fn permits(&self, index: usize) -> bool {
+        (self.allow.is_empty() || self.allow.contains(&index)) && !self.deny.contains(&index)
+    }
+
+    pub fn filter(&self, repositories: &[Repository]) -> Vec<Repository> {
+        repositories
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.permits(*index))
+            .map(|(_, repository)| repository.clone())
+            .collect()
+    }
+
+    pub fn indices(&self) -> impl Iterator<Item=usize> + '_ {
+        self.allow.iter().chain(&self.deny).copied()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "pattern": self.pattern.to_string(),
+            "allow": self.allow,
+            "deny": self.deny
+        })
+    }
+}
+
+// Restricts proxying to artifacts whose groupId starts with an approved prefix, such as
+// com.mycompany, which also covers com.mycompany.library. Empty allows every artifact
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupAllowlist {
+    prefixes: Vec<Vec<String>>
+}
+
+impl GroupAllowlist {
+    pub fn new(prefixes: &[String]) -> Self {
+        Self {
+            prefixes: prefixes.iter()
+                .map(|prefix| prefix.split('.').filter(|part| !part.is_empty()).map(str::to_owned).collect())
+                .collect()
+        }
+    }
+
+    // Compares whole segments of the path, which begins with the groupId's parts
+    pub fn allows(&self, path: &str) -> bool {
+        if self.prefixes.is_empty() {
+            return true;
+        }
+        let segments: Vec<&str> = split_segments(path).collect();
+        self.prefixes.iter().any(|prefix| {
+            // The artifactId and file follow the groupId
+            segments.len() > prefix.len() + 1 && prefix.iter().zip(&segments).all(|(part, segment)| part == segment)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Uri;
+
+    #[test]
+    fn glob_patterns() {
+        let pattern = PathPattern::new("com/mycompany/**");
+        assert!(pattern.matches("/com/mycompany/library/1.0/library-1.0.jar"));
+        assert!(pattern.matches("/com/mycompany"));
+        assert!(!pattern.matches("/com/mycompanyx/library/1.0/library-1.0.jar"));
+        assert!(!pattern.matches("/org/mycompany/library"));
+
+        let pattern = PathPattern::new("/org/*/internal-?/**/*.pom");
+        assert!(pattern.matches("/org/example/internal-a/1.0/internal-a-1.0.pom"));
+        assert!(!pattern.matches("/org/example/internal-ab/1.0/internal-ab-1.0.pom"));
+        assert!(!pattern.matches("/org/example/internal-a/1.0/internal-a-1.0.jar"));
+        assert!(!pattern.matches("/org/example/deeper/internal-a/1.0/internal-a-1.0.pom"));
+    }
+
+    #[test]
+    fn group_allowlist() {
+        let allowlist = GroupAllowlist::new(&["com.mycompany".to_owned(), "org.example.tools".to_owned()]);
+        assert!(allowlist.allows("/com/mycompany/library/1.0/library-1.0.jar"));
+        assert!(allowlist.allows("/com/mycompany/internal/library/maven-metadata.xml"));
+        assert!(allowlist.allows("/org/example/tools/cli/1.0/cli-1.0.pom"));
+        assert!(!allowlist.allows("/com/mycompanyx/library/1.0/library-1.0.jar"));
+        assert!(!allowlist.allows("/org/example/library/1.0/library-1.0.jar"));
+        assert!(!allowlist.allows("/com/mycompany"));
+        assert!(!allowlist.allows("/com/mycompany/maven-metadata.xml"));
+
+        let empty = GroupAllowlist::new(&[]);
+        assert!(empty.allows("/org/example/library/1.0/library-1.0.jar"));
+    }
+
+    #[test]
+    fn filter_repositories() {
+        let repositories: Vec<Repository> = vec![
+            Uri::from_static("https://internal.example.com/maven").into(),
+            Uri::from_static("https://repo1.maven.org/maven2").into(),
+            Uri::from_static("https://repo.example.com/releases").into()
+        ];
+        let allow = RepositoryRule::new(PathPattern::new("**"), vec![0], vec![]);
+        assert_eq!(repositories[..1].to_vec(), allow.filter(&repositories));
+        let deny = RepositoryRule::new(PathPattern::new("**"), vec![], vec![1]);
+        assert_eq!(vec![repositories[0].clone(), repositories[2].clone()], deny.filter(&repositories));
+    }
+}
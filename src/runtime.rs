@@ -0,0 +1,75 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use tokio::runtime::{Builder, Runtime};
+
+// The current-thread runtime is the default; the multi-thread runtime spreads requests
+// across worker threads, one per core unless a count is given
+pub fn build(multi_thread: bool, worker_threads: Option<usize>) -> std::io::Result<Runtime> {
+    let mut builder = if multi_thread {
+        let mut builder = Builder::new_multi_thread();
+        if let Some(worker_threads) = worker_threads {
+            builder.worker_threads(worker_threads);
+        }
+        builder
+    } else {
+        Builder::new_current_thread()
+    };
+    builder.enable_all().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // Handlers which block their thread, as hashing or decompressing a large jar would
+    fn concurrent_blocking_requests(runtime: Runtime) -> Duration {
+        runtime.block_on(async {
+            let started = Instant::now();
+            let requests: Vec<_> = (0..4)
+                .map(|_| tokio::spawn(async { std::thread::sleep(Duration::from_millis(100)) }))
+                .collect();
+            for request in requests {
+                request.await.unwrap();
+            }
+            started.elapsed()
+        })
+    }
+
+    #[test]
+    fn multi_thread_throughput() -> std::io::Result<()> {
+        let current_thread = concurrent_blocking_requests(build(false, None)?);
+        let multi_thread = concurrent_blocking_requests(build(true, Some(4))?);
+        assert!(current_thread >= Duration::from_millis(400), "Current thread took {:?}", current_thread);
+        assert!(multi_thread < Duration::from_millis(300), "Multi thread took {:?}", multi_thread);
+        Ok(())
+    }
+
+    #[test]
+    fn runs_io_and_timers() -> std::io::Result<()> {
+        for runtime in [build(false, None)?, build(true, Some(2))?] {
+            runtime.block_on(async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use hyper::{Body, Response, StatusCode, http};
+use serde::Serialize;
+use eyre::Result;
+use crate::distinct::DistinctPaths;
+use crate::repository::Repository;
+
+// How a single upstream request ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Hit,
+    NotFound,
+    Error,
+    Timeout
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    not_found: AtomicU64,
+    errors: AtomicU64,
+    timeouts: AtomicU64,
+    latency: LatencyHistogram
+}
+
+// Buckets per doubling of latency, so each bucket spans about 9% of its lower bound
+const BUCKETS_PER_OCTAVE: f64 = 8.0;
+// Covers latencies up to 2^35 microseconds, about 9.5 hours
+const LATENCY_BUCKETS: usize = 35 * 8;
+
+// Counts latencies in exponentially sized buckets of microseconds
+#[derive(Debug)]
+struct LatencyHistogram {
+    buckets: Vec<u64>,
+    samples: u64
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; LATENCY_BUCKETS],
+            samples: 0
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().max(1) as f64;
+        let bucket = ((micros.log2() * BUCKETS_PER_OCTAVE) as usize).min(LATENCY_BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.samples += 1;
+    }
+
+    // The upper bound of the bucket containing the percentile, in milliseconds
+    fn percentile(&self, percentile: f64) -> Option<f64> {
+        if self.samples == 0 {
+            return None;
+        }
+        let rank = ((percentile / 100.0) * self.samples as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        let bucket = self.buckets.iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(LATENCY_BUCKETS - 1);
+        let upper_bound_micros = 2f64.powf((bucket + 1) as f64 / BUCKETS_PER_OCTAVE);
+        Some(upper_bound_micros / 1000.0)
+    }
+
+    fn summary(&self) -> Option<Latency> {
+        Some(Latency {
+            p50: self.percentile(50.0)?,
+            p95: self.percentile(95.0)?,
+            p99: self.percentile(99.0)?,
+            samples: self.samples
+        })
+    }
+}
+
+// Time to the first byte of upstream responses
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct Latency {
+    p50: f64,
+    p95: f64,
+    p99: f64,
+    samples: u64
+}
+
+impl Counters {
+    fn snapshot(&self) -> Counts {
+        Counts {
+            hits: self.hits.load(Ordering::Relaxed),
+            not_found: self.not_found.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed)
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+struct Counts {
+    hits: u64,
+    not_found: u64,
+    errors: u64,
+    timeouts: u64
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.hits += other.hits;
+        self.not_found += other.not_found;
+        self.errors += other.errors;
+        self.timeouts += other.timeouts;
+    }
+}
+
+// Counts upstream request outcomes per repository since startup
+#[derive(Debug, Default)]
+pub struct Stats {
+    repositories: Mutex<HashMap<String, Counters>>,
+    bytes_served: AtomicU64,
+    client_bytes: Mutex<HashMap<IpAddr, u64>>,
+    client_aborts: AtomicU64
+}
+
+#[derive(Debug, Serialize)]
+struct RepositoryStats {
+    url: String,
+    #[serde(flatten)]
+    counts: Counts,
+    latency_ms: Option<Latency>
+}
+
+#[derive(Debug, Serialize)]
+struct StatsReport {
+    repositories: Vec<RepositoryStats>,
+    total: Counts,
+    bytes_served: BytesServed,
+    client_aborts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distinct_paths: Option<u64>
+}
+
+#[derive(Debug, Serialize)]
+struct BytesServed {
+    total: u64,
+    clients: BTreeMap<String, u64>
+}
+
+impl Stats {
+    pub fn record(&self, repository: &str, outcome: Outcome) {
+        let mut repositories = self.repositories.lock().unwrap();
+        let counters = Self::counters(&mut repositories, repository);
+        let counter = match outcome {
+            Outcome::Hit => &counters.hits,
+            Outcome::NotFound => &counters.not_found,
+            Outcome::Error => &counters.errors,
+            Outcome::Timeout => &counters.timeouts
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Records the time taken for a repository's response headers to arrive
+    pub fn record_latency(&self, repository: &str, latency: Duration) {
+        let mut repositories = self.repositories.lock().unwrap();
+        Self::counters(&mut repositories, repository).latency.record(latency);
+    }
+
+    // Counts response body bytes sent to clients, by client address where known
+    pub fn record_bytes(&self, client: Option<IpAddr>, bytes: usize) {
+        self.bytes_served.fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(client) = client {
+            *self.client_bytes.lock().unwrap().entry(client).or_insert(0) += bytes as u64;
+        }
+    }
+
+    // Counts downloads which the client disconnected from before the body was fully sent
+    pub fn record_client_abort(&self) {
+        self.client_aborts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counters<'a>(repositories: &'a mut HashMap<String, Counters>, repository: &str) -> &'a mut Counters {
+        if !repositories.contains_key(repository) {
+            repositories.insert(repository.to_owned(), Counters::default());
+        }
+        repositories.get_mut(repository).expect("Counters were just inserted")
+    }
+
+    // Reports the configured repositories in order. Totals include repositories
+    // which have since been removed by a configuration reload
+    fn report(&self, repositories: &[Repository], distinct_paths: Option<&DistinctPaths>) -> StatsReport {
+        let counters = self.repositories.lock().unwrap();
+        let mut total = Counts::default();
+        for repository_counters in counters.values() {
+            total.add(repository_counters.snapshot());
+        }
+        let repositories = repositories.iter()
+            .map(|repository| {
+                let url = repository.uri().to_string();
+                let repository_counters = counters.get(&url);
+                let counts = repository_counters.map(Counters::snapshot).unwrap_or_default();
+                let latency_ms = repository_counters.and_then(|counters| counters.latency.summary());
+                RepositoryStats { url, counts, latency_ms }
+            })
+            .collect();
+        let bytes_served = BytesServed {
+            total: self.bytes_served.load(Ordering::Relaxed),
+            clients: self.client_bytes.lock().unwrap()
+                .iter()
+                .map(|(client, &bytes)| (client.to_string(), bytes))
+                .collect()
+        };
+        let client_aborts = self.client_aborts.load(Ordering::Relaxed);
+        let distinct_paths = distinct_paths.map(DistinctPaths::estimate);
+        StatsReport { repositories, total, bytes_served, client_aborts, distinct_paths }
+    }
+
+    // Writes the report to a file, replacing it only once the report is fully written
+    pub fn write_snapshot(&self,
+                          path: &Path,
+                          repositories: &[Repository],
+                          distinct_paths: Option<&DistinctPaths>) -> Result<()> {
+        let mut partial_path = path.as_os_str().to_owned();
+        partial_path.push(".part");
+        std::fs::write(&partial_path, serde_json::to_vec_pretty(&self.report(repositories, distinct_paths))?)?;
+        std::fs::rename(&partial_path, path)?;
+        Ok(())
+    }
+
+    pub fn response(&self,
+                    repositories: &[Repository],
+                    distinct_paths: Option<&DistinctPaths>,
+                    version: http::version::Version) -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&self.report(repositories, distinct_paths))?))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Uri;
+
+    #[test]
+    fn report_totals() -> Result<()> {
+        let stats = Stats::default();
+        let first = Repository::new(Uri::from_static("https://first.example/"));
+        let second = Repository::new(Uri::from_static("https://second.example/"));
+        stats.record("https://first.example/", Outcome::Hit);
+        stats.record("https://first.example/", Outcome::Hit);
+        stats.record("https://second.example/", Outcome::NotFound);
+        stats.record("https://second.example/", Outcome::Timeout);
+        stats.record("https://removed.example/", Outcome::Error);
+
+        let report = serde_json::to_value(stats.report(&[first, second], None))?;
+        assert_eq!("https://first.example/", report["repositories"][0]["url"]);
+        assert_eq!(2, report["repositories"][0]["hits"]);
+        assert_eq!(0, report["repositories"][0]["not_found"]);
+        assert_eq!(1, report["repositories"][1]["not_found"]);
+        assert_eq!(1, report["repositories"][1]["timeouts"]);
+        assert_eq!(2, report["total"]["hits"]);
+        assert_eq!(1, report["total"]["errors"]);
+        assert!(report["repositories"][0]["latency_ms"].is_null());
+        assert_eq!(0, report["bytes_served"]["total"]);
+        assert_eq!(0, report["client_aborts"]);
+        assert!(report.get("distinct_paths").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn report_bytes_served() -> Result<()> {
+        let stats = Stats::default();
+        let client = IpAddr::from([192, 168, 1, 7]);
+        stats.record_bytes(Some(client), 100);
+        stats.record_bytes(Some(client), 50);
+        stats.record_bytes(None, 25);
+        let report = serde_json::to_value(stats.report(&[], None))?;
+        assert_eq!(175, report["bytes_served"]["total"]);
+        assert_eq!(150, report["bytes_served"]["clients"]["192.168.1.7"]);
+        Ok(())
+    }
+
+    #[test]
+    fn write_snapshot() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("stats.json");
+        let stats = Stats::default();
+        stats.record("https://first.example/", Outcome::Hit);
+        stats.write_snapshot(&path, &[Repository::new(Uri::from_static("https://first.example/"))], None)?;
+        let snapshot: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+        assert_eq!(1, snapshot["repositories"][0]["hits"]);
+        assert_eq!(vec![path], std::fs::read_dir(directory.path())?.map(|entry| entry.unwrap().path()).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    fn assert_near(expected: f64, actual: Option<f64>) {
+        let actual = actual.expect("Latencies were recorded");
+        assert!((actual - expected).abs() <= expected * 0.1, "Expected about {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(None, histogram.summary());
+        for millis in 1..=1000 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        assert_near(500.0, histogram.percentile(50.0));
+        assert_near(950.0, histogram.percentile(95.0));
+        assert_near(990.0, histogram.percentile(99.0));
+        assert_near(1.0, histogram.percentile(0.0));
+        assert_eq!(1000, histogram.summary().unwrap().samples);
+    }
+
+    #[test]
+    fn extreme_latencies() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::ZERO);
+        histogram.record(Duration::from_secs(1_000_000));
+        assert!(histogram.percentile(50.0).unwrap() < 0.01);
+        assert!(histogram.percentile(100.0).unwrap() > 30_000_000.0);
+    }
+}
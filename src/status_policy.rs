@@ -0,0 +1,92 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::StatusCode;
+
+// What an upstream response's status says about the requested path
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StatusClass {
+    // The repository has it, and the response is passed on
+    Success,
+    // The repository does not have it, so other repositories are asked
+    NotFound,
+    // The repository could not answer properly
+    Failure
+}
+
+// Which upstream statuses count as having the artifact, and which as not having it.
+// Every other status is a failure of the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPolicy {
+    success: Vec<StatusCode>,
+    not_found: Vec<StatusCode>
+}
+
+impl Default for StatusPolicy {
+    fn default() -> Self {
+        Self::new(
+            vec![StatusCode::OK, StatusCode::PARTIAL_CONTENT, StatusCode::NOT_MODIFIED],
+            vec![StatusCode::NOT_FOUND]
+        )
+    }
+}
+
+impl StatusPolicy {
+    pub fn new(success: Vec<StatusCode>, not_found: Vec<StatusCode>) -> Self {
+        Self {
+            success,
+            not_found
+        }
+    }
+
+    pub fn classify(&self, status: StatusCode) -> StatusClass {
+        if self.success.contains(&status) {
+            StatusClass::Success
+        } else if self.not_found.contains(&status) {
+            StatusClass::NotFound
+        } else {
+            StatusClass::Failure
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy() {
+        let policy = StatusPolicy::default();
+        assert_eq!(StatusClass::Success, policy.classify(StatusCode::OK));
+        assert_eq!(StatusClass::Success, policy.classify(StatusCode::NOT_MODIFIED));
+        assert_eq!(StatusClass::NotFound, policy.classify(StatusCode::NOT_FOUND));
+        assert_eq!(StatusClass::Failure, policy.classify(StatusCode::GONE));
+        assert_eq!(StatusClass::Failure, policy.classify(StatusCode::NON_AUTHORITATIVE_INFORMATION));
+    }
+
+    #[test]
+    fn custom_policy() {
+        let policy = StatusPolicy::new(
+            vec![StatusCode::OK, StatusCode::NON_AUTHORITATIVE_INFORMATION],
+            vec![StatusCode::NOT_FOUND, StatusCode::GONE]);
+        assert_eq!(StatusClass::Success, policy.classify(StatusCode::NON_AUTHORITATIVE_INFORMATION));
+        assert_eq!(StatusClass::NotFound, policy.classify(StatusCode::GONE));
+        assert_eq!(StatusClass::Failure, policy.classify(StatusCode::PARTIAL_CONTENT));
+    }
+}
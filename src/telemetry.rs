@@ -0,0 +1,449 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use hyper::HeaderMap;
+use hyper::header::HeaderValue;
+use rand::Rng;
+
+pub const TRACEPARENT: &str = "traceparent";
+// Set on spans which were dropped before being finished
+pub const CANCELLED: &str = "maven.cancelled";
+
+// Identifies a span within a trace, as carried between services by the W3C traceparent header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    sampled: bool
+}
+
+impl TraceContext {
+    // None if the header is missing or malformed, in which case a new trace is started
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        Self::parse(headers.get(TRACEPARENT)?.to_str().ok()?)
+    }
+
+    // version-trace_id-parent_id-flags, in lowercase hex. Later versions may append fields,
+    // which are ignored
+    fn parse(traceparent: &str) -> Option<Self> {
+        let fields: Vec<&str> = traceparent.trim().split('-').collect();
+        let (version, trace_id, span_id, flags) = match fields[..] {
+            [version, trace_id, span_id, flags, ..] => (version, trace_id, span_id, flags),
+            _ => return None
+        };
+        let is_hex = |field: &str, length: usize| {
+            field.len() == length && field.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && fields.len() != 4)
+            || !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        (trace_id != 0 && span_id != 0).then_some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1
+        })
+    }
+
+    fn new_trace() -> Self {
+        Self {
+            trace_id: rand::thread_rng().gen_range(1..=u128::MAX),
+            span_id: new_span_id(),
+            sampled: true
+        }
+    }
+
+    // A new span in the same trace
+    fn child(&self) -> Self {
+        Self {
+            span_id: new_span_id(),
+            ..*self
+        }
+    }
+
+    pub fn trace_id(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    pub fn span_id(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        let traceparent = format!("00-{}-{}-{:02x}", self.trace_id(), self.span_id(), u8::from(self.sampled));
+        HeaderValue::from_str(&traceparent).expect("Hex digits are a valid header value")
+    }
+}
+
+fn new_span_id() -> u64 {
+    rand::thread_rng().gen_range(1..=u64::MAX)
+}
+
+// Whether a span is the proxy answering a client, or the proxy asking a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Server,
+    Client
+}
+
+// A finished span, as handed to the exporter
+#[derive(Debug, Clone)]
+pub struct SpanData {
+    name: &'static str,
+    kind: SpanKind,
+    context: TraceContext,
+    parent_span_id: Option<u64>,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+    error: bool
+}
+
+impl SpanData {
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn kind(&self) -> SpanKind {
+        self.kind
+    }
+
+    pub fn context(&self) -> TraceContext {
+        self.context
+    }
+
+    pub fn parent_span_id(&self) -> Option<String> {
+        self.parent_span_id.map(|span_id| format!("{:016x}", span_id))
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(name, _)| *name == key).map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.error
+    }
+
+    // A span in the OTLP JSON encoding
+    fn to_otlp(&self) -> serde_json::Value {
+        let nanos = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+        let attributes: Vec<serde_json::Value> = self.attributes.iter()
+            .map(|(key, value)| serde_json::json!({
+                "key": key,
+                "value": {
+                    "stringValue": value
+                }
+            }))
+            .collect();
+        let mut span = serde_json::json!({
+            "traceId": self.context.trace_id(),
+            "spanId": self.context.span_id(),
+            "name": self.name,
+            // SPAN_KIND_SERVER and SPAN_KIND_CLIENT
+            "kind": match self.kind {
+                SpanKind::Server => 2,
+                SpanKind::Client => 3
+            },
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(self.end),
+            "attributes": attributes,
+            // STATUS_CODE_UNSET and STATUS_CODE_ERROR
+            "status": {
+                "code": if self.error { 2 } else { 0 }
+            }
+        });
+        if let Some(parent_span_id) = self.parent_span_id() {
+            span["parentSpanId"] = serde_json::json!(parent_span_id);
+        }
+        span
+    }
+}
+
+// The body of an OTLP/HTTP export request for the given spans
+pub fn export_request(spans: &[SpanData]) -> serde_json::Value {
+    serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{
+                    "key": "service.name",
+                    "value": {
+                        "stringValue": "rust-maven-proxy"
+                    }
+                }]
+            },
+            "scopeSpans": [{
+                "scope": {
+                    "name": "rust-maven-proxy",
+                    "version": env!("CARGO_PKG_VERSION")
+                },
+                "spans": spans.iter().map(SpanData::to_otlp).collect::<Vec<_>>()
+            }]
+        }]
+    })
+}
+
+// Somewhere finished spans are sent. Exporting must not wait, since it happens while
+// requests are answered
+pub trait SpanExporter: Send + Sync {
+    fn export(&self, span: SpanData);
+}
+
+// Starts spans, which are exported once they end. Spans of unsampled traces are
+// propagated but not exported
+#[derive(Clone)]
+pub struct Tracer {
+    exporter: Arc<dyn SpanExporter>
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracer").finish_non_exhaustive()
+    }
+}
+
+impl Tracer {
+    pub fn new(exporter: Arc<dyn SpanExporter>) -> Self {
+        Self {
+            exporter
+        }
+    }
+
+    // Continues the client's trace when the request carries a valid traceparent
+    pub fn request_span(&self, name: &'static str, headers: &HeaderMap) -> Span {
+        match TraceContext::from_headers(headers) {
+            Some(parent) => self.start(name, SpanKind::Server, parent.child(), Some(parent.span_id)),
+            None => self.start(name, SpanKind::Server, TraceContext::new_trace(), None)
+        }
+    }
+
+    pub fn child_span(&self, name: &'static str, kind: SpanKind, parent: TraceContext) -> Span {
+        self.start(name, kind, parent.child(), Some(parent.span_id))
+    }
+
+    fn start(&self, name: &'static str, kind: SpanKind, context: TraceContext, parent_span_id: Option<u64>) -> Span {
+        let start = SystemTime::now();
+        Span {
+            data: Some(SpanData {
+                name,
+                kind,
+                context,
+                parent_span_id,
+                start,
+                end: start,
+                attributes: Vec::new(),
+                error: false
+            }),
+            finished: false,
+            exporter: self.exporter.clone()
+        }
+    }
+}
+
+// A span in progress, which ends when dropped. Spans dropped before being finished, such as
+// attempts abandoned once another repository answered, are marked as cancelled
+pub struct Span {
+    // Taken when the span ends
+    data: Option<SpanData>,
+    finished: bool,
+    exporter: Arc<dyn SpanExporter>
+}
+
+impl Span {
+    pub fn context(&self) -> TraceContext {
+        self.data.as_ref().expect("The span has not ended").context
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<String>) {
+        if let Some(data) = &mut self.data {
+            data.attributes.retain(|(name, _)| *name != key);
+            data.attributes.push((key, value.into()));
+        }
+    }
+
+    pub fn set_error(&mut self) {
+        if let Some(data) = &mut self.data {
+            data.error = true;
+        }
+    }
+
+    // The work the span describes is done, so it ends normally when dropped
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(mut data) = self.data.take() {
+            if data.context.sampled {
+                if !self.finished {
+                    data.attributes.push((CANCELLED, "true".to_owned()));
+                }
+                data.end = SystemTime::now();
+                self.exporter.export(data);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+pub use otlp::OtlpExporter;
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use std::time::Duration;
+    use hyper::{Body, Client, Request, Uri};
+    use hyper::client::connect::Connect;
+    use hyper::header::CONTENT_TYPE;
+    use futures_util::FutureExt;
+    use tokio::sync::mpsc::{self, error::TrySendError};
+    use super::{export_request, SpanData, SpanExporter};
+
+    // Spans waiting to be exported, beyond which new spans are dropped
+    const QUEUE_CAPACITY: usize = 4096;
+    // Spans sent to the collector in a single request
+    const MAX_BATCH: usize = 512;
+    const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    // Sends spans to an OpenTelemetry collector over OTLP/HTTP, using the JSON encoding.
+    // Spans are queued and sent in batches by a background task, so a slow or unreachable
+    // collector never holds up requests
+    #[derive(Debug)]
+    pub struct OtlpExporter {
+        sender: mpsc::Sender<SpanData>
+    }
+
+    impl OtlpExporter {
+        // The endpoint is the collector's traces URL, such as http://localhost:4318/v1/traces.
+        // Must be called within the runtime, which runs the export task
+        pub fn start<C>(client: Client<C>, endpoint: Uri) -> Self
+            where C: Connect + Clone + Send + Sync + 'static {
+
+            let (sender, mut receiver) = mpsc::channel(QUEUE_CAPACITY);
+            tokio::spawn(async move {
+                while let Some(span) = receiver.recv().await {
+                    let mut batch = vec![span];
+                    // Spans which are already waiting are sent together
+                    while batch.len() < MAX_BATCH {
+                        match receiver.recv().now_or_never().flatten() {
+                            Some(span) => batch.push(span),
+                            None => break
+                        }
+                    }
+                    let request = Request::post(endpoint.clone())
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(Body::from(export_request(&batch).to_string()))
+                        .expect("The export request is valid");
+                    match tokio::time::timeout(EXPORT_TIMEOUT, client.request(request)).await {
+                        Ok(Ok(response)) if response.status().is_success() => {},
+                        Ok(Ok(response)) => log::warn!("The trace collector rejected {} spans with status {}",
+                                                       batch.len(), response.status()),
+                        Ok(Err(error)) => log::warn!("Unable to export {} spans: {}", batch.len(), error),
+                        Err(_) => log::warn!("Exporting {} spans timed out after {:?}", batch.len(), EXPORT_TIMEOUT)
+                    }
+                }
+            });
+            Self {
+                sender
+            }
+        }
+    }
+
+    impl SpanExporter for OtlpExporter {
+        fn export(&self, span: SpanData) {
+            if let Err(TrySendError::Full(_)) = self.sender.try_send(span) {
+                log::debug!("Dropping a span, since {} are waiting to be exported", QUEUE_CAPACITY);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::RecordingExporter;
+
+    #[test]
+    fn parse_traceparent() {
+        let context = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", context.trace_id());
+        assert_eq!("00f067aa0ba902b7", context.span_id());
+        assert!(context.sampled);
+        assert_eq!("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", context.header_value());
+        assert!(!TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().sampled);
+        // Later versions may add fields
+        assert!(TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+        for invalid in ["",
+                        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+                        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+                        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+                        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+                        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01"] {
+            assert_eq!(None, TraceContext::parse(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn spans_exported_when_ended() {
+        let exporter = Arc::new(RecordingExporter::default());
+        let tracer = Tracer::new(exporter.clone());
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"));
+        let mut request_span = tracer.request_span("request", &headers);
+        request_span.set_attribute("maven.gav", "/org/example/example.jar");
+        let upstream_span = tracer.child_span("upstream", SpanKind::Client, request_span.context());
+        assert!(exporter.spans().is_empty());
+        drop(upstream_span);
+        drop(request_span);
+
+        let spans = exporter.spans();
+        assert_eq!(vec!["upstream", "request"], spans.iter().map(SpanData::name).collect::<Vec<_>>());
+        assert!(spans.iter().all(|span| span.context().trace_id() == "4bf92f3577b34da6a3ce929d0e0e4736"));
+        assert_eq!(Some("00f067aa0ba902b7".to_owned()), spans[1].parent_span_id());
+        assert_eq!(Some(spans[1].context().span_id()), spans[0].parent_span_id());
+
+        let request = export_request(&spans);
+        let exported = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][1];
+        assert_eq!("request", exported["name"]);
+        assert_eq!(2, exported["kind"]);
+        assert_eq!("00f067aa0ba902b7", exported["parentSpanId"]);
+        assert_eq!("maven.gav", exported["attributes"][0]["key"]);
+        assert_eq!("/org/example/example.jar", exported["attributes"][0]["value"]["stringValue"]);
+    }
+
+    #[test]
+    fn unsampled_traces_not_exported() {
+        let exporter = Arc::new(RecordingExporter::default());
+        let tracer = Tracer::new(exporter.clone());
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"));
+        let span = tracer.request_span("request", &headers);
+        assert_eq!(span.context().header_value().to_str().unwrap().get(..36),
+                   Some("00-4bf92f3577b34da6a3ce929d0e0e4736"));
+        drop(span);
+        assert!(exporter.spans().is_empty());
+    }
+}
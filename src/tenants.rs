@@ -0,0 +1,128 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use hyper::{Body, HeaderMap, Response, StatusCode, http};
+use hyper::header::HeaderName;
+use serde::Serialize;
+use eyre::Result;
+use crate::top_clients::Tally;
+
+// Requests without the tenant header, or with an unusable value, are counted under this name
+pub const UNKNOWN_TENANT: &str = "unknown";
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Serialize)]
+struct TenantsReport<'r> {
+    tenants: BTreeMap<&'r str, Tally>
+}
+
+// Counts requests and bytes per tenant, named by a request header such as X-Team, to attribute
+// usage of a shared proxy. Once full, further tenants are counted as unknown, since clients
+// may send any value
+#[derive(Debug)]
+pub struct TenantStats {
+    header: HeaderName,
+    capacity: usize,
+    tenants: Mutex<HashMap<String, Tally>>
+}
+
+impl TenantStats {
+    pub fn new(header: HeaderName) -> Self {
+        Self::with_capacity(header, CAPACITY)
+    }
+
+    fn with_capacity(header: HeaderName, capacity: usize) -> Self {
+        Self {
+            header,
+            capacity,
+            tenants: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn tenant(&self, headers: &HeaderMap) -> String {
+        headers.get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|tenant| !tenant.is_empty())
+            .unwrap_or(UNKNOWN_TENANT)
+            .to_owned()
+    }
+
+    pub fn record_request(&self, tenant: &str) {
+        self.update(tenant, |tally| tally.requests += 1);
+    }
+
+    pub fn record_bytes(&self, tenant: &str, bytes: usize) {
+        self.update(tenant, |tally| tally.bytes += bytes as u64);
+    }
+
+    fn update(&self, tenant: &str, update: impl FnOnce(&mut Tally)) {
+        let mut tenants = self.tenants.lock().unwrap();
+        let tenant = if tenants.contains_key(tenant) || tenants.len() < self.capacity {
+            tenant
+        } else {
+            UNKNOWN_TENANT
+        };
+        update(tenants.entry(tenant.to_owned()).or_default());
+    }
+
+    pub fn response(&self, version: http::version::Version) -> Result<Response<Body>> {
+        let tenants = self.tenants.lock().unwrap();
+        let report = TenantsReport {
+            tenants: tenants.iter().map(|(tenant, tally)| (tenant.as_str(), *tally)).collect()
+        };
+        Ok(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&report)?))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn tenant_from_header() {
+        let tenants = TenantStats::new(HeaderName::from_static("x-team"));
+        let mut headers = HeaderMap::new();
+        assert_eq!(UNKNOWN_TENANT, tenants.tenant(&headers));
+        headers.insert("X-Team", HeaderValue::from_static(" "));
+        assert_eq!(UNKNOWN_TENANT, tenants.tenant(&headers));
+        headers.insert("X-Team", HeaderValue::from_static("builds"));
+        assert_eq!("builds", tenants.tenant(&headers));
+    }
+
+    #[test]
+    fn bounded_capacity() {
+        let tenants = TenantStats::with_capacity(HeaderName::from_static("x-team"), 2);
+        tenants.record_request("builds");
+        tenants.record_request("releases");
+        tenants.record_request("overflow");
+        tenants.record_bytes("builds", 10);
+        let counted = tenants.tenants.lock().unwrap();
+        assert_eq!(Some(&Tally { requests: 1, bytes: 10 }), counted.get("builds"));
+        assert_eq!(Some(&Tally { requests: 1, bytes: 0 }), counted.get(UNKNOWN_TENANT));
+        assert!(!counted.contains_key("overflow"));
+    }
+}
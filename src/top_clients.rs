@@ -0,0 +1,148 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use hyper::{Body, Response, StatusCode, http};
+use serde::Serialize;
+use eyre::Result;
+
+const CAPACITY: usize = 4096;
+const DEFAULT_TOP: usize = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub requests: u64,
+    pub bytes: u64
+}
+
+#[derive(Debug, Serialize)]
+struct ClientReport {
+    client: String,
+    #[serde(flatten)]
+    tally: Tally
+}
+
+// Counts requests and bytes per client address, to find the busiest clients. Once full,
+// the least busy client makes room for a new one, so memory stays bounded
+#[derive(Debug)]
+pub struct TopClients {
+    capacity: usize,
+    clients: Mutex<HashMap<IpAddr, Tally>>
+}
+
+impl Default for TopClients {
+    fn default() -> Self {
+        Self::with_capacity(CAPACITY)
+    }
+}
+
+impl TopClients {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clients: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn record_request(&self, client: IpAddr) {
+        self.update(client, |tally| tally.requests += 1);
+    }
+
+    pub fn record_bytes(&self, client: IpAddr, bytes: usize) {
+        self.update(client, |tally| tally.bytes += bytes as u64);
+    }
+
+    fn update(&self, client: IpAddr, update: impl FnOnce(&mut Tally)) {
+        let mut clients = self.clients.lock().unwrap();
+        if !clients.contains_key(&client) && clients.len() >= self.capacity {
+            let least_busy = clients.iter()
+                .min_by_key(|(_, tally)| (tally.requests, tally.bytes))
+                .map(|(client, _)| *client);
+            if let Some(least_busy) = least_busy {
+                clients.remove(&least_busy);
+            }
+        }
+        update(clients.entry(client).or_default());
+    }
+
+    // The busiest clients by request count, then by bytes
+    fn top(&self, count: usize) -> Vec<ClientReport> {
+        let clients = self.clients.lock().unwrap();
+        let mut ranked: Vec<(&IpAddr, &Tally)> = clients.iter().collect();
+        ranked.sort_by(|(_, left), (_, right)| {
+            (right.requests, right.bytes).cmp(&(left.requests, left.bytes))
+        });
+        ranked.into_iter()
+            .take(count)
+            .map(|(client, tally)| ClientReport { client: client.to_string(), tally: *tally })
+            .collect()
+    }
+
+    // Reports as many clients as ?top=<count> asks for, or ten
+    pub fn response(&self,
+                    query: Option<&str>,
+                    version: http::version::Version) -> Result<Response<Body>> {
+        let count = query
+            .and_then(|query| url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "top")
+                .and_then(|(_, value)| value.parse().ok()))
+            .unwrap_or(DEFAULT_TOP);
+        Ok(Response::builder()
+            .version(version)
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&self.top(count))?))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranking() {
+        let clients = TopClients::default();
+        let busy = IpAddr::from([10, 0, 0, 1]);
+        let quiet = IpAddr::from([10, 0, 0, 2]);
+        let heavy = IpAddr::from([10, 0, 0, 3]);
+        for _ in 0..3 {
+            clients.record_request(busy);
+        }
+        clients.record_request(quiet);
+        clients.record_request(heavy);
+        clients.record_bytes(heavy, 1000);
+        let top: Vec<String> = clients.top(2).into_iter().map(|report| report.client).collect();
+        assert_eq!(vec!["10.0.0.1", "10.0.0.3"], top);
+        assert_eq!(3, clients.top(10).len());
+    }
+
+    #[test]
+    fn bounded_capacity() {
+        let clients = TopClients::with_capacity(2);
+        let busy = IpAddr::from([10, 0, 0, 1]);
+        clients.record_request(busy);
+        clients.record_request(busy);
+        clients.record_request(IpAddr::from([10, 0, 0, 2]));
+        clients.record_request(IpAddr::from([10, 0, 0, 3]));
+        let top: Vec<String> = clients.top(10).into_iter().map(|report| report.client).collect();
+        assert_eq!(vec!["10.0.0.1", "10.0.0.3"], top);
+    }
+}
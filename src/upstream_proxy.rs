@@ -0,0 +1,136 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use hyper::Uri;
+use hyper_proxy::{Intercept, Proxy, ProxyConnector};
+use std::str::FromStr;
+use std::sync::Arc;
+use rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+use url::Url;
+use eyre::{eyre, Result};
+
+// Proxies through which outbound requests to repositories are sent
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProxySettings {
+    http_proxy: Option<Url>,
+    https_proxy: Option<Url>
+}
+
+impl ProxySettings {
+    // Configured values take precedence over the standard environment variables
+    pub fn resolve(http_proxy: Option<&Url>, https_proxy: Option<&Url>) -> Result<Self> {
+        Self::resolve_with(http_proxy, https_proxy, |name| std::env::var(name).ok())
+    }
+
+    fn resolve_with<E>(http_proxy: Option<&Url>,
+                       https_proxy: Option<&Url>,
+                       env: E) -> Result<Self> where E: Fn(&str) -> Option<String> {
+        let from_env = |names: &[&str]| -> Result<Option<Url>> {
+            names.iter()
+                .filter_map(|name| env(name).filter(|value| !value.is_empty()).map(|value| (name, value)))
+                .map(|(name, value)| Url::parse(&value).map_err(|error| {
+                    eyre!("Invalid proxy URL {:?} in {}: {}", value, name, error)
+                }))
+                .next()
+                .transpose()
+        };
+        let http_proxy = match http_proxy {
+            Some(url) => Some(url.clone()),
+            None => from_env(&["HTTP_PROXY", "http_proxy"])?
+        };
+        let https_proxy = match https_proxy {
+            Some(url) => Some(url.clone()),
+            None => from_env(&["HTTPS_PROXY", "https_proxy"])?
+        };
+        Ok(Self {
+            http_proxy,
+            https_proxy
+        })
+    }
+
+    // Without any proxies, the connector passes connections through unchanged.
+    // The TLS configuration is used for HTTPS connections tunneled through a proxy
+    pub fn connector<C>(&self, connector: C, tls_config: Arc<ClientConfig>) -> Result<ProxyConnector<C>> {
+        let mut proxy_connector = ProxyConnector::unsecured(connector);
+        proxy_connector.set_tls(Some(TlsConnector::from(tls_config)));
+        let proxies = [(Intercept::Http, &self.http_proxy), (Intercept::Https, &self.https_proxy)];
+        for (intercept, url) in proxies {
+            if let Some(url) = url {
+                let uri = Uri::from_str(url.as_str())
+                    .map_err(|error| eyre!("Invalid proxy URL {}: {}", url, error))?;
+                log::info!("Using proxy {} for {:?} requests", url, intercept);
+                proxy_connector.add_proxy(Proxy::new(intercept, uri));
+            }
+        }
+        Ok(proxy_connector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use hyper::client::HttpConnector;
+
+    fn resolve(http_proxy: Option<&str>,
+               https_proxy: Option<&str>,
+               env: &[(&str, &str)]) -> Result<ProxySettings> {
+        let env: HashMap<String, String> = env.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let http_proxy = http_proxy.map(Url::parse).transpose()?;
+        let https_proxy = https_proxy.map(Url::parse).transpose()?;
+        ProxySettings::resolve_with(http_proxy.as_ref(), https_proxy.as_ref(), |name| env.get(name).cloned())
+    }
+
+    fn tls_config() -> Arc<ClientConfig> {
+        Arc::new(ClientConfig::new())
+    }
+
+    #[test]
+    fn no_proxies() -> Result<()> {
+        let settings = resolve(None, None, &[])?;
+        assert_eq!(ProxySettings::default(), settings);
+        assert!(settings.connector(HttpConnector::new(), tls_config())?.proxies().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn config_overrides_env() -> Result<()> {
+        let settings = resolve(Some("http://config-proxy:3128"), None, &[
+            ("HTTP_PROXY", "http://env-proxy:3128"),
+            ("https_proxy", "http://secure-proxy:3129")
+        ])?;
+        let connector = settings.connector(HttpConnector::new(), tls_config())?;
+        let proxies = connector.proxies();
+        assert_eq!(2, proxies.len());
+        assert!(matches!(proxies[0].intercept(), Intercept::Http));
+        assert_eq!("http://config-proxy:3128/", proxies[0].uri().to_string());
+        assert!(matches!(proxies[1].intercept(), Intercept::Https));
+        assert_eq!("http://secure-proxy:3129/", proxies[1].uri().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_env_proxy() {
+        resolve(None, None, &[("HTTPS_PROXY", "not a url")])
+            .expect_err("Proxy URL is invalid");
+    }
+}
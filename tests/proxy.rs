@@ -0,0 +1,108 @@
+/*
+ * rust-maven-proxy
+ * Copyright © 2021 SolarMC Developers
+ *
+ * rust-maven-proxy is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * rust-maven-proxy is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with rust-maven-proxy. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU Affero General Public License.
+ */
+
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::time::Duration;
+use eyre::Result;
+use hyper::{Body, Client, Request, Response, Server, StatusCode, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use rust_maven_proxy::{Application, Config, Repository};
+use tempfile::tempdir;
+use tokio::sync::oneshot;
+
+// Serves a fixed artifact at a single path, returning the repository base URI
+async fn upstream_repository() -> Result<Uri> {
+    let service_function = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+            let response = if request.uri().path() == "/maven2/org/example/example/1.0/example-1.0.pom" {
+                Response::new(Body::from("<project/>"))
+            } else {
+                Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap()
+            };
+            Ok::<_, Infallible>(response)
+        }))
+    });
+    let server = Server::try_bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?.serve(service_function);
+    let address = server.local_addr();
+    tokio::spawn(server);
+    Ok(Uri::from_str(&format!("http://{}/maven2", address))?)
+}
+
+fn ephemeral_socket() -> Result<SocketAddr> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?)
+}
+
+async fn get(uri: String) -> Result<(StatusCode, String)> {
+    // The proxy may not have started listening yet
+    let mut attempts = 0;
+    let response = loop {
+        match Client::new().get(Uri::from_str(&uri)?).await {
+            Ok(response) => break response,
+            Err(error) if error.is_connect() && attempts < 50 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            },
+            Err(error) => return Err(error.into())
+        }
+    };
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    Ok((status, String::from_utf8(body.to_vec())?))
+}
+
+#[tokio::test]
+async fn proxy_through_running_server() -> Result<()> {
+    let upstream: Repository = upstream_repository().await?.into();
+    let application = Application::new(Client::new(), vec![upstream], Duration::from_secs(5));
+    let socket = ephemeral_socket()?;
+    let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(application.start_on(socket, async move {
+        let _ = shutdown_signal.await;
+    }));
+
+    let (status, body) = get(format!("http://{}/org/example/example/1.0/example-1.0.pom", socket)).await?;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("<project/>", body);
+    let (status, _) = get(format!("http://{}/org/example/example/2.0/example-2.0.pom", socket)).await?;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+
+    let _ = shutdown.send(());
+    server.await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn application_from_config_file() -> Result<()> {
+    let upstream = upstream_repository().await?;
+    let temp_dir = tempdir()?;
+    let config_path = temp_dir.path().join("config.ron");
+    std::fs::write(&config_path, format!("(repositories: [\"{}\"])", upstream))?;
+    let config = Config::load_from(&config_path)?;
+    config.validate()?;
+
+    let application = Application::new(Client::new(), config.repositories()?, config.proxy_timeout());
+    let socket = ephemeral_socket()?;
+    tokio::spawn(application.start_on(socket, futures_util::future::pending()));
+    let (status, body) = get(format!("http://{}/org/example/example/1.0/example-1.0.pom", socket)).await?;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("<project/>", body);
+    Ok(())
+}